@@ -0,0 +1,63 @@
+//! Account decoding, plus RPC fetch helpers behind the `rpc` feature.
+
+use anchor_lang::{AccountDeserialize, Result};
+use sakura_fee_router::Subscription;
+
+/// Decodes a router account, checking its discriminator
+pub fn decode<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    let mut data = data;
+    T::try_deserialize(&mut data)
+}
+
+/// Whether `subscription` is not revoked and still paid up at unix time `now`,
+/// allowing `grace_seconds` past its expiry; `assert_active` applies it with
+/// the instance's `grace_period`
+pub fn is_active(subscription: &Subscription, now: i64, grace_seconds: i64) -> bool {
+    subscription.status() != Subscription::STATUS_REVOKED
+        && sakura_fee_router_state::is_active(
+            subscription
+                .expires_at()
+                .saturating_add(grace_seconds.max(0)),
+            now,
+        )
+}
+
+#[cfg(feature = "rpc")]
+pub mod rpc {
+    use std::error::Error;
+
+    use anchor_lang::prelude::Pubkey;
+    use anchor_lang::{AccountDeserialize, Discriminator};
+    use solana_client::rpc_client::RpcClient;
+    use solana_client::rpc_config::RpcProgramAccountsConfig;
+    use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+
+    pub type FetchResult<T> = std::result::Result<T, Box<dyn Error>>;
+
+    pub fn fetch<T: AccountDeserialize>(client: &RpcClient, key: &Pubkey) -> FetchResult<T> {
+        let data = client.get_account_data(key)?;
+        Ok(super::decode(&data)?)
+    }
+
+    /// Every router account of type `T`, selected by discriminator
+    pub fn fetch_all<T: AccountDeserialize + Discriminator>(
+        client: &RpcClient,
+    ) -> FetchResult<Vec<(Pubkey, T)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                0,
+                T::DISCRIMINATOR.to_vec(),
+            ))]),
+            ..Default::default()
+        };
+        let accounts = client.get_program_accounts_with_config(&sakura_fee_router::ID, config)?;
+        Ok(accounts
+            .into_iter()
+            .filter_map(|(key, account)| {
+                super::decode(&account.data)
+                    .ok()
+                    .map(|decoded| (key, decoded))
+            })
+            .collect())
+    }
+}
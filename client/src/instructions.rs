@@ -0,0 +1,1698 @@
+//! Instruction builders. Each takes the instance's config address plus its
+//! decoded `Config` so the mint and vault accounts can be filled in.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+};
+use anchor_spl::{memo, token, token_2022};
+use sakura_fee_router::{
+    accounts, instruction, AutoRenew, BurnMode, Config, Incident, Invoice, LoyaltyTier, OrgAccount,
+    Organization, PriceQuote, Product, ProductKind, Referrer, RegistryEntryArgs, Route,
+    RoutingTable, Slab, VaultKind, BUBBLEGUM_PROGRAM_ID, ID, STATS_SHARD_COUNT,
+};
+
+use crate::pda;
+
+/// A held NFT from a collection whitelisted with `set_nft_discount`
+pub struct NftClaim {
+    pub collection: Pubkey,
+    pub token_account: Pubkey,
+    pub mint: Pubkey,
+}
+
+impl NftClaim {
+    fn accounts(claim: Option<&NftClaim>, config_key: &Pubkey) -> [Option<Pubkey>; 3] {
+        match claim {
+            Some(claim) => [
+                Some(pda::nft_discount(config_key, &claim.collection).0),
+                Some(claim.token_account),
+                Some(pda::nft_metadata(&claim.mint).0),
+            ],
+            None => [None; 3],
+        }
+    }
+}
+
+/// The instance's Bubblegum receipt tree, with the noop and compression
+/// programs it was created with
+pub struct CompressedReceipt {
+    pub merkle_tree: Pubkey,
+    pub log_wrapper: Pubkey,
+    pub compression_program: Pubkey,
+}
+
+/// How a payment leaves a receipt: a [`pda::receipt`] account seeded by the
+/// payer's current payment count, or a compressed NFT minted to the payer.
+/// Receipt accounts can't be left in light mode.
+pub enum PaymentReceipt<'a> {
+    Account { payment_index: u64 },
+    Compressed(&'a CompressedReceipt),
+}
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Whether a payment can burn, and so needs the mint, under either split
+fn may_burn(config: &Config) -> bool {
+    config.burn_bps > 0 || config.split_promo_burn_bps > 0
+}
+
+fn instructions_sysvar() -> Pubkey {
+    anchor_lang::solana_program::sysvar::instructions::ID
+}
+
+/// Pays `amount` of the instance mint from `user_token_account`.
+/// The memo program is only attached when a memo is given. `token_program`
+/// is whichever token program owns the instance mint. Pass a `receipt` to
+/// leave one, see [`PaymentReceipt`].
+/// `light_mode` leaves the stats shard and user stats out of the transaction.
+/// On quoted instances pass the signed `quote` and precede the payment with
+/// the authority's ed25519 verification of [`PriceQuote::message`]. Pass an
+/// `nft` to claim its collection's discount, a `plan` id to pay for that plan,
+/// and a `promo` code hash to redeem that discount code. USD plans also need
+/// a fresh Pyth `price_update` for the instance feed.
+/// Pass a `beneficiary` to gift the period to their subscription instead, and
+/// the payment's `referrer` to pay it the instance's referral share. Instances
+/// that route through a routing table must pass it as `routing_table`, and a
+/// `slab` insures that registered Percolator slab's vault. Pass
+/// `soulbound` to mint the subscriber a soulbound receipt token if they don't
+/// hold one. A `reference` records an external id such as an invoice number;
+/// paying the same reference twice from one wallet fails.
+#[allow(clippy::too_many_arguments)]
+pub fn process_payment(
+    config_key: &Pubkey,
+    config: &Config,
+    token_program: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    amount: u64,
+    memo: Option<String>,
+    receipt: Option<PaymentReceipt>,
+    light_mode: bool,
+    quote: Option<PriceQuote>,
+    nft: Option<&NftClaim>,
+    plan: Option<u64>,
+    promo: Option<&[u8; 32]>,
+    price_update: Option<&Pubkey>,
+    beneficiary: Option<&Pubkey>,
+    referrer: Option<&Referrer>,
+    routing_table: Option<&RoutingTable>,
+    slab: Option<&Slab>,
+    soulbound: bool,
+    reference: Option<[u8; 32]>,
+) -> Instruction {
+    let [nft_discount, nft_token_account, nft_metadata] = NftClaim::accounts(nft, config_key);
+    let memo_program = memo.as_ref().map(|_| memo::ID);
+    let subscription = pda::subscription(config_key, beneficiary.unwrap_or(user)).0;
+    let soulbound_mint = soulbound.then(|| pda::soulbound_mint(config_key).0);
+    let (receipt, compressed) = match receipt {
+        Some(PaymentReceipt::Account { payment_index }) => {
+            (Some(pda::receipt(config_key, user, payment_index).0), None)
+        }
+        Some(PaymentReceipt::Compressed(compressed)) => (None, Some(compressed)),
+        None => (None, None),
+    };
+    let burns = routing_table.map_or(may_burn(config), |routing_table| {
+        routing_table.routes().iter().any(Route::burns)
+    });
+    let mut ix = build(
+        accounts::ProcessPayment {
+            user: *user,
+            config: *config_key,
+            user_token_account: *user_token_account,
+            insurance_vault: slab
+                .map_or(config.insurance_destination(), |slab| slab.insurance_vault),
+            slab_registry: slab.map(|_| pda::slab_registry(config_key).0),
+            mint: ((burns && config.burn_flush_threshold == 0) || price_update.is_some())
+                .then_some(config.mint),
+            pending_burn_vault: (config.burn_flush_threshold > 0)
+                .then_some(config.pending_burn_vault),
+            routing_table: routing_table.map(|_| pda::routing_table(config_key).0),
+            beneficiary: beneficiary.copied(),
+            subscription,
+            stats_shard: (!light_mode).then(|| pda::stats_shard_for(config_key, user).0),
+            user_stats: (!light_mode).then(|| pda::user_stats(config_key, user).0),
+            attestation: config
+                .require_jurisdiction_attestation
+                .then(|| pda::attestation(config_key, user).0),
+            plan: plan.map(|plan_id| pda::plan(config_key, plan_id).0),
+            promo: promo.map(|code_hash| pda::promo(config_key, code_hash).0),
+            price_update: price_update.copied(),
+            nft_discount,
+            nft_token_account,
+            nft_metadata,
+            referrer: referrer.map(|referrer| pda::referrer(config_key, &referrer.authority).0),
+            referrer_token_account: referrer.map(|referrer| referrer.token_account),
+            receipt,
+            staking_vault: matches!(config.burn_mode, BurnMode::SplitWithStaking { .. })
+                .then_some(config.staking_vault),
+            payment_reference: reference
+                .map(|reference| pda::payment_reference(config_key, user, &reference).0),
+            receipt_tree: compressed.map(|_| pda::receipt_tree(config_key).0),
+            merkle_tree: compressed.map(|c| c.merkle_tree),
+            tree_config: compressed.map(|c| pda::bubblegum_tree_config(&c.merkle_tree).0),
+            tree_delegate: compressed.map(|_| pda::mint_authority(config_key).0),
+            bubblegum_program: compressed.map(|_| BUBBLEGUM_PROGRAM_ID),
+            log_wrapper: compressed.map(|c| c.log_wrapper),
+            compression_program: compressed.map(|c| c.compression_program),
+            soulbound_mint,
+            soulbound_account: soulbound_mint.map(|mint| {
+                get_associated_token_address_with_program_id(
+                    beneficiary.unwrap_or(user),
+                    &mint,
+                    &token_2022::ID,
+                )
+            }),
+            soulbound_authority: soulbound.then(|| pda::mint_authority(config_key).0),
+            token_2022_program: soulbound.then_some(token_2022::ID),
+            associated_token_program: soulbound.then_some(anchor_spl::associated_token::ID),
+            instructions: instructions_sysvar(),
+            token_program: *token_program,
+            system_program: system_program::ID,
+            memo_program,
+        },
+        instruction::ProcessPayment {
+            amount,
+            memo,
+            light_mode,
+            quote,
+            slab_id: slab.map(|slab| slab.slab_id),
+            reference,
+        },
+    );
+    for route in routing_table
+        .iter()
+        .flat_map(|routing_table| routing_table.routes())
+    {
+        if !route.burns() {
+            ix.accounts.push(AccountMeta::new(route.destination, false));
+        }
+    }
+    ix
+}
+
+/// Pre-flight for [`process_payment`] with the same arguments; simulate it to
+/// learn why a payment would fail. `user` doesn't need to sign. Pass
+/// `first_payment` when `user` has no subscription or user stats yet.
+#[allow(clippy::too_many_arguments)]
+pub fn validate_payment(
+    config_key: &Pubkey,
+    config: &Config,
+    token_program: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    amount: u64,
+    light_mode: bool,
+    quote: Option<PriceQuote>,
+    nft: Option<&NftClaim>,
+    plan: Option<u64>,
+    promo: Option<&[u8; 32]>,
+    price_update: Option<&Pubkey>,
+    first_payment: bool,
+) -> Instruction {
+    let [nft_discount, nft_token_account, nft_metadata] = NftClaim::accounts(nft, config_key);
+    build(
+        accounts::ValidatePayment {
+            user: *user,
+            config: *config_key,
+            user_token_account: *user_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: (may_burn(config) || price_update.is_some()).then_some(config.mint),
+            subscription: (!first_payment).then(|| pda::subscription(config_key, user).0),
+            user_stats: (!first_payment).then(|| pda::user_stats(config_key, user).0),
+            attestation: config
+                .require_jurisdiction_attestation
+                .then(|| pda::attestation(config_key, user).0),
+            plan: plan.map(|plan_id| pda::plan(config_key, plan_id).0),
+            promo: promo.map(|code_hash| pda::promo(config_key, code_hash).0),
+            price_update: price_update.copied(),
+            nft_discount,
+            nft_token_account,
+            nft_metadata,
+            instructions: instructions_sysvar(),
+            token_program: *token_program,
+        },
+        instruction::ValidatePayment {
+            amount,
+            light_mode,
+            quote,
+        },
+    )
+}
+
+/// Pays `amount` as the router delegate. Precede it with an approve of
+/// [`pda::pay_delegate`] for `amount` on `user_token_account`, e.g. for CPI-guarded accounts.
+pub fn process_delegated_payment(
+    config_key: &Pubkey,
+    config: &Config,
+    token_program: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    amount: u64,
+    light_mode: bool,
+) -> Instruction {
+    build(
+        accounts::ProcessDelegatedPayment {
+            user: *user,
+            config: *config_key,
+            user_token_account: *user_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: may_burn(config).then_some(config.mint),
+            delegate: pda::pay_delegate(config_key).0,
+            subscription: pda::subscription(config_key, user).0,
+            stats_shard: (!light_mode).then(|| pda::stats_shard_for(config_key, user).0),
+            user_stats: (!light_mode).then(|| pda::user_stats(config_key, user).0),
+            attestation: config
+                .require_jurisdiction_attestation
+                .then(|| pda::attestation(config_key, user).0),
+            instructions: instructions_sysvar(),
+            token_program: *token_program,
+            system_program: system_program::ID,
+        },
+        instruction::ProcessDelegatedPayment { amount, light_mode },
+    )
+}
+
+/// The instruction an allowlisted `caller_program` invokes to pay `amount`
+/// for `user` out of `caller_token_account`, owned by its CPI authority PDA
+#[allow(clippy::too_many_arguments)]
+pub fn process_payment_via_cpi(
+    config_key: &Pubkey,
+    config: &Config,
+    token_program: &Pubkey,
+    caller_program: &Pubkey,
+    payer: &Pubkey,
+    user: &Pubkey,
+    caller_token_account: &Pubkey,
+    amount: u64,
+    light_mode: bool,
+) -> Instruction {
+    build(
+        accounts::ProcessPaymentViaCpi {
+            payer: *payer,
+            caller_authority: pda::cpi_authority(config_key, caller_program).0,
+            caller_program: *caller_program,
+            config: *config_key,
+            user: *user,
+            caller_token_account: *caller_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: may_burn(config).then_some(config.mint),
+            subscription: pda::subscription(config_key, user).0,
+            stats_shard: (!light_mode).then(|| pda::stats_shard_for(config_key, user).0),
+            user_stats: (!light_mode).then(|| pda::user_stats(config_key, user).0),
+            attestation: config
+                .require_jurisdiction_attestation
+                .then(|| pda::attestation(config_key, user).0),
+            instructions: instructions_sysvar(),
+            token_program: *token_program,
+            system_program: system_program::ID,
+        },
+        instruction::ProcessPaymentViaCpi { amount, light_mode },
+    )
+}
+
+/// Rebuilds the aggregate stats from every shard; all of them must exist
+pub fn aggregate_stats(config_key: &Pubkey) -> Instruction {
+    let mut ix = build(
+        accounts::AggregateStats {
+            config: *config_key,
+            stats: pda::stats(config_key).0,
+        },
+        instruction::AggregateStats {},
+    );
+    ix.accounts.extend(
+        (0..STATS_SHARD_COUNT)
+            .map(|index| AccountMeta::new_readonly(pda::stats_shard(config_key, index).0, false)),
+    );
+    ix
+}
+
+/// Switches the instance to its scheduled successor mint once the cutover passes
+pub fn complete_mint_migration(config_key: &Pubkey) -> Instruction {
+    build(
+        accounts::CompleteMintMigration {
+            config: *config_key,
+        },
+        instruction::CompleteMintMigration {},
+    )
+}
+
+/// Fails unless `user` holds a live, unrevoked subscription; partner programs
+/// can CPI the same instruction as a gate
+pub fn assert_subscription_active(config_key: &Pubkey, user: &Pubkey) -> Instruction {
+    build(
+        accounts::AssertSubscriptionActive {
+            config: *config_key,
+            user: *user,
+            subscription: pda::subscription(config_key, user).0,
+        },
+        instruction::AssertSubscriptionActive {},
+    )
+}
+
+/// Fails unless `user`'s subscription is active within the instance's grace
+/// period past its expiry; the return data holds the expiry
+pub fn assert_active(config_key: &Pubkey, user: &Pubkey) -> Instruction {
+    build(
+        accounts::AssertSubscriptionActive {
+            config: *config_key,
+            user: *user,
+            subscription: pda::subscription(config_key, user).0,
+        },
+        instruction::AssertActive {},
+    )
+}
+
+/// Returns `user`'s Active / Grace / Expired state as return data
+pub fn subscription_state(config_key: &Pubkey, user: &Pubkey) -> Instruction {
+    build(
+        accounts::AssertSubscriptionActive {
+            config: *config_key,
+            user: *user,
+            subscription: pda::subscription(config_key, user).0,
+        },
+        instruction::SubscriptionState {},
+    )
+}
+
+/// Closes `user`'s expired subscription, returning its rent to them
+pub fn close_subscription(config_key: &Pubkey, user: &Pubkey) -> Instruction {
+    build(
+        accounts::CloseSubscription {
+            user: *user,
+            config: *config_key,
+            subscription: pda::subscription(config_key, user).0,
+        },
+        instruction::CloseSubscription {},
+    )
+}
+
+/// Closes `user`'s subscription once it is past the close grace window,
+/// paying `cranker` the rent bounty
+pub fn reclaim_subscription(config_key: &Pubkey, cranker: &Pubkey, user: &Pubkey) -> Instruction {
+    build(
+        accounts::ReclaimSubscription {
+            cranker: *cranker,
+            config: *config_key,
+            user: *user,
+            subscription: pda::subscription(config_key, user).0,
+        },
+        instruction::ReclaimSubscription {},
+    )
+}
+
+/// Offers `user`'s remaining subscription time to `to`
+pub fn initiate_transfer(config_key: &Pubkey, user: &Pubkey, to: Pubkey) -> Instruction {
+    build(
+        accounts::InitiateTransfer {
+            user: *user,
+            config: *config_key,
+            subscription: pda::subscription(config_key, user).0,
+            subscription_transfer: pda::subscription_transfer(config_key, user).0,
+            system_program: system_program::ID,
+        },
+        instruction::InitiateTransfer { to },
+    )
+}
+
+/// Withdraws `user`'s pending transfer offer
+pub fn cancel_transfer(config_key: &Pubkey, user: &Pubkey) -> Instruction {
+    build(
+        accounts::CancelTransfer {
+            user: *user,
+            config: *config_key,
+            subscription_transfer: pda::subscription_transfer(config_key, user).0,
+        },
+        instruction::CancelTransfer {},
+    )
+}
+
+/// Accepts `from`'s offer, signed by the recipient `to`
+pub fn accept_transfer(config_key: &Pubkey, from: &Pubkey, to: &Pubkey) -> Instruction {
+    build(
+        accounts::AcceptTransfer {
+            to: *to,
+            config: *config_key,
+            from: *from,
+            subscription_transfer: pda::subscription_transfer(config_key, from).0,
+            from_subscription: pda::subscription(config_key, from).0,
+            to_subscription: pda::subscription(config_key, to).0,
+            system_program: system_program::ID,
+        },
+        instruction::AcceptTransfer {},
+    )
+}
+
+/// Admin-only: moves `from`'s remaining time to `to` without their signatures
+pub fn admin_transfer_subscription(
+    config_key: &Pubkey,
+    config: &Config,
+    from: &Pubkey,
+    to: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::AdminTransferSubscription {
+            admin: config.admin,
+            config: *config_key,
+            from: *from,
+            to: *to,
+            from_subscription: pda::subscription(config_key, from).0,
+            to_subscription: pda::subscription(config_key, to).0,
+            system_program: system_program::ID,
+        },
+        instruction::AdminTransferSubscription {},
+    )
+}
+
+/// Fails unless `user`'s loyalty tier is at least `min_tier`
+pub fn assert_loyalty_tier(
+    config_key: &Pubkey,
+    user: &Pubkey,
+    min_tier: LoyaltyTier,
+) -> Instruction {
+    build(
+        accounts::AssertLoyaltyTier {
+            config: *config_key,
+            user: *user,
+            user_stats: pda::user_stats(config_key, user).0,
+        },
+        instruction::AssertLoyaltyTier { min_tier },
+    )
+}
+
+/// Mints badge `badge_id` to `user` once their lifetime burn has reached its threshold
+pub fn claim_badge(config_key: &Pubkey, user: &Pubkey, badge_id: u8) -> Instruction {
+    let badge = pda::badge(config_key, badge_id).0;
+    let badge_mint = pda::badge_mint(&badge, user).0;
+    build(
+        accounts::ClaimBadge {
+            user: *user,
+            config: *config_key,
+            badge,
+            user_stats: pda::user_stats(config_key, user).0,
+            mint_authority: pda::mint_authority(config_key).0,
+            badge_mint,
+            user_badge_account: get_associated_token_address_with_program_id(
+                user,
+                &badge_mint,
+                &token_2022::ID,
+            ),
+            token_program: token_2022::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ClaimBadge {},
+    )
+}
+
+/// Burns `holder`'s soulbound receipt token once their subscription has lapsed;
+/// anyone can send it
+pub fn revoke_expired_receipt(config_key: &Pubkey, holder: &Pubkey) -> Instruction {
+    let soulbound_mint = pda::soulbound_mint(config_key).0;
+    build(
+        accounts::RevokeExpiredReceipt {
+            config: *config_key,
+            holder: *holder,
+            subscription: pda::subscription(config_key, holder).0,
+            mint_authority: pda::mint_authority(config_key).0,
+            soulbound_mint,
+            soulbound_account: get_associated_token_address_with_program_id(
+                holder,
+                &soulbound_mint,
+                &token_2022::ID,
+            ),
+            token_program: token_2022::ID,
+        },
+        instruction::RevokeExpiredReceipt {},
+    )
+}
+
+/// Admin-only: revokes `user`'s subscription
+pub fn revoke_subscription(config_key: &Pubkey, config: &Config, user: &Pubkey) -> Instruction {
+    build(
+        accounts::RevokeSubscription {
+            admin: config.admin,
+            config: *config_key,
+            user: *user,
+            subscription: pda::subscription(config_key, user).0,
+        },
+        instruction::RevokeSubscription {},
+    )
+}
+
+/// Pays `amount` of the instance mint for a period of the service `service_id`
+pub fn process_service_payment(
+    config_key: &Pubkey,
+    config: &Config,
+    service_id: u64,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let service = pda::service(config_key, service_id).0;
+    build(
+        accounts::ProcessServicePayment {
+            user: *user,
+            config: *config_key,
+            service,
+            user_token_account: *user_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            subscription: pda::service_subscription(&service, user).0,
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ProcessServicePayment { amount },
+    )
+}
+
+/// Buys catalog product `product_id`, passing the subscription accounts its
+/// kind needs
+pub fn purchase_product(
+    config_key: &Pubkey,
+    config: &Config,
+    product: &Product,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let product_key = pda::product(config_key, product.product_id).0;
+    let subscription = match product.kind {
+        ProductKind::OneTime => None,
+        ProductKind::Subscription | ProductKind::AddOn => {
+            Some(pda::subscription(config_key, user).0)
+        }
+    };
+    let entitlement = (product.kind == ProductKind::AddOn)
+        .then(|| pda::product_subscription(&product_key, user).0);
+    build(
+        accounts::PurchaseProduct {
+            user: *user,
+            config: *config_key,
+            product: product_key,
+            user_token_account: *user_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            subscription,
+            entitlement,
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::PurchaseProduct {
+            product_id: product.product_id,
+            amount,
+        },
+    )
+}
+
+/// Buys `seller`'s subscription listing; `seller_token_account` receives the
+/// price less the instance's resale royalty
+pub fn buy_listing(
+    config_key: &Pubkey,
+    config: &Config,
+    seller: &Pubkey,
+    seller_token_account: &Pubkey,
+    buyer: &Pubkey,
+    buyer_token_account: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::BuyListing {
+            buyer: *buyer,
+            config: *config_key,
+            seller: *seller,
+            listing: pda::listing(config_key, seller).0,
+            buyer_token_account: *buyer_token_account,
+            seller_token_account: *seller_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            subscription: pda::subscription(config_key, buyer).0,
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::BuyListing {},
+    )
+}
+
+/// Pays the current period of `invoice` from the payer's instance-mint account
+pub fn pay_invoice(
+    config_key: &Pubkey,
+    config: &Config,
+    invoice_key: &Pubkey,
+    invoice: &Invoice,
+    payer_token_account: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::PayInvoice {
+            payer: invoice.payer,
+            config: *config_key,
+            service: invoice.service,
+            invoice: *invoice_key,
+            payer_token_account: *payer_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+        },
+        instruction::PayInvoice {},
+    )
+}
+
+/// Buys `quantity` gift cards into the buyer's gift-card token account
+pub fn purchase_gift_cards(
+    config_key: &Pubkey,
+    config: &Config,
+    buyer: &Pubkey,
+    buyer_token_account: &Pubkey,
+    quantity: u64,
+) -> Instruction {
+    let gift_card_mint = pda::gift_card_mint(config_key).0;
+    build(
+        accounts::PurchaseGiftCards {
+            buyer: *buyer,
+            config: *config_key,
+            gift_card: pda::gift_card(config_key).0,
+            gift_card_mint,
+            buyer_card_account: get_associated_token_address(buyer, &gift_card_mint),
+            mint_authority: pda::mint_authority(config_key).0,
+            buyer_token_account: *buyer_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::PurchaseGiftCards { quantity },
+    )
+}
+
+/// Burns `quantity` of the holder's gift cards for subscription time
+pub fn redeem_gift_cards(config_key: &Pubkey, holder: &Pubkey, quantity: u64) -> Instruction {
+    let gift_card_mint = pda::gift_card_mint(config_key).0;
+    build(
+        accounts::RedeemGiftCards {
+            holder: *holder,
+            config: *config_key,
+            gift_card: pda::gift_card(config_key).0,
+            gift_card_mint,
+            holder_card_account: get_associated_token_address(holder, &gift_card_mint),
+            subscription: pda::subscription(config_key, holder).0,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::RedeemGiftCards { quantity },
+    )
+}
+
+/// Prepays `quantity` unassigned passes into batch `batch_id`
+pub fn purchase_passes(
+    config_key: &Pubkey,
+    config: &Config,
+    buyer: &Pubkey,
+    buyer_token_account: &Pubkey,
+    batch_id: u64,
+    quantity: u32,
+    amount: u64,
+) -> Instruction {
+    build(
+        accounts::PurchasePasses {
+            buyer: *buyer,
+            config: *config_key,
+            pass_batch: pda::pass_batch(config_key, buyer, batch_id).0,
+            buyer_token_account: *buyer_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::PurchasePasses {
+            batch_id,
+            quantity,
+            amount,
+        },
+    )
+}
+
+/// Assigns one pass from the buyer's batch `batch_id` to `recipient`
+pub fn assign_pass(
+    config_key: &Pubkey,
+    buyer: &Pubkey,
+    batch_id: u64,
+    recipient: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::AssignPass {
+            buyer: *buyer,
+            config: *config_key,
+            pass_batch: pda::pass_batch(config_key, buyer, batch_id).0,
+            recipient: *recipient,
+            subscription: pda::subscription(config_key, recipient).0,
+            system_program: system_program::ID,
+        },
+        instruction::AssignPass {},
+    )
+}
+
+/// Operator-only: burns the pending-burn vault's balance
+pub fn burn_pending(config_key: &Pubkey, config: &Config) -> Instruction {
+    let vault_authority = pda::vault_authority(config_key, VaultKind::PendingBurn).0;
+    build(
+        accounts::BurnPending {
+            operator: config.operator,
+            config: *config_key,
+            vault_authority,
+            vault: get_associated_token_address(&vault_authority, &config.mint),
+            mint: config.mint,
+            token_program: token::ID,
+        },
+        instruction::BurnPending {},
+    )
+}
+
+/// Burns the batched pending-burn vault once it has reached the instance's
+/// flush threshold; anyone can send it. `token_program` owns the instance mint.
+pub fn flush_burn(config_key: &Pubkey, config: &Config, token_program: &Pubkey) -> Instruction {
+    build(
+        accounts::FlushBurn {
+            config: *config_key,
+            vault_authority: pda::vault_authority(config_key, VaultKind::PendingBurn).0,
+            pending_burn_vault: config.pending_burn_vault,
+            mint: config.mint,
+            token_program: *token_program,
+        },
+        instruction::FlushBurn {},
+    )
+}
+
+/// Moves the insurance escrow on to the insurance vault; anyone can send it.
+/// `token_program` owns the instance mint.
+pub fn sweep_insurance_escrow(
+    config_key: &Pubkey,
+    config: &Config,
+    token_program: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::SweepInsuranceEscrow {
+            config: *config_key,
+            escrow_authority: pda::vault_authority(config_key, VaultKind::InsuranceEscrow).0,
+            insurance_escrow: config.insurance_escrow,
+            insurance_vault: config.insurance_vault,
+            mint: config.mint,
+            token_program: *token_program,
+        },
+        instruction::SweepInsuranceEscrow {},
+    )
+}
+
+/// Admin-only: refunds `amount` of unswept escrow for the payment on
+/// `receipt` to `user`'s associated token account and rolls their
+/// subscription back by the share of time it bought. Pass `with_outbox` to
+/// queue a notice once the instance has an outbox.
+pub fn refund(
+    config_key: &Pubkey,
+    config: &Config,
+    user: &Pubkey,
+    receipt: &Pubkey,
+    token_program: &Pubkey,
+    amount: u64,
+    with_outbox: bool,
+) -> Instruction {
+    build(
+        accounts::Refund {
+            admin: config.admin,
+            config: *config_key,
+            escrow_authority: pda::vault_authority(config_key, VaultKind::InsuranceEscrow).0,
+            insurance_escrow: config.insurance_escrow,
+            user: *user,
+            subscription: pda::subscription(config_key, user).0,
+            receipt: *receipt,
+            user_token_account: get_associated_token_address_with_program_id(
+                user,
+                &config.mint,
+                token_program,
+            ),
+            mint: config.mint,
+            token_program: *token_program,
+            outbox: with_outbox.then(|| pda::outbox(config_key).0),
+        },
+        instruction::Refund { amount },
+    )
+}
+
+/// Operator-only: sweeps the treasury vault for `vault_mint` into `destination`,
+/// a token account owned by the admin
+pub fn sweep_treasury(
+    config_key: &Pubkey,
+    config: &Config,
+    vault_mint: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::SweepTreasury {
+            operator: config.operator,
+            config: *config_key,
+            treasury_authority: pda::vault_authority(config_key, VaultKind::Treasury).0,
+            treasury_vault: pda::program_vault(config_key, VaultKind::Treasury, vault_mint),
+            mint: *vault_mint,
+            destination: *destination,
+            token_program: token::ID,
+        },
+        instruction::SweepTreasury {},
+    )
+}
+
+/// Admin-only: pays `user` the compensation published for `incident`
+pub fn compensate_subscriber(
+    config_key: &Pubkey,
+    config: &Config,
+    incident: &Incident,
+    user: &Pubkey,
+) -> Instruction {
+    let incident_key = pda::incident(config_key, incident.incident_id).0;
+    let treasury_authority = pda::vault_authority(config_key, VaultKind::Treasury).0;
+    build(
+        accounts::CompensateSubscriber {
+            admin: config.admin,
+            config: *config_key,
+            incident: incident_key,
+            user: *user,
+            compensation_claim: pda::compensation_claim(&incident_key, user).0,
+            subscription: pda::subscription(config_key, user).0,
+            treasury_authority,
+            treasury_vault: pda::program_vault(
+                config_key,
+                VaultKind::Treasury,
+                &incident.stable_mint,
+            ),
+            stable_mint: incident.stable_mint,
+            user_stable_account: get_associated_token_address(user, &incident.stable_mint),
+            token_program: token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::CompensateSubscriber {},
+    )
+}
+
+/// Admin-only: proposes `new_admin` as the instance's next admin. The
+/// instruction can be wrapped in a multisig or governance proposal as-is.
+pub fn propose_admin(config_key: &Pubkey, config: &Config, new_admin: Pubkey) -> Instruction {
+    build(
+        accounts::AdminConfig {
+            admin: config.admin,
+            config: *config_key,
+        },
+        instruction::ProposeAdmin { new_admin },
+    )
+}
+
+/// Signed by the proposed admin to take over the instance
+pub fn accept_admin(config_key: &Pubkey, config: &Config) -> Instruction {
+    build(
+        accounts::AcceptAdmin {
+            pending_admin: config.pending_admin,
+            config: *config_key,
+        },
+        instruction::AcceptAdmin {},
+    )
+}
+
+/// Admin-only: replaces the instance split, insurance vault, subscription
+/// duration and base price
+pub fn update_config(
+    config_key: &Pubkey,
+    config: &Config,
+    insurance_vault: &Pubkey,
+    insurance_bps: u16,
+    burn_bps: u16,
+    subscription_duration: i64,
+    price: u64,
+) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            admin: config.admin,
+            config: *config_key,
+            insurance_vault: *insurance_vault,
+            price_feed: pda::price_feed(config_key).0,
+            system_program: system_program::ID,
+        },
+        instruction::UpdateConfig {
+            insurance_bps,
+            burn_bps,
+            subscription_duration,
+            price,
+        },
+    )
+}
+
+/// Admin-only: extends the router subscriptions of `users` by `duration`
+pub fn extend_subscriptions(
+    config_key: &Pubkey,
+    config: &Config,
+    users: &[Pubkey],
+    duration: i64,
+) -> Instruction {
+    let mut ix = build(
+        accounts::ExtendSubscriptions {
+            admin: config.admin,
+            config: *config_key,
+        },
+        instruction::ExtendSubscriptions { duration },
+    );
+    for user in users {
+        ix.accounts.extend([
+            AccountMeta::new_readonly(*user, false),
+            AccountMeta::new(pda::subscription(config_key, user).0, false),
+        ]);
+    }
+    ix
+}
+
+pub fn init_outbox(config_key: &Pubkey, payer: &Pubkey) -> Instruction {
+    build(
+        accounts::InitOutbox {
+            payer: *payer,
+            config: *config_key,
+            outbox: pda::outbox(config_key).0,
+            system_program: system_program::ID,
+        },
+        instruction::InitOutbox {},
+    )
+}
+
+/// Queues an expiring-soon notice for `user`; fails outside the notice window
+pub fn enqueue_expiry_notice(config_key: &Pubkey, user: &Pubkey) -> Instruction {
+    build(
+        accounts::EnqueueExpiryNotice {
+            config: *config_key,
+            outbox: pda::outbox(config_key).0,
+            user: *user,
+            subscription: pda::subscription(config_key, user).0,
+        },
+        instruction::EnqueueExpiryNotice {},
+    )
+}
+
+/// Operator-only: marks outbox entries up to `through_seq` as processed
+pub fn ack_outbox(config_key: &Pubkey, config: &Config, through_seq: u64) -> Instruction {
+    build(
+        accounts::AckOutbox {
+            operator: config.operator,
+            config: *config_key,
+            outbox: pda::outbox(config_key).0,
+        },
+        instruction::AckOutbox { through_seq },
+    )
+}
+
+/// Admin-only: creates page `page` of the registry namespaced by `kind`
+pub fn init_registry_page(config_key: &Pubkey, config: &Config, kind: u8, page: u8) -> Instruction {
+    build(
+        accounts::InitRegistryPage {
+            admin: config.admin,
+            config: *config_key,
+            registry_page: pda::registry_page(config_key, kind, page).0,
+            system_program: system_program::ID,
+        },
+        instruction::InitRegistryPage { kind, page },
+    )
+}
+
+/// Admin-only: inserts or overwrites up to `MAX_REGISTRY_CHUNK` entries on one page
+pub fn upsert_registry_entries(
+    config_key: &Pubkey,
+    config: &Config,
+    registry_page: &Pubkey,
+    entries: Vec<RegistryEntryArgs>,
+) -> Instruction {
+    build(
+        accounts::UpdateRegistryPage {
+            admin: config.admin,
+            config: *config_key,
+            registry_page: *registry_page,
+        },
+        instruction::UpsertRegistryEntries { entries },
+    )
+}
+
+/// Admin-only: removes up to `MAX_REGISTRY_CHUNK` keys from one page
+pub fn remove_registry_entries(
+    config_key: &Pubkey,
+    config: &Config,
+    registry_page: &Pubkey,
+    keys: Vec<Pubkey>,
+) -> Instruction {
+    build(
+        accounts::UpdateRegistryPage {
+            admin: config.admin,
+            config: *config_key,
+            registry_page: *registry_page,
+        },
+        instruction::RemoveRegistryEntries { keys },
+    )
+}
+
+/// Archives `receipts` (at most `MAX_CRANK_BATCH`, each past `RECEIPT_RETENTION`)
+/// into the receipt history and closes them into the treasury
+pub fn archive_receipts(config_key: &Pubkey, receipts: &[Pubkey]) -> Instruction {
+    let mut ix = build(
+        accounts::ArchiveReceipts {
+            config: *config_key,
+            receipt_history: pda::receipt_history(config_key).0,
+            treasury_authority: pda::vault_authority(config_key, VaultKind::Treasury).0,
+        },
+        instruction::ArchiveReceipts {},
+    );
+    ix.accounts.extend(
+        receipts
+            .iter()
+            .map(|receipt| AccountMeta::new(*receipt, false)),
+    );
+    ix
+}
+
+/// Operator-only: moves rent reclaimed from archived receipts to the admin
+pub fn sweep_reclaimed_rent(config_key: &Pubkey, config: &Config) -> Instruction {
+    build(
+        accounts::SweepReclaimedRent {
+            operator: config.operator,
+            config: *config_key,
+            treasury_authority: pda::vault_authority(config_key, VaultKind::Treasury).0,
+            admin: config.admin,
+            system_program: system_program::ID,
+        },
+        instruction::SweepReclaimedRent {},
+    )
+}
+
+/// Pays `amount` of a supported LST (mSOL or jitoSOL) into the instance treasury.
+/// `rate_account` is the Marinade state or stake pool that values `lst_mint`,
+/// `sol_price_update` a Pyth update of the instance's SOL/USD feed.
+pub fn process_lst_payment(
+    config_key: &Pubkey,
+    user: &Pubkey,
+    lst_mint: &Pubkey,
+    rate_account: &Pubkey,
+    sol_price_update: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let treasury_authority = pda::vault_authority(config_key, VaultKind::Treasury).0;
+    build(
+        accounts::ProcessLstPayment {
+            user: *user,
+            config: *config_key,
+            lst_mint: *lst_mint,
+            rate_account: *rate_account,
+            sol_price_update: *sol_price_update,
+            user_lst_account: get_associated_token_address(user, lst_mint),
+            treasury_authority,
+            treasury_vault: get_associated_token_address(&treasury_authority, lst_mint),
+            subscription: pda::subscription(config_key, user).0,
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ProcessLstPayment { amount },
+    )
+}
+
+/// Pays one period in wSOL from `user`'s associated wSOL account.
+/// `light_mode` leaves the stats shard out of the transaction.
+pub fn process_payment_sol(
+    config_key: &Pubkey,
+    config: &Config,
+    user: &Pubkey,
+    amount: u64,
+    light_mode: bool,
+) -> Instruction {
+    let native_mint = token::spl_token::native_mint::ID;
+    let buyback_authority = pda::vault_authority(config_key, VaultKind::Buyback).0;
+    build(
+        accounts::ProcessPaymentSol {
+            user: *user,
+            config: *config_key,
+            native_mint,
+            user_sol_account: get_associated_token_address(user, &native_mint),
+            sol_insurance_vault: config.sol_insurance_vault,
+            buyback_authority,
+            buyback_vault: get_associated_token_address(&buyback_authority, &native_mint),
+            subscription: pda::subscription(config_key, user).0,
+            stats_shard: (!light_mode).then(|| pda::stats_shard_for(config_key, user).0),
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ProcessPaymentSol { amount, light_mode },
+    )
+}
+
+/// Admin-only: prices SOL payments and pins their insurance vault
+pub fn set_sol_payments(
+    config_key: &Pubkey,
+    config: &Config,
+    sol_insurance_vault: &Pubkey,
+    sol_price_lamports: u64,
+) -> Instruction {
+    build(
+        accounts::SetSolPayments {
+            admin: config.admin,
+            config: *config_key,
+            sol_insurance_vault: *sol_insurance_vault,
+        },
+        instruction::SetSolPayments { sol_price_lamports },
+    )
+}
+
+/// Admin-only: swaps escrowed wSOL through `swap_program` and burns the
+/// SAKURA bought. The swap's own accounts go in `swap_accounts`, in the
+/// order `swap_data` expects; the burn is credited to stats shard `shard_index`.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_buyback_burn(
+    config_key: &Pubkey,
+    config: &Config,
+    shard_index: u8,
+    swap_program: &Pubkey,
+    amount_in: u64,
+    min_burn: u64,
+    swap_data: Vec<u8>,
+    swap_accounts: &[AccountMeta],
+) -> Instruction {
+    let buyback_authority = pda::vault_authority(config_key, VaultKind::Buyback).0;
+    let mut ix = build(
+        accounts::ExecuteBuybackBurn {
+            admin: config.admin,
+            config: *config_key,
+            buyback_authority,
+            buyback_sol_vault: get_associated_token_address(
+                &buyback_authority,
+                &token::spl_token::native_mint::ID,
+            ),
+            buyback_vault: get_associated_token_address(&buyback_authority, &config.mint),
+            mint: config.mint,
+            stats_shard: pda::stats_shard(config_key, shard_index).0,
+            swap_program: *swap_program,
+            token_program: token::ID,
+        },
+        instruction::ExecuteBuybackBurn {
+            amount_in,
+            min_burn,
+            swap_data,
+        },
+    );
+    ix.accounts
+        .extend(swap_accounts.iter().map(|meta| AccountMeta {
+            // The program signs for the buyback PDA itself
+            is_signer: meta.is_signer && meta.pubkey != buyback_authority,
+            ..meta.clone()
+        }));
+    ix
+}
+
+/// Brings `user`'s subscription up to the current layout in place. A
+/// pre-packing account refunds its freed rent to them; growing a packed one
+/// to the reserved size needs a `payer` for the extra rent.
+pub fn migrate_subscription(
+    config_key: &Pubkey,
+    user: &Pubkey,
+    payer: Option<&Pubkey>,
+) -> Instruction {
+    build(
+        accounts::MigrateSubscription {
+            config: *config_key,
+            user: *user,
+            subscription: pda::subscription(config_key, user).0,
+            payer: payer.copied(),
+            system_program: payer.map(|_| system_program::ID),
+        },
+        instruction::MigrateSubscription {},
+    )
+}
+
+pub fn migrate_config(config_key: &Pubkey, admin: &Pubkey) -> Instruction {
+    build(
+        accounts::MigrateConfig {
+            admin: *admin,
+            config: *config_key,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateConfig {},
+    )
+}
+
+pub fn register_stream_funding(
+    config_key: &Pubkey,
+    user: &Pubkey,
+    stream: &Pubkey,
+    user_token_account: &Pubkey,
+    amount_per_period: u64,
+    allowance: u64,
+) -> Instruction {
+    build(
+        accounts::RegisterStreamFunding {
+            user: *user,
+            config: *config_key,
+            stream: *stream,
+            stream_funding: pda::stream_funding(config_key, user).0,
+            subscription: pda::subscription(config_key, user).0,
+            user_token_account: *user_token_account,
+            delegate: pda::stream_delegate(config_key).0,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterStreamFunding {
+            amount_per_period,
+            allowance,
+        },
+    )
+}
+
+/// Renews a stream-funded subscription from balance the stream has already
+/// paid out. Streamflow withdraw accounts, if any, go in `remaining_accounts`.
+pub fn crank_stream_funding(
+    config_key: &Pubkey,
+    config: &Config,
+    cranker: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    let stream_program =
+        (!remaining_accounts.is_empty()).then_some(sakura_fee_router::STREAMFLOW_PROGRAM_ID);
+    let mut ix = build(
+        accounts::CrankStreamFunding {
+            cranker: *cranker,
+            config: *config_key,
+            stream_funding: pda::stream_funding(config_key, user).0,
+            subscription: pda::subscription(config_key, user).0,
+            user_token_account: *user_token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            delegate: pda::stream_delegate(config_key).0,
+            stream_program,
+            token_program: token::ID,
+        },
+        instruction::CrankStreamFunding {},
+    );
+    ix.accounts.extend_from_slice(remaining_accounts);
+    ix
+}
+
+/// Locks `amount` from `user`'s associated token account into their
+/// pay-as-you-go stream
+pub fn deposit_stream(
+    config_key: &Pubkey,
+    config: &Config,
+    user: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let payment_stream = pda::payment_stream(config_key, user).0;
+    build(
+        accounts::DepositStream {
+            user: *user,
+            config: *config_key,
+            payment_stream,
+            stream_vault: pda::stream_vault(config_key, user, &config.mint),
+            user_token_account: get_associated_token_address(user, &config.mint),
+            subscription: pda::subscription(config_key, user).0,
+            mint: config.mint,
+            token_program: token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::DepositStream { amount },
+    )
+}
+
+/// Pays the next settle period of `user`'s stream; anyone can send it
+pub fn settle_stream(config_key: &Pubkey, config: &Config, user: &Pubkey) -> Instruction {
+    build(
+        accounts::SettleStream {
+            config: *config_key,
+            user: *user,
+            payment_stream: pda::payment_stream(config_key, user).0,
+            stream_vault: pda::stream_vault(config_key, user, &config.mint),
+            subscription: pda::subscription(config_key, user).0,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            stats_shard: pda::stats_shard_for(config_key, user).0,
+            token_program: token::ID,
+        },
+        instruction::SettleStream {},
+    )
+}
+
+/// Returns the unsettled stream balance to `user`'s associated token account
+pub fn withdraw_stream(config_key: &Pubkey, config: &Config, user: &Pubkey) -> Instruction {
+    let payment_stream = pda::payment_stream(config_key, user).0;
+    build(
+        accounts::WithdrawStream {
+            user: *user,
+            config: *config_key,
+            payment_stream,
+            stream_vault: pda::stream_vault(config_key, user, &config.mint),
+            user_token_account: get_associated_token_address(user, &config.mint),
+            mint: config.mint,
+            token_program: token::ID,
+        },
+        instruction::WithdrawStream {},
+    )
+}
+
+/// Renews each `(user, user_token_account)` stream funding in one transaction,
+/// up to `MAX_CRANK_BATCH` of them
+pub fn crank_stream_funding_batch(
+    config_key: &Pubkey,
+    config: &Config,
+    cranker: &Pubkey,
+    entries: &[(Pubkey, Pubkey)],
+    outbox: bool,
+) -> Instruction {
+    let mut ix = build(
+        accounts::CrankStreamFundingBatch {
+            cranker: *cranker,
+            config: *config_key,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            delegate: pda::stream_delegate(config_key).0,
+            outbox: outbox.then(|| pda::outbox(config_key).0),
+            token_program: token::ID,
+        },
+        instruction::CrankStreamFundingBatch {},
+    );
+    for (user, user_token_account) in entries {
+        ix.accounts.extend([
+            AccountMeta::new(pda::stream_funding(config_key, user).0, false),
+            AccountMeta::new(pda::subscription(config_key, user).0, false),
+            AccountMeta::new(*user_token_account, false),
+        ]);
+    }
+    ix
+}
+
+pub fn cancel_stream_funding(
+    config_key: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::CancelStreamFunding {
+            user: *user,
+            config: *config_key,
+            stream_funding: pda::stream_funding(config_key, user).0,
+            user_token_account: *user_token_account,
+            token_program: token::ID,
+        },
+        instruction::CancelStreamFunding {},
+    )
+}
+
+/// Claims `user`'s one free trial
+pub fn start_trial(config_key: &Pubkey, user: &Pubkey) -> Instruction {
+    build(
+        accounts::StartTrial {
+            user: *user,
+            config: *config_key,
+            trial: pda::trial(config_key, user).0,
+            subscription: pda::subscription(config_key, user).0,
+            system_program: system_program::ID,
+        },
+        instruction::StartTrial {},
+    )
+}
+
+/// Approves `renewals` periods for crank renewal, at `plan`'s price if given
+pub fn enable_auto_renew(
+    config_key: &Pubkey,
+    token_program: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    plan: Option<u64>,
+    renewals: u32,
+) -> Instruction {
+    build(
+        accounts::EnableAutoRenew {
+            user: *user,
+            config: *config_key,
+            auto_renew: pda::auto_renew(config_key, user).0,
+            subscription: pda::subscription(config_key, user).0,
+            plan: plan.map(|plan_id| pda::plan(config_key, plan_id).0),
+            user_token_account: *user_token_account,
+            delegate: pda::renew_delegate(config_key).0,
+            token_program: *token_program,
+            system_program: system_program::ID,
+        },
+        instruction::EnableAutoRenew { renewals },
+    )
+}
+
+/// Renews an expired auto-renew subscription; pass `cranker_token_account`
+/// to collect the renewal bounty
+pub fn crank_renewal(
+    config_key: &Pubkey,
+    config: &Config,
+    token_program: &Pubkey,
+    cranker: &Pubkey,
+    auto_renew: &AutoRenew,
+    cranker_token_account: Option<&Pubkey>,
+) -> Instruction {
+    build(
+        accounts::CrankRenewal {
+            cranker: *cranker,
+            config: *config_key,
+            auto_renew: pda::auto_renew(config_key, &auto_renew.user).0,
+            subscription: pda::subscription(config_key, &auto_renew.user).0,
+            plan: (auto_renew.plan != Pubkey::default()).then_some(auto_renew.plan),
+            user_token_account: auto_renew.token_account,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            cranker_token_account: cranker_token_account.copied(),
+            delegate: pda::renew_delegate(config_key).0,
+            token_program: *token_program,
+        },
+        instruction::CrankRenewal {},
+    )
+}
+
+pub fn disable_auto_renew(
+    config_key: &Pubkey,
+    token_program: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::DisableAutoRenew {
+            user: *user,
+            config: *config_key,
+            auto_renew: pda::auto_renew(config_key, user).0,
+            user_token_account: *user_token_account,
+            token_program: *token_program,
+        },
+        instruction::DisableAutoRenew {},
+    )
+}
+
+pub fn draw_sponsorship(
+    config_key: &Pubkey,
+    config: &Config,
+    wallet: &Pubkey,
+    sponsor: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let sponsor_pool = pda::sponsor_pool(config_key, sponsor, pool_id).0;
+    build(
+        accounts::DrawSponsorship {
+            wallet: *wallet,
+            config: *config_key,
+            sponsor_pool,
+            allowance: pda::sponsor_allowance(&sponsor_pool, wallet).0,
+            pool_vault: get_associated_token_address(&sponsor_pool, &config.mint),
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            subscription: pda::subscription(config_key, wallet).0,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::DrawSponsorship {},
+    )
+}
+
+/// Creates `creator`'s org account and its treasury for the instance mint
+pub fn create_org_account(
+    config_key: &Pubkey,
+    config: &Config,
+    creator: &Pubkey,
+    first_signer: Pubkey,
+    second_signer: Pubkey,
+) -> Instruction {
+    let org_account = pda::org_account(config_key, creator).0;
+    build(
+        accounts::CreateOrgAccount {
+            creator: *creator,
+            config: *config_key,
+            org_account,
+            org_treasury: get_associated_token_address(&org_account, &config.mint),
+            mint: config.mint,
+            token_program: token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::CreateOrgAccount {
+            first_signer,
+            second_signer,
+        },
+    )
+}
+
+/// Pays `amount` from an org treasury for `beneficiary`. Both of the org's
+/// signers must sign; `org` is the decoded org account.
+pub fn process_cosigned_payment(
+    config_key: &Pubkey,
+    config: &Config,
+    org: &OrgAccount,
+    beneficiary: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let org_account = pda::org_account(config_key, &org.creator).0;
+    build(
+        accounts::ProcessCosignedPayment {
+            first_signer: org.first_signer,
+            second_signer: org.second_signer,
+            config: *config_key,
+            org_account,
+            org_treasury: get_associated_token_address(&org_account, &config.mint),
+            beneficiary: *beneficiary,
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            subscription: pda::subscription(config_key, beneficiary).0,
+            stats_shard: pda::stats_shard_for(config_key, &org_account).0,
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ProcessCosignedPayment { amount },
+    )
+}
+
+/// Opens `owner`'s organization, its seats priced at `plan` when given
+pub fn create_organization(
+    config_key: &Pubkey,
+    owner: &Pubkey,
+    plan: Option<&Pubkey>,
+) -> Instruction {
+    build(
+        accounts::CreateOrganization {
+            owner: *owner,
+            config: *config_key,
+            organization: pda::organization(config_key, owner).0,
+            plan: plan.copied(),
+            system_program: system_program::ID,
+        },
+        instruction::CreateOrganization {},
+    )
+}
+
+/// Pays one period for `seats` seats out of the owner's associated token
+/// account; `organization` is the decoded organization account
+pub fn process_org_payment(
+    config_key: &Pubkey,
+    config: &Config,
+    organization: &Organization,
+    seats: u32,
+) -> Instruction {
+    let owner = &organization.owner;
+    build(
+        accounts::ProcessOrgPayment {
+            owner: *owner,
+            config: *config_key,
+            organization: pda::organization(config_key, owner).0,
+            plan: (organization.plan != Pubkey::default()).then_some(organization.plan),
+            owner_token_account: get_associated_token_address(owner, &config.mint),
+            insurance_vault: config.insurance_destination(),
+            mint: config.mint,
+            stats_shard: pda::stats_shard_for(config_key, owner).0,
+            instructions: instructions_sysvar(),
+            token_program: token::ID,
+        },
+        instruction::ProcessOrgPayment { seats },
+    )
+}
+
+/// Owner-only: gives `member` one of the organization's free seats
+pub fn add_member(config_key: &Pubkey, owner: &Pubkey, member: &Pubkey) -> Instruction {
+    let organization = pda::organization(config_key, owner).0;
+    build(
+        accounts::AddMember {
+            owner: *owner,
+            config: *config_key,
+            organization,
+            member: *member,
+            seat: pda::seat(&organization, member).0,
+            system_program: system_program::ID,
+        },
+        instruction::AddMember {},
+    )
+}
+
+/// Owner-only: frees `member`'s seat
+pub fn remove_member(config_key: &Pubkey, owner: &Pubkey, member: &Pubkey) -> Instruction {
+    let organization = pda::organization(config_key, owner).0;
+    build(
+        accounts::RemoveMember {
+            owner: *owner,
+            config: *config_key,
+            organization,
+            seat: pda::seat(&organization, member).0,
+        },
+        instruction::RemoveMember {},
+    )
+}
+
+/// Fails unless `member` holds a seat in `owner`'s paid-up organization
+pub fn assert_seat_active(config_key: &Pubkey, owner: &Pubkey, member: &Pubkey) -> Instruction {
+    let organization = pda::organization(config_key, owner).0;
+    build(
+        accounts::AssertSeatActive {
+            config: *config_key,
+            organization,
+            member: *member,
+            seat: pda::seat(&organization, member).0,
+        },
+        instruction::AssertSeatActive {},
+    )
+}
+
+/// Moves `amount` from an org treasury to `destination`, under both signatures
+pub fn withdraw_org_treasury(
+    config_key: &Pubkey,
+    config: &Config,
+    org: &OrgAccount,
+    destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let org_account = pda::org_account(config_key, &org.creator).0;
+    build(
+        accounts::WithdrawOrgTreasury {
+            first_signer: org.first_signer,
+            second_signer: org.second_signer,
+            config: *config_key,
+            org_account,
+            org_treasury: get_associated_token_address(&org_account, &config.mint),
+            destination: *destination,
+            mint: config.mint,
+            token_program: token::ID,
+        },
+        instruction::WithdrawOrgTreasury { amount },
+    )
+}
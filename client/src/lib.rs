@@ -0,0 +1,21 @@
+//! Lightweight Rust client for the Sakura Fee Router.
+//!
+//! Exposes PDA derivation, account decoding and instruction builders that only
+//! depend on `anchor-lang`, so backend services don't need the full
+//! `anchor-client`. Enable the `rpc` feature for fetch helpers.
+
+pub mod accounts;
+pub mod instructions;
+pub mod pda;
+
+pub use sakura_fee_router::ID as PROGRAM_ID;
+pub use sakura_fee_router::{
+    AutoRenew, Badge, BurnMode, CompensationClaim, ComplianceMode, Config, GiftCard, Incident,
+    Invoice, JurisdictionAttestation, LegacySubscription, Listing, LoyaltyTier, NftDiscount,
+    OrgAccount, Organization, Outbox, OutboxEntry, PassBatch, PaymentReference, PaymentStream,
+    Plan, PriceFeed, PriceQuote, Product, ProductKind, Promo, Receipt, ReceiptHistory, Referrer,
+    RegistryEntry, RegistryEntryArgs, RegistryPage, Route, RouterRegistry, RoutingTable, Seat,
+    Service, Slab, SlabRegistry, SponsorAllowance, SponsorPool, StreamFunding, Subscription,
+    SubscriptionState, SubscriptionTransfer, Trial, UserStats, VaultKind, OUTBOX_CAPACITY,
+    SAKURA_INSTANCE_ID, STATS_SHARD_COUNT,
+};
@@ -0,0 +1,315 @@
+//! Program-derived addresses, mirroring the seeds in the program's account contexts.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address;
+use sakura_fee_router::{
+    stats_shard_index, subscription_address, VaultKind, BUBBLEGUM_PROGRAM_ID, CPI_AUTHORITY_SEED,
+    ID, TOKEN_METADATA_PROGRAM_ID,
+};
+
+pub fn registry() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"registry"], &ID)
+}
+
+pub fn config(instance_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config", instance_id.to_le_bytes().as_ref()], &ID)
+}
+
+pub fn subscription(config: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    subscription_address(config, user)
+}
+
+pub fn price_feed(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"price_feed", config.as_ref()], &ID)
+}
+
+pub fn service(config: &Pubkey, service_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"service",
+            config.as_ref(),
+            service_id.to_le_bytes().as_ref(),
+        ],
+        &ID,
+    )
+}
+
+/// A user's subscription to a registered service, separate from [`subscription`]
+pub fn service_subscription(service: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"service_subscription", service.as_ref(), user.as_ref()],
+        &ID,
+    )
+}
+
+pub fn plan(config: &Pubkey, plan_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"plan", config.as_ref(), &plan_id.to_le_bytes()], &ID)
+}
+
+pub fn product(config: &Pubkey, product_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"product", config.as_ref(), &product_id.to_le_bytes()],
+        &ID,
+    )
+}
+
+/// A user's add-on entitlement, separate from [`subscription`]
+pub fn product_subscription(product: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"product_subscription", product.as_ref(), user.as_ref()],
+        &ID,
+    )
+}
+
+pub fn invoice(service: &Pubkey, invoice_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"invoice", service.as_ref(), &invoice_id.to_le_bytes()],
+        &ID,
+    )
+}
+
+pub fn promo(config: &Pubkey, code_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"promo", config.as_ref(), code_hash], &ID)
+}
+
+pub fn routing_table(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"routing_table", config.as_ref()], &ID)
+}
+
+pub fn slab_registry(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"slab_registry", config.as_ref()], &ID)
+}
+
+pub fn referrer(config: &Pubkey, authority: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"referrer", config.as_ref(), authority.as_ref()], &ID)
+}
+
+pub fn listing(config: &Pubkey, seller: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"listing", config.as_ref(), seller.as_ref()], &ID)
+}
+
+pub fn outbox(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"outbox", config.as_ref()], &ID)
+}
+
+pub fn stats(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"stats", config.as_ref()], &ID)
+}
+
+pub fn user_stats(config: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"user_stats", config.as_ref(), user.as_ref()], &ID)
+}
+
+pub fn attestation(config: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"attestation", config.as_ref(), user.as_ref()], &ID)
+}
+
+pub fn nft_discount(config: &Pubkey, collection: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"nft_discount", config.as_ref(), collection.as_ref()],
+        &ID,
+    )
+}
+
+/// Metaplex metadata account of `mint`
+pub fn nft_metadata(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"metadata",
+            TOKEN_METADATA_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+        ],
+        &TOKEN_METADATA_PROGRAM_ID,
+    )
+}
+
+pub fn stats_shard(config: &Pubkey, index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"stats_shard", config.as_ref(), &[index]], &ID)
+}
+
+/// The shard a payment from `payer` writes to
+pub fn stats_shard_for(config: &Pubkey, payer: &Pubkey) -> (Pubkey, u8) {
+    stats_shard(config, stats_shard_index(payer))
+}
+
+/// The delegate a CPI-guarded payer approves for `process_delegated_payment`
+pub fn pay_delegate(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"pay_delegate", config.as_ref()], &ID)
+}
+
+/// The delegate stream funding cranks pull renewals under
+pub fn stream_delegate(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"stream_delegate", config.as_ref()], &ID)
+}
+
+/// The delegate auto-renew cranks pull renewals under
+pub fn renew_delegate(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"renew_delegate", config.as_ref()], &ID)
+}
+
+/// The receipt for `user`'s `payment_index`th payment, counting from zero; the
+/// next one is at their current [`UserStats`](crate::UserStats) payment count
+pub fn receipt(config: &Pubkey, user: &Pubkey, payment_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"receipt",
+            config.as_ref(),
+            user.as_ref(),
+            payment_index.to_le_bytes().as_ref(),
+        ],
+        &ID,
+    )
+}
+
+/// Marks `reference` as paid by `user`
+pub fn payment_reference(config: &Pubkey, user: &Pubkey, reference: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"payment_reference",
+            config.as_ref(),
+            user.as_ref(),
+            reference,
+        ],
+        &ID,
+    )
+}
+
+/// Page `page` of the instance registry namespaced by `kind`
+pub fn registry_page(config: &Pubkey, kind: u8, page: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"registry", config.as_ref(), &[kind], &[page]], &ID)
+}
+
+pub fn receipt_history(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"receipt_history", config.as_ref()], &ID)
+}
+
+pub fn stream_funding(config: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"stream_funding", config.as_ref(), user.as_ref()], &ID)
+}
+
+pub fn auto_renew(config: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"auto_renew", config.as_ref(), user.as_ref()], &ID)
+}
+
+/// The PDA `caller_program` signs `process_payment_via_cpi` with
+pub fn cpi_authority(config: &Pubkey, caller_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CPI_AUTHORITY_SEED, config.as_ref()], caller_program)
+}
+
+pub fn payment_stream(config: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"payment_stream", config.as_ref(), user.as_ref()], &ID)
+}
+
+/// The token account holding `user`'s stream balance in `mint`
+pub fn stream_vault(config: &Pubkey, user: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(&payment_stream(config, user).0, mint)
+}
+
+pub fn subscription_transfer(config: &Pubkey, from: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"subscription_transfer", config.as_ref(), from.as_ref()],
+        &ID,
+    )
+}
+
+pub fn trial(config: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"trial", config.as_ref(), user.as_ref()], &ID)
+}
+
+pub fn sponsor_pool(config: &Pubkey, sponsor: &Pubkey, pool_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"sponsor_pool",
+            config.as_ref(),
+            sponsor.as_ref(),
+            pool_id.to_le_bytes().as_ref(),
+        ],
+        &ID,
+    )
+}
+
+pub fn sponsor_allowance(pool: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"sponsor_allowance", pool.as_ref(), wallet.as_ref()], &ID)
+}
+
+pub fn org_account(config: &Pubkey, creator: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"org_account", config.as_ref(), creator.as_ref()], &ID)
+}
+
+pub fn organization(config: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"organization", config.as_ref(), owner.as_ref()], &ID)
+}
+
+pub fn seat(organization: &Pubkey, member: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"seat", organization.as_ref(), member.as_ref()], &ID)
+}
+
+pub fn pass_batch(config: &Pubkey, buyer: &Pubkey, batch_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"pass_batch",
+            config.as_ref(),
+            buyer.as_ref(),
+            &batch_id.to_le_bytes(),
+        ],
+        &ID,
+    )
+}
+
+pub fn badge(config: &Pubkey, badge_id: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"badge", config.as_ref(), &[badge_id]], &ID)
+}
+
+/// The one-of-one mint created when `user` claims `badge`
+pub fn badge_mint(badge: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"badge_mint", badge.as_ref(), user.as_ref()], &ID)
+}
+
+pub fn incident(config: &Pubkey, incident_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"incident", config.as_ref(), &incident_id.to_le_bytes()],
+        &ID,
+    )
+}
+
+pub fn compensation_claim(incident: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"compensation", incident.as_ref(), user.as_ref()], &ID)
+}
+
+pub fn vault_authority(config: &Pubkey, kind: VaultKind) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[kind.seed(), config.as_ref()], &ID)
+}
+
+/// The program-owned token vault of `kind` for `mint`
+pub fn program_vault(config: &Pubkey, kind: VaultKind, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(&vault_authority(config, kind).0, mint)
+}
+
+pub fn receipt_tree(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"receipt_tree", config.as_ref()], &ID)
+}
+
+/// Bubblegum's config account for `merkle_tree`
+pub fn bubblegum_tree_config(merkle_tree: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[merkle_tree.as_ref()], &BUBBLEGUM_PROGRAM_ID)
+}
+
+pub fn mint_authority(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"mint_authority", config.as_ref()], &ID)
+}
+
+pub fn gift_card(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"gift_card", config.as_ref()], &ID)
+}
+
+pub fn gift_card_mint(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"gift_card_mint", config.as_ref()], &ID)
+}
+
+pub fn receipt_mint(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"receipt_mint", config.as_ref()], &ID)
+}
+
+pub fn soulbound_mint(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"soulbound_mint", config.as_ref()], &ID)
+}
@@ -0,0 +1,156 @@
+//! PDA helpers re-derived from their seeds, and accounts decoded from what the
+//! program serializes, so the client can't drift from the program's layouts.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{AccountSerialize, Discriminator};
+use sakura_fee_router_client::{accounts, instructions, pda, Config, Receipt, Subscription};
+use sakura_fee_router_client::{PROGRAM_ID, SAKURA_INSTANCE_ID};
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+fn serialize<T: AccountSerialize>(account: &T) -> Vec<u8> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+/// `derived` is the canonical address for `seeds`, bump included
+fn assert_derives(derived: (Pubkey, u8), seeds: &[&[u8]]) {
+    assert_eq!(derived, Pubkey::find_program_address(seeds, &PROGRAM_ID));
+    let bump = [derived.1];
+    let mut signer_seeds = seeds.to_vec();
+    signer_seeds.push(&bump);
+    assert_eq!(
+        Pubkey::create_program_address(&signer_seeds, &PROGRAM_ID).unwrap(),
+        derived.0
+    );
+}
+
+#[test]
+fn pdas_match_their_seeds() {
+    let config = pda::config(SAKURA_INSTANCE_ID).0;
+    let user = key(1);
+
+    assert_derives(pda::registry(), &[b"registry"]);
+    assert_derives(
+        pda::config(SAKURA_INSTANCE_ID),
+        &[b"config", SAKURA_INSTANCE_ID.to_le_bytes().as_ref()],
+    );
+    assert_derives(
+        pda::subscription(&config, &user),
+        &[b"subscription", config.as_ref(), user.as_ref()],
+    );
+    assert_derives(
+        pda::user_stats(&config, &user),
+        &[b"user_stats", config.as_ref(), user.as_ref()],
+    );
+    assert_derives(
+        pda::receipt(&config, &user, 3),
+        &[
+            b"receipt",
+            config.as_ref(),
+            user.as_ref(),
+            &3u64.to_le_bytes(),
+        ],
+    );
+    assert_derives(
+        pda::payment_reference(&config, &user, &[7; 32]),
+        &[
+            b"payment_reference",
+            config.as_ref(),
+            user.as_ref(),
+            &[7; 32],
+        ],
+    );
+    assert_derives(
+        pda::pay_delegate(&config),
+        &[b"pay_delegate", config.as_ref()],
+    );
+    assert_derives(
+        pda::stream_delegate(&config),
+        &[b"stream_delegate", config.as_ref()],
+    );
+    assert_derives(
+        pda::renew_delegate(&config),
+        &[b"renew_delegate", config.as_ref()],
+    );
+}
+
+#[test]
+fn pdas_keep_flows_and_payments_apart() {
+    let config = pda::config(SAKURA_INSTANCE_ID).0;
+    let user = key(1);
+
+    let delegates = [
+        pda::pay_delegate(&config).0,
+        pda::stream_delegate(&config).0,
+        pda::renew_delegate(&config).0,
+    ];
+    assert_ne!(delegates[0], delegates[1]);
+    assert_ne!(delegates[1], delegates[2]);
+    assert_ne!(delegates[0], delegates[2]);
+
+    assert_ne!(
+        pda::receipt(&config, &user, 0).0,
+        pda::receipt(&config, &user, 1).0
+    );
+    assert_ne!(
+        pda::subscription(&config, &user).0,
+        pda::subscription(&pda::config(1).0, &user).0
+    );
+    assert_eq!(
+        pda::stats_shard_for(&config, &user),
+        pda::stats_shard(&config, sakura_fee_router::stats_shard_index(&user))
+    );
+}
+
+#[test]
+fn subscription_decodes() {
+    let mut subscription = Subscription {
+        version: Subscription::VERSION,
+        packed: 0,
+        payment_count: 2,
+        last_payment_slot: 9,
+    };
+    subscription.set_expires_at(1_700_000_000).unwrap();
+    let mut data = serialize(&subscription);
+    data.resize(Subscription::SPACE, 0);
+
+    let decoded: Subscription = accounts::decode(&data).unwrap();
+    assert_eq!(decoded.expires_at(), subscription.expires_at());
+    assert_eq!((decoded.payment_count, decoded.last_payment_slot), (2, 9));
+
+    let expires_at = decoded.expires_at();
+    assert!(accounts::is_active(&decoded, expires_at - 1, 0));
+    assert!(!accounts::is_active(&decoded, expires_at + 10, 0));
+    assert!(accounts::is_active(&decoded, expires_at + 10, 60));
+}
+
+#[test]
+fn decode_checks_the_discriminator() {
+    let config = Config::init_defaults(0, key(1), key(2), key(3), key(4), 255);
+    let data = serialize(&config);
+
+    let decoded: Config = accounts::decode(&data).unwrap();
+    assert_eq!((decoded.admin, decoded.mint), (key(1), key(2)));
+    assert!(accounts::decode::<Receipt>(&data).is_err());
+    assert!(accounts::decode::<Subscription>(&data).is_err());
+}
+
+#[test]
+fn builders_target_the_derived_accounts() {
+    let config = pda::config(SAKURA_INSTANCE_ID).0;
+    let admin = key(1);
+
+    let ix = instructions::migrate_config(&config, &admin);
+    assert_eq!(ix.program_id, PROGRAM_ID);
+    assert_eq!(
+        &ix.data[..8],
+        sakura_fee_router::instruction::MigrateConfig::DISCRIMINATOR
+    );
+    let keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+    assert_eq!(keys[..2], [admin, config]);
+    assert!(ix.accounts[0].is_signer && ix.accounts[1].is_writable);
+}
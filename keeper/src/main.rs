@@ -0,0 +1,513 @@
+//! Sakura Fee Router keeper
+//!
+//! Polls a router instance for work that only a crank can do and submits the
+//! matching permissionless instructions:
+//! - stream-funded subscriptions that have entered their renewal window
+//! - pay-as-you-go streams that can settle another period
+//! - expired auto-renew subscriptions, collecting the renewal bounty
+//! - the pending-burn vault, once it has reached the flush threshold or at any
+//!   balance when the keeper key is the instance operator and burns aren't batched
+//! - the aggregate stats view, rebuilt from the payment shards
+//! - receipts past their retention period, archived to reclaim their rent
+//! - a scheduled mint migration whose cutover has passed
+//!
+//! Every transaction is simulated first so guaranteed reverts don't burn fees.
+
+use std::env;
+use std::error::Error;
+use std::process;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sakura_fee_router::{
+    AutoRenew, Config, PaymentStream, Receipt, StreamFunding, Subscription, VaultKind,
+    MAX_CRANK_BATCH, RECEIPT_RETENTION, SAKURA_INSTANCE_ID, STREAM_RENEWAL_WINDOW,
+    STREAM_SETTLE_PERIOD,
+};
+use sakura_fee_router_client::accounts::rpc::{fetch, fetch_all};
+use sakura_fee_router_client::{instructions, pda};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+
+type KeeperResult<T> = Result<T, Box<dyn Error>>;
+
+// Configuration
+struct Settings {
+    rpc_url: String,
+    keypair_path: String,
+    instance_id: u64,
+    poll_interval: Duration,
+    // Instance-mint account that collects auto-renew bounties
+    bounty_account: Option<Pubkey>,
+}
+
+impl Settings {
+    fn from_env() -> Self {
+        let home = env::var("HOME").unwrap_or_default();
+        Self {
+            rpc_url: env::var("KEEPER_RPC_URL")
+                .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string()),
+            keypair_path: env::var("KEEPER_KEYPAIR")
+                .unwrap_or_else(|_| format!("{home}/.config/solana/id.json")),
+            instance_id: env::var("ROUTER_INSTANCE_ID")
+                .ok()
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(SAKURA_INSTANCE_ID),
+            poll_interval: Duration::from_secs(
+                env::var("KEEPER_POLL_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|secs| secs.parse().ok())
+                    .unwrap_or(30),
+            ),
+            bounty_account: env::var("KEEPER_BOUNTY_ACCOUNT")
+                .ok()
+                .and_then(|key| key.parse().ok()),
+        }
+    }
+}
+
+// Metrics
+#[derive(Default)]
+struct Metrics {
+    successes: u64,
+    failures: u64,
+    skipped: u64,
+}
+
+fn log(msg: &str) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    println!("[{now}] {msg}");
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Simulates, then sends. Returns `Ok(false)` when simulation says it would revert.
+fn submit(client: &RpcClient, keeper: &Keypair, ix: Instruction) -> KeeperResult<bool> {
+    let blockhash = client.get_latest_blockhash()?;
+    let tx =
+        Transaction::new_signed_with_payer(&[ix], Some(&keeper.pubkey()), &[keeper], blockhash);
+
+    let simulation = client.simulate_transaction(&tx)?;
+    if let Some(err) = simulation.value.err {
+        log(&format!("Simulation failed: {err:?}. Skipping execution."));
+        return Ok(false);
+    }
+
+    let sig = client.send_and_confirm_transaction(&tx)?;
+    log(&format!("Success! Crank Tx: {sig}"));
+    Ok(true)
+}
+
+fn record(metrics: &mut Metrics, outcome: KeeperResult<bool>) {
+    match outcome {
+        Ok(true) => metrics.successes += 1,
+        Ok(false) => metrics.skipped += 1,
+        Err(err) => {
+            log(&format!("Crank execution failed: {err}"));
+            metrics.failures += 1;
+        }
+    }
+}
+
+// StreamFunding and AutoRenew don't store their instance, so they are matched
+// on the PDA instead
+fn is_instance_stream_funding(config_key: &Pubkey, key: &Pubkey, funding: &StreamFunding) -> bool {
+    *key == pda::stream_funding(config_key, &funding.user).0
+}
+
+fn has_renewals_left(config_key: &Pubkey, key: &Pubkey, auto_renew: &AutoRenew) -> bool {
+    *key == pda::auto_renew(config_key, &auto_renew.user).0 && auto_renew.remaining_renewals > 0
+}
+
+fn is_archivable(config_key: &Pubkey, receipt: &Receipt, archive_before: i64) -> bool {
+    receipt.config == *config_key && receipt.paid_at <= archive_before
+}
+
+fn crank_stream_fundings(
+    client: &RpcClient,
+    keeper: &Keypair,
+    config_key: &Pubkey,
+    config: &Config,
+    metrics: &mut Metrics,
+) -> KeeperResult<()> {
+    let renew_before = unix_now() + STREAM_RENEWAL_WINDOW;
+    let mut due = Vec::new();
+
+    for (key, funding) in fetch_all::<StreamFunding>(client)? {
+        if !is_instance_stream_funding(config_key, &key, &funding) {
+            continue;
+        }
+
+        let subscription_key = pda::subscription(config_key, &funding.user).0;
+        let subscription: Subscription = match fetch(client, &subscription_key) {
+            Ok(subscription) => subscription,
+            Err(_) => continue,
+        };
+        if subscription.expires_at() > renew_before {
+            continue;
+        }
+
+        log(&format!(
+            "Stream-funded subscription for {} expires at {}. Queueing renewal...",
+            funding.user,
+            subscription.expires_at()
+        ));
+        due.push((funding.user, funding.token_account));
+    }
+
+    // Renewals that can't be funded are queued to the outbox, if the instance has one
+    let outbox = client.get_account(&pda::outbox(config_key).0).is_ok();
+
+    // The keeper only pulls balance the streams have already paid out
+    for batch in due.chunks(MAX_CRANK_BATCH) {
+        let ix = instructions::crank_stream_funding_batch(
+            config_key,
+            config,
+            &keeper.pubkey(),
+            batch,
+            outbox,
+        );
+        record(metrics, submit(client, keeper, ix));
+    }
+
+    Ok(())
+}
+
+fn settle_payment_streams(
+    client: &RpcClient,
+    keeper: &Keypair,
+    config_key: &Pubkey,
+    config: &Config,
+    metrics: &mut Metrics,
+) -> KeeperResult<()> {
+    if config.stream_rate == 0 {
+        return Ok(());
+    }
+    let settle_before = unix_now() + STREAM_RENEWAL_WINDOW;
+    let period_cost = config.stream_rate * STREAM_SETTLE_PERIOD as u64;
+
+    for (_, stream) in fetch_all::<PaymentStream>(client)? {
+        if stream.config != *config_key {
+            continue;
+        }
+
+        let subscription_key = pda::subscription(config_key, &stream.user).0;
+        let subscription: Subscription = match fetch(client, &subscription_key) {
+            Ok(subscription) => subscription,
+            Err(_) => continue,
+        };
+        if subscription.expires_at() > settle_before {
+            continue;
+        }
+        let vault = pda::stream_vault(config_key, &stream.user, &config.mint);
+        let balance = match client.get_token_account_balance(&vault) {
+            Ok(balance) => balance.amount.parse::<u64>().unwrap_or_default(),
+            Err(_) => continue,
+        };
+        if balance < period_cost {
+            continue;
+        }
+
+        log(&format!(
+            "Pay-as-you-go stream for {} is due. Settling...",
+            stream.user
+        ));
+        let ix = instructions::settle_stream(config_key, config, &stream.user);
+        record(metrics, submit(client, keeper, ix));
+    }
+    Ok(())
+}
+
+fn crank_auto_renewals(
+    client: &RpcClient,
+    keeper: &Keypair,
+    config_key: &Pubkey,
+    config: &Config,
+    bounty_account: Option<&Pubkey>,
+    metrics: &mut Metrics,
+) -> KeeperResult<()> {
+    let now = unix_now();
+    let mut due = Vec::new();
+
+    for (key, auto_renew) in fetch_all::<AutoRenew>(client)? {
+        if !has_renewals_left(config_key, &key, &auto_renew) {
+            continue;
+        }
+        let subscription_key = pda::subscription(config_key, &auto_renew.user).0;
+        match fetch::<Subscription>(client, &subscription_key) {
+            Ok(subscription) if subscription.expires_at() <= now => due.push(auto_renew),
+            _ => continue,
+        }
+    }
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    let token_program = client.get_account(&config.mint)?.owner;
+    log(&format!(
+        "{} auto-renew subscriptions expired. Renewing...",
+        due.len()
+    ));
+    for auto_renew in &due {
+        let ix = instructions::crank_renewal(
+            config_key,
+            config,
+            &token_program,
+            &keeper.pubkey(),
+            auto_renew,
+            bounty_account,
+        );
+        record(metrics, submit(client, keeper, ix));
+    }
+    Ok(())
+}
+
+fn burn_pending(
+    client: &RpcClient,
+    keeper: &Keypair,
+    config_key: &Pubkey,
+    config: &Config,
+    metrics: &mut Metrics,
+) -> KeeperResult<()> {
+    // Batched burns wait for the threshold; anyone can flush them then
+    if config.burn_flush_threshold > 0 {
+        let balance = client
+            .get_token_account_balance(&config.pending_burn_vault)?
+            .amount
+            .parse::<u64>()
+            .unwrap_or_default();
+        if balance == 0 || balance < config.burn_flush_threshold {
+            return Ok(());
+        }
+
+        log(&format!("Batched burn holds {balance}. Flushing..."));
+        let token_program = client.get_account(&config.mint)?.owner;
+        let ix = instructions::flush_burn(config_key, config, &token_program);
+        record(metrics, submit(client, keeper, ix));
+        return Ok(());
+    }
+    if config.operator != keeper.pubkey() {
+        return Ok(());
+    }
+
+    let vault = pda::program_vault(config_key, VaultKind::PendingBurn, &config.mint);
+    let balance = match client.get_token_account_balance(&vault) {
+        Ok(balance) => balance.amount.parse::<u64>().unwrap_or_default(),
+        // The vault is only created once something is routed into it
+        Err(_) => return Ok(()),
+    };
+    if balance == 0 {
+        return Ok(());
+    }
+
+    log(&format!("Pending-burn vault holds {balance}. Burning..."));
+    let ix = instructions::burn_pending(config_key, config);
+    record(metrics, submit(client, keeper, ix));
+    Ok(())
+}
+
+fn archive_receipts(
+    client: &RpcClient,
+    keeper: &Keypair,
+    config_key: &Pubkey,
+    metrics: &mut Metrics,
+) -> KeeperResult<()> {
+    let archive_before = unix_now() - RECEIPT_RETENTION;
+    let expired: Vec<Pubkey> = fetch_all::<Receipt>(client)?
+        .into_iter()
+        .filter(|(_, receipt)| is_archivable(config_key, receipt, archive_before))
+        .map(|(key, _)| key)
+        .collect();
+    if expired.is_empty() {
+        return Ok(());
+    }
+
+    log(&format!(
+        "{} receipts past retention. Archiving...",
+        expired.len()
+    ));
+    for batch in expired.chunks(MAX_CRANK_BATCH) {
+        let ix = instructions::archive_receipts(config_key, batch);
+        record(metrics, submit(client, keeper, ix));
+    }
+    Ok(())
+}
+
+fn complete_mint_migration(
+    client: &RpcClient,
+    keeper: &Keypair,
+    config_key: &Pubkey,
+    config: &Config,
+    metrics: &mut Metrics,
+) -> KeeperResult<()> {
+    // Payments are held from the cutover until the config is switched over
+    if config.successor_mint == Pubkey::default() || unix_now() < config.mint_cutover_at {
+        return Ok(());
+    }
+
+    log(&format!(
+        "Mint cutover reached. Migrating to {}...",
+        config.successor_mint
+    ));
+    let ix = instructions::complete_mint_migration(config_key);
+    record(metrics, submit(client, keeper, ix));
+    Ok(())
+}
+
+fn run_once(
+    client: &RpcClient,
+    keeper: &Keypair,
+    config_key: &Pubkey,
+    bounty_account: Option<&Pubkey>,
+    metrics: &mut Metrics,
+) -> KeeperResult<()> {
+    let config: Config = fetch(client, config_key)?;
+    complete_mint_migration(client, keeper, config_key, &config, metrics)?;
+    crank_stream_fundings(client, keeper, config_key, &config, metrics)?;
+    settle_payment_streams(client, keeper, config_key, &config, metrics)?;
+    crank_auto_renewals(client, keeper, config_key, &config, bounty_account, metrics)?;
+    burn_pending(client, keeper, config_key, &config, metrics)?;
+    archive_receipts(client, keeper, config_key, metrics)?;
+    record(
+        metrics,
+        submit(client, keeper, instructions::aggregate_stats(config_key)),
+    );
+
+    log(&format!(
+        "[METRICS] Successes: {} | Failures: {} | Skipped: {}",
+        metrics.successes, metrics.failures, metrics.skipped
+    ));
+    Ok(())
+}
+
+fn main() {
+    let settings = Settings::from_env();
+    let keeper = read_keypair_file(&settings.keypair_path).unwrap_or_else(|err| {
+        eprintln!(
+            "Failed to read keeper keypair at {}: {err}",
+            settings.keypair_path
+        );
+        process::exit(1);
+    });
+    let client =
+        RpcClient::new_with_commitment(settings.rpc_url.clone(), CommitmentConfig::confirmed());
+    let config_key = pda::config(settings.instance_id).0;
+
+    log("Starting Sakura Keeper Service...");
+    log(&format!(
+        "RPC: {} | Instance: #{} ({config_key}) | Keeper: {}",
+        settings.rpc_url,
+        settings.instance_id,
+        keeper.pubkey()
+    ));
+
+    let mut metrics = Metrics::default();
+    loop {
+        if let Err(err) = run_once(
+            &client,
+            &keeper,
+            &config_key,
+            settings.bounty_account.as_ref(),
+            &mut metrics,
+        ) {
+            log(&format!("Loop execution error: {err}"));
+        }
+        sleep(settings.poll_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::AccountSerialize;
+    use sakura_fee_router_client::accounts::decode;
+
+    fn key(byte: u8) -> Pubkey {
+        Pubkey::new_from_array([byte; 32])
+    }
+
+    // What `fetch_all` hands the cranks: the program's bytes, decoded by the client
+    fn round_trip<T: AccountSerialize + anchor_lang::AccountDeserialize>(account: &T) -> T {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        decode(&data).unwrap()
+    }
+
+    #[test]
+    fn stream_fundings_match_their_instance() {
+        let config = pda::config(SAKURA_INSTANCE_ID).0;
+        let funding = round_trip(&StreamFunding {
+            user: key(1),
+            stream: key(2),
+            token_account: key(3),
+            amount_per_period: 100,
+            last_funded_at: 0,
+            bump: 254,
+        });
+        let key = pda::stream_funding(&config, &funding.user).0;
+
+        assert!(is_instance_stream_funding(&config, &key, &funding));
+        assert!(!is_instance_stream_funding(
+            &pda::config(1).0,
+            &key,
+            &funding
+        ));
+    }
+
+    #[test]
+    fn auto_renewals_need_renewals_left() {
+        let config = pda::config(SAKURA_INSTANCE_ID).0;
+        let mut auto_renew = AutoRenew {
+            user: key(1),
+            token_account: key(2),
+            plan: Pubkey::default(),
+            remaining_renewals: 1,
+            bump: 254,
+        };
+        let key = pda::auto_renew(&config, &auto_renew.user).0;
+
+        assert!(has_renewals_left(&config, &key, &round_trip(&auto_renew)));
+        assert!(!has_renewals_left(&pda::config(1).0, &key, &auto_renew));
+        auto_renew.remaining_renewals = 0;
+        assert!(!has_renewals_left(&config, &key, &round_trip(&auto_renew)));
+    }
+
+    #[test]
+    fn receipts_archive_after_retention() {
+        let config = pda::config(SAKURA_INSTANCE_ID).0;
+        let receipt = round_trip(&Receipt {
+            config,
+            user: key(1),
+            subscriber: key(1),
+            payment_index: 0,
+            amount: 10,
+            insurance_amount: 5,
+            burn_amount: 5,
+            refunded: 0,
+            escrowed: false,
+            plan_id: None,
+            slab_id: None,
+            reference: None,
+            duration: 60,
+            expires_at: 200,
+            paid_at: 100,
+            bump: 254,
+        });
+
+        assert!(is_archivable(&config, &receipt, 100));
+        assert!(!is_archivable(&config, &receipt, 99));
+        assert!(!is_archivable(&pda::config(1).0, &receipt, 100));
+    }
+}
@@ -1,126 +1,10860 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_lang::solana_program::program_option::COption;
+use anchor_spl::associated_token::{
+    self, get_associated_token_address, get_associated_token_address_with_program_id,
+    AssociatedToken,
+};
+use anchor_spl::memo::{self, BuildMemo, Memo};
+use anchor_spl::token::{self, Approve, Mint, Revoke, Token, TokenAccount};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        cpi_guard::CpiGuard, transfer_fee::TransferFeeConfig, BaseStateWithExtensions,
+        ExtensionType, StateWithExtensions,
+    },
+};
+use anchor_spl::token_2022::{self, Token2022};
+use anchor_spl::token_2022_extensions::spl_token_metadata_interface::state::TokenMetadata;
+use anchor_spl::token_2022_extensions::{token_metadata_initialize, TokenMetadataInitialize};
+use anchor_spl::token_interface::{self, TokenInterface};
+use solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked};
+use solana_sdk_ids::{ed25519_program, sysvar};
+use solana_sha256_hasher::hashv;
 
-declare_id!("FNoE2JUhn981hBDyBMvWJYkw9DThhtYwWoPbw6wgz1rg");
+#[cfg(any(
+    all(feature = "mainnet", feature = "devnet"),
+    all(feature = "mainnet", feature = "localnet"),
+    all(feature = "devnet", feature = "localnet")
+))]
+compile_error!("Enable at most one of the `mainnet`, `devnet` and `localnet` cluster features");
 
-pub const SAKURA_MINT: Pubkey = pubkey!("EWiVNxCqNatzV2paBHyfKUwGLnk7WKs9uZTA5jkTpump");
+#[cfg(all(
+    feature = "test-mode",
+    not(any(feature = "devnet", feature = "localnet"))
+))]
+compile_error!("`test-mode` is only for `devnet` and `localnet` builds");
 
-// TODO: Replace this with the actual mainnet Percolator Insurance Vault for the corresponding slab
-pub const PERCOLATOR_INSURANCE_VAULT: Pubkey =
-    pubkey!("63juJmvm1XHCHveWv9WdanxqJX6tD6DLFTZD7dvH12dc");
+// Cluster-specific addresses. `mainnet` is the default when no other cluster
+// feature is enabled, so a plain `anchor build` always targets mainnet.
+#[cfg(not(any(feature = "devnet", feature = "localnet")))]
+mod cluster {
+    use super::*;
+
+    declare_id!("FNoE2JUhn981hBDyBMvWJYkw9DThhtYwWoPbw6wgz1rg");
+
+    pub const SAKURA_MINT: Pubkey = pubkey!("EWiVNxCqNatzV2paBHyfKUwGLnk7WKs9uZTA5jkTpump");
+
+    // TODO: Replace this with the actual mainnet Percolator Insurance Vault for the corresponding slab
+    pub const PERCOLATOR_INSURANCE_VAULT: Pubkey =
+        pubkey!("63juJmvm1XHCHveWv9WdanxqJX6tD6DLFTZD7dvH12dc");
+
+    pub const PERCOLATOR_VAULT_AUTHORITY: Pubkey = pubkey!("11111111111111111111111111111111");
+}
+
+// Devnet and localnet have no canonical SAKURA mint or Percolator vault, so
+// `initialize_config` takes instance #0's from its accounts; only the id
+// differs per cluster, and it follows Anchor.toml
+#[cfg(any(feature = "devnet", feature = "localnet"))]
+mod cluster {
+    use super::*;
+
+    declare_id!("FNoE2JUhn981hBDyBMvWJYkw9DThhtYwWoPbw6wgz1rg");
+}
+
+pub use cluster::{check_id, id, ID};
+#[cfg(not(any(feature = "devnet", feature = "localnet")))]
+pub use cluster::{PERCOLATOR_INSURANCE_VAULT, PERCOLATOR_VAULT_AUTHORITY, SAKURA_MINT};
+
+pub const INSURANCE_BPS: u64 = 5000;
+pub const BURN_BPS: u64 = 5000;
+
+// 30 days subscription in seconds
+#[cfg(not(feature = "test-mode"))]
+pub const SUBSCRIPTION_TIME: i64 = 30 * 24 * 60 * 60;
+// Test builds expire after a day so renewal and expiry flows can be exercised;
+// expiry is packed in whole days, so nothing shorter would take effect
+#[cfg(feature = "test-mode")]
+pub const SUBSCRIPTION_TIME: i64 = SECONDS_PER_DAY;
+
+// The constants above configure router instance #0, the SAKURA deployment
+pub const SAKURA_INSTANCE_ID: u64 = 0;
+
+// Streamflow timelock program, whose stream withdrawals can fund renewals
+pub const STREAMFLOW_PROGRAM_ID: Pubkey = pubkey!("strmRqUCoQUgGUan5YhzUZa6KqdzwX5L6FpUxfmKg5m");
+// Anchor sighash for Streamflow's `global:withdraw`
+const STREAMFLOW_WITHDRAW_DISCRIMINATOR: [u8; 8] = [183, 18, 70, 156, 148, 109, 161, 34];
+
+// Metaplex Bubblegum, which mints compressed receipts into an instance's tree
+pub const BUBBLEGUM_PROGRAM_ID: Pubkey = pubkey!("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+// Anchor sighash for Bubblegum's `global:mint_v1`
+const BUBBLEGUM_MINT_V1_DISCRIMINATOR: [u8; 8] = [145, 98, 192, 118, 184, 147, 118, 104];
+
+// Stream-funded renewals may be cranked once the subscription is within a day of expiring
+pub const STREAM_RENEWAL_WINDOW: i64 = 24 * 60 * 60;
+
+// Time one `settle_stream` buys; a day is the smallest step a subscription's
+// expiry can take
+pub const STREAM_SETTLE_PERIOD: i64 = 24 * 60 * 60;
+
+// Liquid-staking tokens accepted at their on-chain SOL exchange rate
+pub const MSOL_MINT: Pubkey = pubkey!("mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So");
+pub const MARINADE_STATE: Pubkey = pubkey!("8szGkuLTAux9XMgZ2vtY39jVSowEcpBfFfD8hXSEqdGC");
+pub const JITOSOL_MINT: Pubkey = pubkey!("J1toso1uCk3RLmjorhTtrVwY9HJ7X8V9yYac6Y7kGCPn");
+pub const JITO_STAKE_POOL: Pubkey = pubkey!("Jito4APyf642JPZPx3hGc6WWJ8zPKtRbRs4P815Awbb");
+
+// Marinade `State.msol_price` is SOL per mSOL scaled by 2^32
+const MARINADE_MSOL_PRICE_OFFSET: usize = 512;
+const MARINADE_PRICE_DENOMINATOR: u128 = 1 << 32;
+// SPL stake pool `total_lamports`, `pool_token_supply` and `last_update_epoch`
+const STAKE_POOL_TOTAL_LAMPORTS_OFFSET: usize = 258;
+const STAKE_POOL_TOKEN_SUPPLY_OFFSET: usize = 266;
+const STAKE_POOL_LAST_UPDATE_EPOCH_OFFSET: usize = 274;
+
+// Pyth pull-oracle receiver and its `PriceUpdateV2` layout. Only fully
+// verified updates are accepted, which fixes the price message offset.
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+const PRICE_UPDATE_VERIFICATION_OFFSET: usize = 40;
+const PRICE_UPDATE_FULLY_VERIFIED: u8 = 1;
+const PRICE_UPDATE_FEED_ID_OFFSET: usize = 41;
+const PRICE_UPDATE_PRICE_OFFSET: usize = 73;
+const PRICE_UPDATE_CONF_OFFSET: usize = 81;
+const PRICE_UPDATE_EXPONENT_OFFSET: usize = 89;
+const PRICE_UPDATE_PUBLISH_TIME_OFFSET: usize = 93;
+// USD plan prices are in millionths of a dollar
+const USD_PRICE_DECIMALS: u32 = 6;
+// LST payments are priced in lamports
+const SOL_DECIMALS: u8 = 9;
+
+// Upper bound on stream renewals per batch crank, keeping it inside one
+// transaction's account and compute limits
+pub const MAX_CRANK_BATCH: usize = 8;
+
+// Upper bound on routing table entries; each destination is a remaining
+// account of `process_payment`
+pub const MAX_ROUTES: usize = 6;
+
+// Partner programs allowed to pay through `process_payment_via_cpi`, each
+// signing with its PDA at `[CPI_AUTHORITY_SEED, config]`
+pub const MAX_CPI_CALLERS: usize = 4;
+pub const CPI_AUTHORITY_SEED: &[u8] = b"sakura_cpi";
+
+// Upper bound on Percolator slabs, each with its own insurance vault, that
+// one instance can route to
+pub const MAX_SLABS: usize = 8;
+
+// Payment counters are spread over this many shards, picked by payer key, so
+// payments from different wallets don't serialize on one writable account
+pub const STATS_SHARD_COUNT: u8 = 16;
+
+// Base SPL token layouts, shared by both token programs, for fields read in place
+const TOKEN_ACCOUNT_MINT_OFFSET: usize = 0;
+const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+const MINT_DECIMALS_OFFSET: usize = 44;
+
+// Metaplex Token Metadata, whose verified collections gate NFT discounts
+pub const TOKEN_METADATA_PROGRAM_ID: Pubkey =
+    pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+const METADATA_V1_KEY: u8 = 4;
+const METADATA_CREATOR_LEN: usize = 34;
+
+// Packed subscriptions get a discriminator of their own so a legacy account can
+// never be misread as the new layout; the legacy one is the original
+// `account:Subscription` sighash
+pub const SUBSCRIPTION_DISCRIMINATOR: [u8; 8] = [86, 65, 12, 166, 144, 147, 252, 224];
+pub const LEGACY_SUBSCRIPTION_DISCRIMINATOR: [u8; 8] = [64, 7, 26, 135, 102, 132, 98, 33];
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// Registries are stored as fixed-capacity zero-copy pages, so lookups borrow
+// account data in place instead of deserializing a Vec onto the 32KB heap;
+// updates are bounded per instruction and spread across transactions
+pub const REGISTRY_PAGE_CAPACITY: usize = 64;
+pub const MAX_REGISTRY_CHUNK: usize = 16;
+
+// The outbox ring holds this many entries; once full, the oldest unacked entry
+// is overwritten and counted as dropped
+pub const OUTBOX_CAPACITY: usize = 64;
+
+// Subscriptions expiring within this window can be queued for a reminder
+pub const OUTBOX_EXPIRY_NOTICE: i64 = 3 * SECONDS_PER_DAY;
+
+// Receipts can be archived by anyone once they are this old
+#[cfg(not(feature = "test-mode"))]
+pub const RECEIPT_RETENTION: i64 = 90 * SECONDS_PER_DAY;
+#[cfg(feature = "test-mode")]
+pub const RECEIPT_RETENTION: i64 = 10 * 60;
+
+// Anyone can close a subscription this long after it expired, for a
+// `SUBSCRIPTION_CLOSE_BOUNTY_BPS` cut of its rent; the rest goes to the user
+#[cfg(not(feature = "test-mode"))]
+pub const SUBSCRIPTION_CLOSE_GRACE: i64 = 180 * SECONDS_PER_DAY;
+#[cfg(feature = "test-mode")]
+pub const SUBSCRIPTION_CLOSE_GRACE: i64 = 10 * 60;
+pub const SUBSCRIPTION_CLOSE_BOUNTY_BPS: u64 = 1_000;
+
+// Depth of the receipt history Merkle tree, enough for ~16.7M receipts
+pub const RECEIPT_HISTORY_DEPTH: usize = 24;
+
+// Upper bound on the payment reference so the memo CPI stays cheap
+pub const MAX_MEMO_LEN: usize = 128;
+
+// Layout of the native ed25519 program instruction data
+const ED25519_OFFSETS_START: usize = 2;
+const ED25519_OFFSETS_LEN: usize = 14;
+const ED25519_PUBKEY_LEN: usize = 32;
+
+#[program]
+pub mod sakura_fee_router {
+    use super::*;
+
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        registry.instance_count = 1;
+        registry.bump = ctx.bumps.registry;
+
+        let (mint, insurance_vault, vault_authority) = instance_zero_vault(ctx.accounts)?;
+        ctx.accounts.config.set_inner(Config::init_defaults(
+            SAKURA_INSTANCE_ID,
+            ctx.accounts.admin.key(),
+            mint,
+            insurance_vault,
+            vault_authority,
+            ctx.bumps.config,
+        ));
+        Ok(())
+    }
+
+    /// Admin only: replaces the split, insurance vault, subscription duration
+    /// and base price set at initialization, so none of them needs a redeploy.
+    /// The vault must hold the instance mint; its owner becomes the vault authority.
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        insurance_bps: u16,
+        burn_bps: u16,
+        subscription_duration: i64,
+        price: u64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "update_config");
+        validate_split(insurance_bps, burn_bps)?;
+        require!(subscription_duration > 0, ErrorCode::InvalidDuration);
+
+        let config = &mut ctx.accounts.config;
+        config.insurance_bps = insurance_bps;
+        config.burn_bps = burn_bps;
+        config.subscription_duration = subscription_duration;
+        config.price = price;
+        config.insurance_vault = ctx.accounts.insurance_vault.key();
+        config.vault_authority = ctx.accounts.insurance_vault.owner;
+
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.price_feed.bump = ctx.bumps.price_feed;
+        write_price_feed(&mut ctx.accounts.price_feed, config, now);
+        Ok(())
+    }
+
+    pub fn create_instance(
+        ctx: Context<CreateInstance>,
+        insurance_bps: u16,
+        burn_bps: u16,
+        subscription_duration: i64,
+    ) -> Result<()> {
+        validate_split(insurance_bps, burn_bps)?;
+        require!(subscription_duration > 0, ErrorCode::InvalidDuration);
+
+        let registry = &mut ctx.accounts.registry;
+        let id = registry.instance_count;
+        registry.instance_count = id.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+
+        // The creator administers their own instance, independent of instance #0
+        let mut defaults = Config::init_defaults(
+            id,
+            ctx.accounts.admin.key(),
+            ctx.accounts.mint.key(),
+            ctx.accounts.insurance_vault.key(),
+            ctx.accounts.insurance_vault.owner,
+            ctx.bumps.config,
+        );
+        defaults.insurance_bps = insurance_bps;
+        defaults.burn_bps = burn_bps;
+        defaults.subscription_duration = subscription_duration;
+        let config = &mut ctx.accounts.config;
+        config.set_inner(defaults);
+
+        msg!("Router instance #{} created for mint {}", id, config.mint);
+        Ok(())
+    }
+
+    pub fn set_compliance(
+        ctx: Context<AdminConfig>,
+        compliance_mode: ComplianceMode,
+        attestor: Pubkey,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_compliance");
+        if compliance_mode != ComplianceMode::Disabled {
+            require!(attestor != Pubkey::default(), ErrorCode::InvalidAttestor);
+        }
+
+        let config = &mut ctx.accounts.config;
+        config.compliance_mode = compliance_mode;
+        config.attestor = attestor;
+        Ok(())
+    }
+
+    /// Requires payers to hold an unexpired jurisdiction attestation issued by
+    /// `attestor`, checked by the payment handlers themselves
+    pub fn set_jurisdiction_attestation(
+        ctx: Context<AdminConfig>,
+        required: bool,
+        attestor: Pubkey,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_jurisdiction_attestation");
+        if required {
+            require!(attestor != Pubkey::default(), ErrorCode::InvalidAttestor);
+        }
+
+        let config = &mut ctx.accounts.config;
+        config.require_jurisdiction_attestation = required;
+        config.jurisdiction_attestor = attestor;
+        Ok(())
+    }
+
+    /// Admin only: schedules the instance's switch to `successor_mint` at
+    /// `cutover_at`. The successor vault must sit under the same vault authority,
+    /// so routing carries on unchanged once `complete_mint_migration` runs.
+    pub fn schedule_mint_migration(
+        ctx: Context<ScheduleMintMigration>,
+        cutover_at: i64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "schedule_mint_migration");
+        let now = Clock::get()?.unix_timestamp;
+        require!(cutover_at > now, ErrorCode::InvalidMintMigration);
+
+        let config = &mut ctx.accounts.config;
+        require_keys_neq!(
+            ctx.accounts.successor_mint.key(),
+            config.mint,
+            ErrorCode::InvalidMintMigration
+        );
+        config.successor_mint = ctx.accounts.successor_mint.key();
+        config.successor_insurance_vault = ctx.accounts.successor_insurance_vault.key();
+        config.mint_cutover_at = cutover_at;
+
+        msg!(
+            "Instance #{} migrates to mint {} at {}",
+            config.id,
+            config.successor_mint,
+            cutover_at
+        );
+        Ok(())
+    }
+
+    /// Admin only: drops a scheduled migration that hasn't cut over yet
+    pub fn cancel_mint_migration(ctx: Context<AdminConfig>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "cancel_mint_migration");
+        let now = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.config;
+        require!(
+            config.successor_mint != Pubkey::default() && now < config.mint_cutover_at,
+            ErrorCode::InvalidMintMigration
+        );
+        clear_mint_migration(config);
+        Ok(())
+    }
+
+    /// Permissionless once the cutover time passes: points the instance at the
+    /// successor mint and vault. Payments are held until this runs.
+    pub fn complete_mint_migration(ctx: Context<CompleteMintMigration>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.config;
+        require!(
+            config.successor_mint != Pubkey::default(),
+            ErrorCode::InvalidMintMigration
+        );
+        require!(
+            now >= config.mint_cutover_at,
+            ErrorCode::MintMigrationNotDue
+        );
+        // The escrow holds the old mint, so it is swept and turned off first
+        require!(!config.escrows_insurance(), ErrorCode::InvalidMintMigration);
+
+        let previous = config.mint;
+        config.mint = config.successor_mint;
+        config.insurance_vault = config.successor_insurance_vault;
+        clear_mint_migration(config);
+
+        msg!(
+            "Instance #{} migrated from mint {} to {}",
+            config.id,
+            previous,
+            config.mint
+        );
+        Ok(())
+    }
+
+    /// Payers holding a verified NFT from `collection` get `discount_bps` off
+    /// the effective price; calling it again updates the discount
+    pub fn set_nft_discount(
+        ctx: Context<SetNftDiscount>,
+        collection: Pubkey,
+        discount_bps: u16,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_nft_discount");
+        require!(discount_bps <= 10_000, ErrorCode::InvalidPromo);
+
+        let nft_discount = &mut ctx.accounts.nft_discount;
+        nft_discount.config = ctx.accounts.config.key();
+        nft_discount.collection = collection;
+        nft_discount.discount_bps = discount_bps;
+        nft_discount.bump = ctx.bumps.nft_discount;
+        Ok(())
+    }
+
+    /// Admin only: creates or updates a catalog product. Every product carries
+    /// its own split; `duration` must be zero for one-time purchases.
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_product(
+        ctx: Context<SetProduct>,
+        product_id: u64,
+        kind: ProductKind,
+        price: u64,
+        duration: i64,
+        insurance_bps: u16,
+        burn_bps: u16,
+        active: bool,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_product");
+        validate_split(insurance_bps, burn_bps)?;
+        require!(price > 0, ErrorCode::InvalidAmount);
+        match kind {
+            ProductKind::OneTime => require!(duration == 0, ErrorCode::InvalidDuration),
+            ProductKind::Subscription | ProductKind::AddOn => {
+                require!(duration > 0, ErrorCode::InvalidDuration)
+            }
+        }
+
+        let product = &mut ctx.accounts.product;
+        product.config = ctx.accounts.config.key();
+        product.product_id = product_id;
+        product.kind = kind;
+        product.price = price;
+        product.duration = duration;
+        product.insurance_bps = insurance_bps;
+        product.burn_bps = burn_bps;
+        product.active = active;
+        product.bump = ctx.bumps.product;
+        Ok(())
+    }
+
+    pub fn remove_nft_discount(ctx: Context<RemoveNftDiscount>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "remove_nft_discount");
+        Ok(())
+    }
+
+    /// Admin only: a fixed-price plan that buys `duration` per payment, e.g. a
+    /// discounted 12-month plan. Payments select it by passing its account.
+    /// A nonzero `usd_price`, in millionths of a dollar, prices it in USD
+    /// through the instance's Pyth feed instead of `price`.
+    pub fn create_plan(
+        ctx: Context<CreatePlan>,
+        plan_id: u64,
+        duration: i64,
+        price: u64,
+        usd_price: u64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "create_plan");
+        require!(duration > 0, ErrorCode::InvalidDuration);
+        require!(price > 0 || usd_price > 0, ErrorCode::InvalidAmount);
+
+        let plan = &mut ctx.accounts.plan;
+        plan.config = ctx.accounts.config.key();
+        plan.plan_id = plan_id;
+        plan.duration = duration;
+        plan.price = price;
+        plan.usd_price = usd_price;
+        plan.active = true;
+        plan.bump = ctx.bumps.plan;
+        Ok(())
+    }
+
+    /// Admin only: retires a plan from sale, or puts it back
+    pub fn set_plan_status(ctx: Context<SetPlanStatus>, active: bool) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_plan_status");
+        ctx.accounts.plan.active = active;
+        Ok(())
+    }
+
+    /// Admin only: the Pyth feed that prices USD plans, with the oldest update
+    /// and widest confidence interval payments accept. An all-zero feed id
+    /// turns USD pricing off.
+    pub fn set_price_oracle(
+        ctx: Context<AdminConfig>,
+        pyth_feed_id: [u8; 32],
+        max_oracle_age: i64,
+        max_oracle_conf_bps: u16,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_price_oracle");
+        require!(max_oracle_age > 0, ErrorCode::InvalidDuration);
+        require!(max_oracle_conf_bps <= 10_000, ErrorCode::InvalidPriceUpdate);
+        let config = &mut ctx.accounts.config;
+        config.pyth_feed_id = pyth_feed_id;
+        config.max_oracle_age = max_oracle_age;
+        config.max_oracle_conf_bps = max_oracle_conf_bps;
+        Ok(())
+    }
+
+    /// Admin only: the free trial `start_trial` grants, zero to stop new trials
+    pub fn set_trial_duration(ctx: Context<AdminConfig>, trial_duration: i64) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_trial_duration");
+        require!(trial_duration >= 0, ErrorCode::InvalidDuration);
+        ctx.accounts.config.trial_duration = trial_duration;
+        Ok(())
+    }
+
+    /// Admin only: the per-second rate pay-as-you-go streams settle at, zero
+    /// to stop new deposits. Settling always uses the current rate.
+    pub fn set_stream_rate(ctx: Context<AdminConfig>, stream_rate: u64) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_stream_rate");
+        ctx.accounts.config.stream_rate = stream_rate;
+        Ok(())
+    }
+
+    /// Admin only: how long past expiry a renewal still counts as on time and
+    /// extends from the old expiry; zero restarts every lapsed renewal from now
+    pub fn set_grace_period(ctx: Context<AdminConfig>, grace_period: i64) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_grace_period");
+        require!(grace_period >= 0, ErrorCode::InvalidDuration);
+        ctx.accounts.config.grace_period = grace_period;
+        Ok(())
+    }
+
+    /// Admin only: adds `program` to or drops it from the partner programs
+    /// allowed to pay through `process_payment_via_cpi`
+    pub fn set_cpi_caller(ctx: Context<AdminConfig>, program: Pubkey, allowed: bool) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_cpi_caller");
+        require_keys_neq!(program, Pubkey::default(), ErrorCode::InvalidCpiCaller);
+        let config = &mut ctx.accounts.config;
+        let slot = match (allowed, config.allows_cpi_caller(&program)) {
+            (true, true) | (false, false) => return Ok(()),
+            (true, false) => Pubkey::default(),
+            (false, true) => program,
+        };
+        let index = config
+            .cpi_callers
+            .iter()
+            .position(|caller| *caller == slot)
+            .ok_or(ErrorCode::CpiCallersFull)?;
+        config.cpi_callers[index] = if allowed { program } else { Pubkey::default() };
+        Ok(())
+    }
+
+    /// Admin only: caps how far past now `process_payment` can push an expiry,
+    /// zero for no cap. With `clamp` set, a payment past the cap buys the time
+    /// up to it instead of failing.
+    pub fn set_prepaid_cap(
+        ctx: Context<AdminConfig>,
+        max_prepaid_seconds: i64,
+        clamp: bool,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_prepaid_cap");
+        require!(max_prepaid_seconds >= 0, ErrorCode::InvalidDuration);
+        let config = &mut ctx.accounts.config;
+        config.max_prepaid_seconds = max_prepaid_seconds;
+        config.clamp_prepaid = clamp;
+        Ok(())
+    }
+
+    /// Admin only: parks `process_payment` burn shares in the instance-mint
+    /// pending-burn vault until `threshold` base units have accrued, saving
+    /// payments the burn CPI and the mint write lock. A zero threshold goes
+    /// back to burning inline.
+    pub fn set_burn_batching(ctx: Context<SetBurnBatching>, threshold: u64) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_burn_batching");
+        let config = &mut ctx.accounts.config;
+        config.burn_flush_threshold = threshold;
+        config.pending_burn_vault = ctx.accounts.pending_burn_vault.key();
+        Ok(())
+    }
+
+    /// Permissionless: burns the pending-burn vault's balance in one CPI once it
+    /// has reached the instance's flush threshold
+    pub fn flush_burn(ctx: Context<FlushBurn>) -> Result<()> {
+        let amount = ctx.accounts.pending_burn_vault.amount;
+        require!(
+            amount >= ctx.accounts.config.burn_flush_threshold,
+            ErrorCode::BelowFlushThreshold
+        );
+        burn_vault(
+            &ctx.accounts.config.key(),
+            ctx.bumps.vault_authority,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.pending_burn_vault.to_account_info(),
+            ctx.accounts.vault_authority.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.mint.decimals,
+            amount,
+        )?;
+        msg!("Flushed {} pending burn", amount);
+        Ok(())
+    }
+
+    /// Admin only: routes the insurance share of instance payments through
+    /// `insurance_escrow` until it is swept, or turns that off again. An
+    /// escrow has to be swept empty before it can be swapped out.
+    pub fn set_insurance_escrow(ctx: Context<SetInsuranceEscrow>, enabled: bool) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_insurance_escrow");
+        let config = &mut ctx.accounts.config;
+        let escrow = &ctx.accounts.insurance_escrow;
+        if config.escrows_insurance() && config.insurance_escrow != escrow.key() {
+            return err!(ErrorCode::InvalidVault);
+        }
+        if !enabled {
+            require!(escrow.amount == 0, ErrorCode::EscrowNotEmpty);
+        }
+        config.insurance_escrow = if enabled {
+            escrow.key()
+        } else {
+            Pubkey::default()
+        };
+        Ok(())
+    }
+
+    /// Permissionless: moves the escrow's full balance on to the insurance vault
+    pub fn sweep_insurance_escrow(ctx: Context<SweepInsuranceEscrow>) -> Result<()> {
+        let amount = ctx.accounts.insurance_escrow.amount;
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let config_key = ctx.accounts.config.key();
+        let escrow_seeds: &[&[&[u8]]] = &[&[
+            VaultKind::InsuranceEscrow.seed(),
+            config_key.as_ref(),
+            &[ctx.bumps.escrow_authority],
+        ]];
+        transfer_tokens(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.insurance_escrow.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(&ctx.accounts.mint.to_account_info()),
+            ctx.accounts.escrow_authority.to_account_info(),
+            escrow_seeds,
+            amount,
+        )?;
+        ctx.accounts.config.escrow_swept_at = Clock::get()?.unix_timestamp;
+        msg!("Swept {} insurance escrow", amount);
+        Ok(())
+    }
+
+    /// Admin only: returns `amount` of not-yet-swept escrow for the payment on
+    /// `receipt` to `user` and takes back the matching share of the time it
+    /// bought. Only a payment made into the escrow since its last sweep has
+    /// escrow to refund from; the rest belongs to other payers.
+    pub fn refund(ctx: Context<Refund>, amount: u64) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "refund");
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.config;
+        let receipt = &mut ctx.accounts.receipt;
+        require!(
+            receipt.escrowed && receipt.paid_at > config.escrow_swept_at,
+            ErrorCode::RefundNotEscrowed
+        );
+        require!(
+            amount <= receipt.insurance_amount.saturating_sub(receipt.refunded),
+            ErrorCode::RefundExceedsPayment
+        );
+        receipt.refunded += amount;
+
+        let config_key = config.key();
+        let escrow_seeds: &[&[&[u8]]] = &[&[
+            VaultKind::InsuranceEscrow.seed(),
+            config_key.as_ref(),
+            &[ctx.bumps.escrow_authority],
+        ]];
+        transfer_tokens(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.insurance_escrow.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            Some(&ctx.accounts.mint.to_account_info()),
+            ctx.accounts.escrow_authority.to_account_info(),
+            escrow_seeds,
+            amount,
+        )?;
+
+        // Scaled by what the payment cost and bought, not the current price
+        let rollback = ((amount as u128).saturating_mul(receipt.duration.max(0) as u128)
+            / receipt.amount as u128)
+            .min(i64::MAX as u128) as i64;
+        let subscription = &mut ctx.accounts.subscription;
+        let expires_at = subscription.expires_at().saturating_sub(rollback).max(0);
+        if rollback > 0 {
+            subscription.set_expires_at(expires_at)?;
+        }
+
+        let user = ctx.accounts.user.key();
+        if let Some(outbox) = ctx.accounts.outbox.as_ref() {
+            outbox
+                .load_mut()?
+                .push(OutboxEntry::REFUND_ISSUED, user, amount, now);
+        }
+        emit!(RefundIssued {
+            config: config_key,
+            user,
+            amount,
+            expires_at: subscription.expires_at(),
+        });
+        Ok(())
+    }
+
+    /// Admin only: a discount code good for `max_uses` payments until
+    /// `expires_at`. Only `code_hash`, e.g. the code's sha256, goes on chain;
+    /// payers redeem it by passing the promo account to `process_payment`.
+    pub fn create_promo(
+        ctx: Context<CreatePromo>,
+        code_hash: [u8; 32],
+        discount_bps: u16,
+        max_uses: u32,
+        expires_at: i64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "create_promo");
+        require!(
+            discount_bps > 0 && discount_bps <= 10_000,
+            ErrorCode::InvalidPromo
+        );
+        require!(max_uses > 0, ErrorCode::InvalidPromo);
+        require!(
+            expires_at > Clock::get()?.unix_timestamp,
+            ErrorCode::InvalidPromo
+        );
+
+        let promo = &mut ctx.accounts.promo;
+        promo.config = ctx.accounts.config.key();
+        promo.code_hash = code_hash;
+        promo.discount_bps = discount_bps;
+        promo.remaining_uses = max_uses;
+        promo.expires_at = expires_at;
+        promo.bump = ctx.bumps.promo;
+        Ok(())
+    }
+
+    /// Admin only: the USD price of a period paid in an LST, in millionths of
+    /// a dollar, and the Pyth SOL/USD feed that converts it to lamports at
+    /// payment time. Updates are held to the `set_price_oracle` age and
+    /// confidence limits. A zero price turns LST payments off.
+    pub fn set_lst_price(
+        ctx: Context<AdminConfig>,
+        lst_usd_price: u64,
+        sol_usd_feed_id: [u8; 32],
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_lst_price");
+        require!(
+            lst_usd_price == 0 || sol_usd_feed_id != [0; 32],
+            ErrorCode::InvalidPriceUpdate
+        );
+        let config = &mut ctx.accounts.config;
+        config.lst_usd_price = lst_usd_price;
+        config.sol_usd_feed_id = sol_usd_feed_id;
+        Ok(())
+    }
+
+    /// Admin only: burns `process_payment`'s whole burn share, or pays part of
+    /// it into `staking_vault` as staking rewards. Routing tables keep their
+    /// own routes either way.
+    pub fn set_burn_mode(ctx: Context<SetBurnMode>, burn_mode: BurnMode) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_burn_mode");
+        let staking_vault = match burn_mode {
+            BurnMode::FullBurn => Pubkey::default(),
+            BurnMode::SplitWithStaking { staking_bps } => {
+                require!(
+                    staking_bps > 0 && staking_bps <= 10_000,
+                    ErrorCode::InvalidBurnMode
+                );
+                ctx.accounts
+                    .staking_vault
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingStakingVault)?
+                    .key()
+            }
+        };
+        let config = &mut ctx.accounts.config;
+        config.burn_mode = burn_mode;
+        config.staking_vault = staking_vault;
+        Ok(())
+    }
+
+    /// Admin only: schedules the router's wind-down. From `sunset_at` every
+    /// payment path fails with `ProgramSunset` pointing at `successor_program`,
+    /// while closing subscriptions, refunds and withdrawals keep working so
+    /// users can leave. A zero `sunset_at` calls the sunset off.
+    pub fn set_sunset(
+        ctx: Context<AdminConfig>,
+        successor_program: Pubkey,
+        sunset_at: i64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_sunset");
+        require!(sunset_at >= 0, ErrorCode::InvalidSunset);
+        if sunset_at > 0 {
+            require_keys_neq!(
+                successor_program,
+                Pubkey::default(),
+                ErrorCode::InvalidSunset
+            );
+            require_keys_neq!(successor_program, crate::ID, ErrorCode::InvalidSunset);
+        }
+        let config = &mut ctx.accounts.config;
+        config.successor_program = if sunset_at > 0 {
+            successor_program
+        } else {
+            Pubkey::default()
+        };
+        config.sunset_at = sunset_at;
+        emit!(SunsetScheduled {
+            config: config.key(),
+            successor_program: config.successor_program,
+            sunset_at,
+        });
+        Ok(())
+    }
+
+    /// Admin only: the smallest amount any payment accepts and how many
+    /// slots must pass between payments on one subscription. Zero turns either off.
+    pub fn set_payment_limits(
+        ctx: Context<AdminConfig>,
+        min_payment_amount: u64,
+        min_payment_interval_slots: u64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_payment_limits");
+        let config = &mut ctx.accounts.config;
+        config.min_payment_amount = min_payment_amount;
+        config.min_payment_interval_slots = min_payment_interval_slots;
+        Ok(())
+    }
+
+    /// Admin only: prices a period paid through `process_payment_sol` and pins
+    /// the wSOL account its insurance share goes to. Zero turns SOL payments off.
+    pub fn set_sol_payments(ctx: Context<SetSolPayments>, sol_price_lamports: u64) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_sol_payments");
+        let config = &mut ctx.accounts.config;
+        config.sol_price_lamports = sol_price_lamports;
+        config.sol_insurance_vault = ctx.accounts.sol_insurance_vault.key();
+        Ok(())
+    }
+
+    /// Admin only: first step of handing the instance to `new_admin`, which
+    /// only takes over once it signs `accept_admin`. Proposing the default key
+    /// withdraws a pending proposal.
+    pub fn propose_admin(ctx: Context<AdminConfig>, new_admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.pending_admin = new_admin;
+        emit!(AdminProposed {
+            config: config.key(),
+            admin: config.admin,
+            pending_admin: new_admin,
+        });
+        Ok(())
+    }
+
+    /// Signed by the proposed admin, e.g. a multisig vault through CPI
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let previous_admin = config.admin;
+        config.admin = config.pending_admin;
+        config.pending_admin = Pubkey::default();
+        emit!(AdminAccepted {
+            config: config.key(),
+            previous_admin,
+            admin: config.admin,
+        });
+        Ok(())
+    }
+
+    pub fn set_operator(ctx: Context<AdminConfig>, operator: Pubkey) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_operator");
+        ctx.accounts.config.operator = operator;
+        Ok(())
+    }
+
+    pub fn set_require_receipts(ctx: Context<AdminConfig>, required: bool) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_require_receipts");
+        ctx.accounts.config.require_receipts = required;
+        Ok(())
+    }
+
+    /// Admin only: splits `process_payment` across up to [`MAX_ROUTES`]
+    /// destinations instead of the two-way split. A route to the default key
+    /// burns its share. Other payment paths keep the two-way split.
+    pub fn set_routing_table(ctx: Context<SetRoutingTable>, routes: Vec<Route>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_routing_table");
+        require!(
+            !routes.is_empty() && routes.len() <= MAX_ROUTES,
+            ErrorCode::InvalidRoutingTable
+        );
+        require!(
+            routes.iter().all(|route| route.bps > 0)
+                && routes.iter().filter(|route| route.burns()).count() <= 1,
+            ErrorCode::InvalidRoutingTable
+        );
+        require!(
+            routes.iter().map(|route| route.bps as u64).sum::<u64>() == 10_000,
+            ErrorCode::InvalidSplit
+        );
+
+        let routing_table = &mut ctx.accounts.routing_table;
+        routing_table.config = ctx.accounts.config.key();
+        routing_table.routes = [Route::default(); MAX_ROUTES];
+        routing_table.routes[..routes.len()].copy_from_slice(&routes);
+        routing_table.route_count = routes.len() as u8;
+        routing_table.bump = ctx.bumps.routing_table;
+        ctx.accounts.config.use_routing_table = true;
+        Ok(())
+    }
+
+    /// Admin only: returns `process_payment` to the two-way split
+    pub fn clear_routing_table(ctx: Context<ClearRoutingTable>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "clear_routing_table");
+        ctx.accounts.config.use_routing_table = false;
+        Ok(())
+    }
+
+    /// Admin only: registers, or repoints and reactivates, Percolator slab
+    /// `slab_id` and its insurance vault. `process_payment` routes to a slab's
+    /// vault when given its id, and to the instance vault otherwise.
+    pub fn register_slab(ctx: Context<RegisterSlab>, slab_id: u64) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "register_slab");
+        let slab_registry = &mut ctx.accounts.slab_registry;
+        slab_registry.config = ctx.accounts.config.key();
+        slab_registry.bump = ctx.bumps.slab_registry;
+
+        let slab = Slab {
+            slab_id,
+            insurance_vault: ctx.accounts.insurance_vault.key(),
+            active: true,
+        };
+        let count = slab_registry.slab_count as usize;
+        match slab_registry.slabs[..count]
+            .iter_mut()
+            .find(|registered| registered.slab_id == slab_id)
+        {
+            Some(registered) => *registered = slab,
+            None => {
+                require!(count < MAX_SLABS, ErrorCode::InvalidSlab);
+                slab_registry.slabs[count] = slab;
+                slab_registry.slab_count += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Admin only: stops routing payments to slab `slab_id`; registering it
+    /// again reactivates it
+    pub fn deactivate_slab(ctx: Context<DeactivateSlab>, slab_id: u64) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "deactivate_slab");
+        let slab_registry = &mut ctx.accounts.slab_registry;
+        let count = slab_registry.slab_count as usize;
+        let slab = slab_registry.slabs[..count]
+            .iter_mut()
+            .find(|slab| slab.slab_id == slab_id)
+            .ok_or(ErrorCode::InvalidSlab)?;
+        slab.active = false;
+        Ok(())
+    }
+
+    /// Admin only: stops every payment path at once, e.g. while the insurance
+    /// vault is compromised. Admin and read-only instructions keep working.
+    pub fn pause(ctx: Context<AdminConfig>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "pause");
+        ctx.accounts.config.paused = true;
+        Ok(())
+    }
+
+    pub fn unpause(ctx: Context<AdminConfig>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "unpause");
+        ctx.accounts.config.paused = false;
+        Ok(())
+    }
+
+    /// Share of every subscription resale routed through the split and burn
+    pub fn set_resale_royalty(ctx: Context<AdminConfig>, royalty_bps: u16) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_resale_royalty");
+        require!(royalty_bps <= 10_000, ErrorCode::InvalidPromo);
+        ctx.accounts.config.resale_royalty_bps = royalty_bps;
+        Ok(())
+    }
+
+    /// Share of every referred `process_payment` paid to its referrer
+    pub fn set_referral_share(ctx: Context<AdminConfig>, referral_bps: u16) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_referral_share");
+        require!(referral_bps <= 10_000, ErrorCode::InvalidReferral);
+        ctx.accounts.config.referral_bps = referral_bps;
+        Ok(())
+    }
+
+    /// Share of every auto-renewal paid to the cranker that submits it
+    pub fn set_renewal_bounty(ctx: Context<AdminConfig>, bounty_bps: u16) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_renewal_bounty");
+        require!(bounty_bps <= 10_000, ErrorCode::InvalidAutoRenew);
+        ctx.accounts.config.renewal_bounty_bps = bounty_bps;
+        Ok(())
+    }
+
+    /// Key whose signed quotes price `process_payment`; the default key turns
+    /// quoting off. Other payment paths keep charging the configured price.
+    pub fn set_quote_authority(ctx: Context<AdminConfig>, quote_authority: Pubkey) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_quote_authority");
+        ctx.accounts.config.quote_authority = quote_authority;
+        Ok(())
+    }
+
+    /// Lifetime burn needed for Bronze, Silver and Gold; zero disables a tier
+    pub fn set_loyalty_thresholds(
+        ctx: Context<AdminConfig>,
+        loyalty_thresholds: [u64; 3],
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_loyalty_thresholds");
+        let [bronze, silver, gold] = loyalty_thresholds;
+        require!(
+            (silver == 0 || silver >= bronze) && (gold == 0 || gold >= silver),
+            ErrorCode::InvalidLoyaltyThresholds
+        );
+        ctx.accounts.config.loyalty_thresholds = loyalty_thresholds;
+        Ok(())
+    }
+
+    pub fn set_pricing(
+        ctx: Context<SetPricing>,
+        price: u64,
+        promo_bps: u16,
+        promo_ends_at: i64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_pricing");
+        require!(promo_bps <= 10_000, ErrorCode::InvalidPromo);
+
+        let config = &mut ctx.accounts.config;
+        config.price = price;
+        config.promo_bps = promo_bps;
+        config.promo_ends_at = promo_ends_at;
+
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.price_feed.bump = ctx.bumps.price_feed;
+        write_price_feed(&mut ctx.accounts.price_feed, config, now);
+        Ok(())
+    }
+
+    /// Bonding-curve pricing: every `burn_step` of cumulative instance burn takes
+    /// `step_bps` off the base price, up to `max_bps`. The discount follows the
+    /// burn totals each time `aggregate_stats` runs. A zero step disables it.
+    pub fn set_price_curve(
+        ctx: Context<SetPricing>,
+        burn_step: u64,
+        step_bps: u16,
+        max_bps: u16,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_price_curve");
+        require!(
+            step_bps <= 10_000 && max_bps <= 10_000,
+            ErrorCode::InvalidPromo
+        );
+
+        let config = &mut ctx.accounts.config;
+        config.curve_burn_step = burn_step;
+        config.curve_step_bps = step_bps;
+        config.curve_max_bps = max_bps;
+        // Recomputed from the burn totals by the next aggregation
+        config.curve_discount_bps = 0;
+
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.price_feed.bump = ctx.bumps.price_feed;
+        write_price_feed(&mut ctx.accounts.price_feed, config, now);
+        Ok(())
+    }
+
+    /// Schedules a window where payments use a different split, e.g. 10000/0
+    /// for an insurance week. Windows can't start in the past, so every
+    /// promotion is visible on-chain before it applies. All zeros clears it.
+    pub fn schedule_split_promo(
+        ctx: Context<SetPricing>,
+        insurance_bps: u16,
+        burn_bps: u16,
+        starts_at: i64,
+        ends_at: i64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "schedule_split_promo");
+        let now = Clock::get()?.unix_timestamp;
+        let clearing = insurance_bps == 0 && burn_bps == 0 && starts_at == 0 && ends_at == 0;
+        if !clearing {
+            validate_split(insurance_bps, burn_bps)?;
+            require!(
+                starts_at >= now && ends_at > starts_at,
+                ErrorCode::InvalidSplitPromo
+            );
+        }
+
+        let config = &mut ctx.accounts.config;
+        config.split_promo_insurance_bps = insurance_bps;
+        config.split_promo_burn_bps = burn_bps;
+        config.split_promo_starts_at = starts_at;
+        config.split_promo_ends_at = ends_at;
+
+        ctx.accounts.price_feed.bump = ctx.bumps.price_feed;
+        write_price_feed(&mut ctx.accounts.price_feed, config, now);
+        Ok(())
+    }
+
+    /// Permissionless, so the feed drops an expired promo without an admin transaction
+    pub fn refresh_price_feed(ctx: Context<RefreshPriceFeed>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        write_price_feed(&mut ctx.accounts.price_feed, &ctx.accounts.config, now);
+        Ok(())
+    }
+
+    /// Test builds only: mints `amount` of the shared mock mint to the caller,
+    /// for instances created over it with `create_instance`
+    #[cfg(feature = "test-mode")]
+    pub fn test_faucet(ctx: Context<TestFaucet>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let faucet_seeds: &[&[&[u8]]] = &[&[b"test_mint", &[ctx.bumps.test_mint]]];
+        let mint_cpi_accounts = token_interface::MintToChecked {
+            mint: ctx.accounts.test_mint.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.test_mint.to_account_info(),
+        };
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            mint_cpi_accounts,
+            faucet_seeds,
+        );
+        token_interface::mint_to_checked(mint_ctx, amount, ctx.accounts.test_mint.decimals)
+    }
+
+    /// Test builds only: repoints an instance at any insurance vault, so
+    /// instance #0 can run without the mainnet SAKURA mint and Percolator vault
+    #[cfg(feature = "test-mode")]
+    pub fn test_set_vault(ctx: Context<TestSetVault>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.mint = ctx.accounts.insurance_vault.mint;
+        config.insurance_vault = ctx.accounts.insurance_vault.key();
+        config.vault_authority = ctx.accounts.insurance_vault.owner;
+        Ok(())
+    }
+
+    pub fn create_program_vault(ctx: Context<CreateProgramVault>, kind: VaultKind) -> Result<()> {
+        // Idempotent so deploy scripts and new-mint onboarding can call it blindly
+        let create_cpi_accounts = associated_token::Create {
+            payer: ctx.accounts.payer.to_account_info(),
+            associated_token: ctx.accounts.vault.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+        };
+        let create_ctx = CpiContext::new(
+            ctx.accounts.associated_token_program.to_account_info(),
+            create_cpi_accounts,
+        );
+        associated_token::create_idempotent(create_ctx)?;
+
+        msg!(
+            "Program vault {:?} ready at {}",
+            kind,
+            ctx.accounts.vault.key()
+        );
+        Ok(())
+    }
+
+    /// Burns everything accrued in the pending-burn vault, under the flush
+    /// threshold or not
+    pub fn burn_pending(ctx: Context<BurnPending>) -> Result<()> {
+        burn_vault(
+            &ctx.accounts.config.key(),
+            ctx.bumps.vault_authority,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.vault_authority.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.mint.decimals,
+            ctx.accounts.vault.amount,
+        )
+    }
+
+    /// Moves a treasury vault's full balance to an account held by the admin
+    pub fn sweep_treasury(ctx: Context<SweepTreasury>) -> Result<()> {
+        let amount = ctx.accounts.treasury_vault.amount;
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let config_key = ctx.accounts.config.key();
+        let treasury_seeds: &[&[&[u8]]] = &[&[
+            VaultKind::Treasury.seed(),
+            config_key.as_ref(),
+            &[ctx.bumps.treasury_authority],
+        ]];
+        let transfer_cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.treasury_vault.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.destination.to_account_info(),
+            authority: ctx.accounts.treasury_authority.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_cpi_accounts,
+            treasury_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)
+    }
+
+    /// Publishes the compensation for incident `incident_id`: `amount` of
+    /// `stable_mint` paid from the treasury and/or `extension` seconds of
+    /// subscription time per affected subscriber
+    pub fn publish_incident(
+        ctx: Context<PublishIncident>,
+        incident_id: u64,
+        amount: u64,
+        extension: i64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "publish_incident");
+        require!(amount > 0 || extension > 0, ErrorCode::InvalidAmount);
+        require!(extension >= 0, ErrorCode::InvalidDuration);
+
+        let incident = &mut ctx.accounts.incident;
+        incident.config = ctx.accounts.config.key();
+        incident.incident_id = incident_id;
+        incident.stable_mint = ctx.accounts.stable_mint.key();
+        incident.amount = amount;
+        incident.extension = extension;
+        incident.compensated = 0;
+        incident.bump = ctx.bumps.incident;
+        Ok(())
+    }
+
+    /// Admin-only: compensates one affected subscriber for a published
+    /// incident. The claim record makes a second payout for the pair fail.
+    pub fn compensate_subscriber(ctx: Context<CompensateSubscriber>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "compensate_subscriber");
+        let incident = &ctx.accounts.incident;
+        if incident.amount > 0 {
+            let config_key = ctx.accounts.config.key();
+            let treasury_seeds: &[&[&[u8]]] = &[&[
+                VaultKind::Treasury.seed(),
+                config_key.as_ref(),
+                &[ctx.bumps.treasury_authority],
+            ]];
+            let transfer_cpi_accounts = token_interface::TransferChecked {
+                from: ctx.accounts.treasury_vault.to_account_info(),
+                mint: ctx.accounts.stable_mint.to_account_info(),
+                to: ctx.accounts.user_stable_account.to_account_info(),
+                authority: ctx.accounts.treasury_authority.to_account_info(),
+            };
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                transfer_cpi_accounts,
+                treasury_seeds,
+            );
+            token_interface::transfer_checked(
+                transfer_ctx,
+                incident.amount,
+                ctx.accounts.stable_mint.decimals,
+            )?;
+        }
+        if incident.extension > 0 {
+            extend_subscription(&mut ctx.accounts.subscription, incident.extension)?;
+        }
+
+        let claim = &mut ctx.accounts.compensation_claim;
+        claim.incident = ctx.accounts.incident.key();
+        claim.user = ctx.accounts.user.key();
+        claim.claimed_at = Clock::get()?.unix_timestamp;
+        claim.bump = ctx.bumps.compensation_claim;
+
+        let incident = &mut ctx.accounts.incident;
+        incident.compensated = incident.compensated.checked_add(1).unwrap();
+        Ok(())
+    }
+
+    /// Admin-only: pushes every subscription in the batch `duration` further out,
+    /// so an outage can be made good without the affected users doing anything.
+    /// Remaining accounts are `(user, subscription)` pairs; revoked subscriptions
+    /// are skipped. Returns which entries were extended.
+    pub fn extend_subscriptions<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExtendSubscriptions<'info>>,
+        duration: i64,
+    ) -> Result<Vec<bool>> {
+        emit_admin_action(&ctx.accounts.config, "extend_subscriptions");
+        require!(duration > 0, ErrorCode::InvalidDuration);
+        let entries = ctx.remaining_accounts.chunks_exact(2);
+        require!(
+            entries.remainder().is_empty() && entries.len() > 0 && entries.len() <= MAX_CRANK_BATCH,
+            ErrorCode::InvalidBatch
+        );
+
+        let config_key = ctx.accounts.config.key();
+        let mut extended = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (subscription_key, _) = Pubkey::find_program_address(
+                &[b"subscription", config_key.as_ref(), entry[0].key.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(entry[1].key(), subscription_key, ErrorCode::InvalidBatch);
+            let mut subscription = Account::<Subscription>::try_from(&entry[1])?;
+
+            if subscription.status() == Subscription::STATUS_REVOKED {
+                extended.push(false);
+                continue;
+            }
+            let expires_at = extend_subscription(&mut subscription, duration)?;
+            subscription.exit(&crate::ID)?;
+            msg!("Extended {} to {}", entry[0].key(), expires_at);
+            extended.push(true);
+        }
+
+        Ok(extended)
+    }
+
+    pub fn create_receipt_mint(
+        ctx: Context<CreateReceiptMint>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "create_receipt_mint");
+        init_mint_metadata(
+            &ctx.accounts.config.key(),
+            &ctx.accounts.admin,
+            &ctx.accounts.receipt_mint.to_account_info(),
+            &ctx.accounts.mint_authority,
+            ctx.bumps.mint_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            name,
+            symbol,
+            uri,
+        )
+    }
+
+    /// Admin-only: creates the instance's soulbound receipt mint. Payments that
+    /// opt in mint the subscriber one non-transferable token if they don't hold
+    /// one; the mint authority PDA is also its permanent delegate, so lapsed
+    /// tokens can be burned without the holder.
+    pub fn create_soulbound_mint(
+        ctx: Context<CreateSoulboundMint>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "create_soulbound_mint");
+        let config_key = ctx.accounts.config.key();
+        let mint = ctx.accounts.soulbound_mint.to_account_info();
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let mint_authority = ctx.accounts.mint_authority.key();
+
+        // Anchor can't initialize the non-transferable extension, so the mint
+        // is laid out by hand
+        let space = token_interface::find_mint_account_size(Some(&vec![
+            ExtensionType::NonTransferable,
+            ExtensionType::PermanentDelegate,
+            ExtensionType::MetadataPointer,
+        ]))?;
+        let mint_seeds: &[&[&[u8]]] = &[&[
+            b"soulbound_mint",
+            config_key.as_ref(),
+            &[ctx.bumps.soulbound_mint],
+        ]];
+        let create_cpi_accounts = anchor_lang::system_program::CreateAccount {
+            from: ctx.accounts.admin.to_account_info(),
+            to: mint.clone(),
+        };
+        let create_ctx = CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            create_cpi_accounts,
+            mint_seeds,
+        );
+        anchor_lang::system_program::create_account(
+            create_ctx,
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &token_2022::ID,
+        )?;
+
+        token_interface::non_transferable_mint_initialize(CpiContext::new(
+            token_program.clone(),
+            token_interface::NonTransferableMintInitialize {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            },
+        ))?;
+        token_interface::permanent_delegate_initialize(
+            CpiContext::new(
+                token_program.clone(),
+                token_interface::PermanentDelegateInitialize {
+                    token_program_id: token_program.clone(),
+                    mint: mint.clone(),
+                },
+            ),
+            &mint_authority,
+        )?;
+        token_interface::metadata_pointer_initialize(
+            CpiContext::new(
+                token_program.clone(),
+                token_interface::MetadataPointerInitialize {
+                    token_program_id: token_program.clone(),
+                    mint: mint.clone(),
+                },
+            ),
+            Some(mint_authority),
+            Some(mint.key()),
+        )?;
+        token_2022::initialize_mint2(
+            CpiContext::new(
+                token_program,
+                token_2022::InitializeMint2 { mint: mint.clone() },
+            ),
+            0,
+            &mint_authority,
+            None,
+        )?;
+
+        init_mint_metadata(
+            &config_key,
+            &ctx.accounts.admin,
+            &mint,
+            &ctx.accounts.mint_authority,
+            ctx.bumps.mint_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            name,
+            symbol,
+            uri,
+        )
+    }
+
+    /// Permissionless: burns a lapsed or revoked subscriber's soulbound receipt
+    /// token as the mint's permanent delegate. Only the holder can close the
+    /// emptied account, so it stays with them; paying again mints a new token.
+    pub fn revoke_expired_receipt(ctx: Context<RevokeExpiredReceipt>) -> Result<()> {
+        require!(
+            !ctx.accounts
+                .subscription
+                .is_active(Clock::get()?.unix_timestamp),
+            ErrorCode::SubscriptionStillActive
+        );
+        let amount = ctx.accounts.soulbound_account.amount;
+        require!(amount > 0, ErrorCode::InvalidSoulboundAccount);
+
+        let config_key = ctx.accounts.config.key();
+        let authority_seeds: &[&[&[u8]]] = &[&[
+            b"mint_authority",
+            config_key.as_ref(),
+            &[ctx.bumps.mint_authority],
+        ]];
+        let burn_cpi_accounts = token_interface::BurnChecked {
+            mint: ctx.accounts.soulbound_mint.to_account_info(),
+            from: ctx.accounts.soulbound_account.to_account_info(),
+            authority: ctx.accounts.mint_authority.to_account_info(),
+        };
+        let burn_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            burn_cpi_accounts,
+            authority_seeds,
+        );
+        token_interface::burn_checked(burn_ctx, amount, 0)
+    }
+
+    /// Admin-only: mints future receipts as compressed NFTs into `merkle_tree`,
+    /// a Bubblegum tree whose tree delegate is the instance mint authority PDA.
+    /// Calling it again points receipts at a new tree.
+    pub fn set_receipt_tree(
+        ctx: Context<SetReceiptTree>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "set_receipt_tree");
+        let receipt_tree = &mut ctx.accounts.receipt_tree;
+        receipt_tree.config = ctx.accounts.config.key();
+        receipt_tree.merkle_tree = ctx.accounts.merkle_tree.key();
+        receipt_tree.name = name;
+        receipt_tree.symbol = symbol;
+        receipt_tree.uri = uri;
+        receipt_tree.bump = ctx.bumps.receipt_tree;
+        Ok(())
+    }
+
+    pub fn register_stream_funding(
+        ctx: Context<RegisterStreamFunding>,
+        amount_per_period: u64,
+        allowance: u64,
+    ) -> Result<()> {
+        require!(amount_per_period > 0, ErrorCode::InvalidAmount);
+        require!(
+            allowance >= amount_per_period,
+            ErrorCode::InsufficientAllowance
+        );
+
+        // Let the stream delegate pull renewals out of the account the stream pays into
+        let approve_cpi_accounts = Approve {
+            to: ctx.accounts.user_token_account.to_account_info(),
+            delegate: ctx.accounts.delegate.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let approve_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            approve_cpi_accounts,
+        );
+        token::approve(approve_ctx, allowance)?;
+
+        let stream_funding = &mut ctx.accounts.stream_funding;
+        stream_funding.user = ctx.accounts.user.key();
+        stream_funding.stream = ctx.accounts.stream.key();
+        stream_funding.token_account = ctx.accounts.user_token_account.key();
+        stream_funding.amount_per_period = amount_per_period;
+        stream_funding.last_funded_at = 0;
+        stream_funding.bump = ctx.bumps.stream_funding;
+
+        ctx.accounts.subscription.version = Subscription::VERSION;
+        Ok(())
+    }
+
+    pub fn crank_stream_funding<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CrankStreamFunding<'info>>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            ctx.accounts.subscription.expires_at()
+                <= now.checked_add(STREAM_RENEWAL_WINDOW).unwrap(),
+            ErrorCode::RenewalNotDue
+        );
+
+        let stream_funding = &ctx.accounts.stream_funding;
+        let amount = stream_funding.amount_per_period;
+
+        // 1. Optionally pull the vested balance out of the stream first; the
+        //    remaining accounts are forwarded verbatim to the withdraw instruction
+        if let Some(stream_program) = &ctx.accounts.stream_program {
+            require!(
+                ctx.remaining_accounts
+                    .iter()
+                    .any(|account| account.key() == stream_funding.stream),
+                ErrorCode::InvalidStream
+            );
+
+            let mut data = STREAMFLOW_WITHDRAW_DISCRIMINATOR.to_vec();
+            data.extend_from_slice(&amount.to_le_bytes());
+            let withdraw_ix = Instruction {
+                program_id: stream_program.key(),
+                accounts: ctx
+                    .remaining_accounts
+                    .iter()
+                    .map(|account| AccountMeta {
+                        pubkey: account.key(),
+                        is_signer: account.is_signer,
+                        is_writable: account.is_writable,
+                    })
+                    .collect(),
+                data,
+            };
+            invoke(&withdraw_ix, ctx.remaining_accounts)?;
+            ctx.accounts.user_token_account.reload()?;
+        }
+
+        // 2. Route one period through the normal split under the stream delegate PDA
+        let config_key = ctx.accounts.config.key();
+        let delegate_seeds: &[&[&[u8]]] = &[&[
+            b"stream_delegate",
+            config_key.as_ref(),
+            &[ctx.bumps.delegate],
+        ]];
+        route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.delegate.to_account_info(),
+            delegate_seeds,
+            amount,
+            effective_price(&ctx.accounts.config, now),
+            None,
+        )?;
+
+        // 3. Extend the subscription on behalf of the stream recipient
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            ctx.accounts.config.subscription_duration,
+            ctx.accounts.config.grace_period,
+        )?;
+        ctx.accounts.stream_funding.last_funded_at = now;
+
+        Ok(())
+    }
+
+    /// Renews up to [`MAX_CRANK_BATCH`] stream-funded subscriptions from balance
+    /// already paid out by their streams. Remaining accounts are
+    /// `(stream_funding, subscription, user_token_account)` triples. Entries
+    /// that aren't due or can't cover a period are skipped rather than failing
+    /// the batch; the return data holds one success flag per entry.
+    pub fn crank_stream_funding_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CrankStreamFundingBatch<'info>>,
+    ) -> Result<Vec<bool>> {
+        let entries = ctx.remaining_accounts.chunks_exact(3);
+        require!(
+            entries.remainder().is_empty() && entries.len() <= MAX_CRANK_BATCH,
+            ErrorCode::InvalidBatch
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.config;
+        let config_key = config.key();
+        let price = effective_price(config, now);
+        let delegate_key = ctx.accounts.delegate.key();
+        let delegate_seeds: &[&[&[u8]]] = &[&[
+            b"stream_delegate",
+            config_key.as_ref(),
+            &[ctx.bumps.delegate],
+        ]];
+
+        let mut renewed = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let mut stream_funding = Account::<StreamFunding>::try_from(&entry[0])?;
+            let user = stream_funding.user;
+            let funding_key = Pubkey::create_program_address(
+                &[
+                    b"stream_funding",
+                    config_key.as_ref(),
+                    user.as_ref(),
+                    &[stream_funding.bump],
+                ],
+                &crate::ID,
+            )
+            .map_err(|_| ErrorCode::InvalidStream)?;
+            require_keys_eq!(entry[0].key(), funding_key, ErrorCode::InvalidStream);
+
+            let (subscription_key, _) = Pubkey::find_program_address(
+                &[b"subscription", config_key.as_ref(), user.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(entry[1].key(), subscription_key, ErrorCode::InvalidStream);
+            let mut subscription = Account::<Subscription>::try_from(&entry[1])?;
+
+            require_keys_eq!(
+                entry[2].key(),
+                stream_funding.token_account,
+                ErrorCode::InvalidOwner
+            );
+            let token_account = Account::<TokenAccount>::try_from(&entry[2])?;
+
+            // Anything that would make the token CPI fail is checked up front,
+            // so one bad entry can't revert the whole batch
+            let amount = stream_funding.amount_per_period;
+            let due = subscription.expires_at() <= now.checked_add(STREAM_RENEWAL_WINDOW).unwrap();
+            let fundable = token_account.mint == config.mint
+                && !token_account.is_frozen()
+                && token_account.amount >= amount
+                && token_account.delegate == COption::Some(delegate_key)
+                && token_account.delegated_amount >= amount
+                && amount >= price
+                && amount > 0;
+            if !due || !fundable {
+                // A due renewal that can't be funded is queued for the user's notifier
+                if let (true, Some(outbox)) = (due, ctx.accounts.outbox.as_ref()) {
+                    let mut outbox = outbox.load_mut()?;
+                    let period = subscription.expires_at() as u64;
+                    if !outbox.contains(OutboxEntry::PAYMENT_FAILED, &user, period) {
+                        outbox.push(OutboxEntry::PAYMENT_FAILED, user, period, now);
+                    }
+                }
+                renewed.push(false);
+                continue;
+            }
+
+            route_payment(
+                config,
+                ctx.accounts.token_program.to_account_info(),
+                entry[2].clone(),
+                ctx.accounts.insurance_vault.to_account_info(),
+                Some(ctx.accounts.mint.to_account_info()),
+                ctx.accounts.delegate.to_account_info(),
+                delegate_seeds,
+                amount,
+                price,
+                None,
+            )?;
+
+            renew_prepaid(
+                config,
+                &mut subscription,
+                config.subscription_duration,
+                config.grace_period,
+            )?;
+            stream_funding.last_funded_at = now;
+            subscription.exit(&crate::ID)?;
+            stream_funding.exit(&crate::ID)?;
+            renewed.push(true);
+        }
+
+        Ok(renewed)
+    }
+
+    pub fn cancel_stream_funding(ctx: Context<CancelStreamFunding>) -> Result<()> {
+        let revoke_cpi_accounts = Revoke {
+            source: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let revoke_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            revoke_cpi_accounts,
+        );
+        token::revoke(revoke_ctx)
+    }
+
+    /// Locks `amount` into the user's pay-as-you-go stream, which
+    /// `settle_stream` draws a day at a time instead of a whole period upfront
+    pub fn deposit_stream(ctx: Context<DepositStream>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        require!(
+            ctx.accounts.config.stream_rate > 0,
+            ErrorCode::StreamsDisabled
+        );
+        enforce_not_paused(&ctx.accounts.config)?;
+
+        let transfer_cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.stream_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_cpi_accounts,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        let payment_stream = &mut ctx.accounts.payment_stream;
+        payment_stream.config = ctx.accounts.config.key();
+        payment_stream.user = ctx.accounts.user.key();
+        payment_stream.bump = ctx.bumps.payment_stream;
+        Ok(())
+    }
+
+    /// Permissionless: once the user's subscription is within
+    /// [`STREAM_RENEWAL_WINDOW`] of expiring, routes [`STREAM_SETTLE_PERIOD`]
+    /// at the instance's stream rate out of their stream and extends it by
+    /// that much
+    pub fn settle_stream(ctx: Context<SettleStream>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.config;
+        enforce_not_paused(config)?;
+        enforce_mint_cutover(config, now)?;
+        require!(config.stream_rate > 0, ErrorCode::StreamsDisabled);
+        require!(
+            ctx.accounts.subscription.expires_at()
+                <= now.checked_add(STREAM_RENEWAL_WINDOW).unwrap(),
+            ErrorCode::RenewalNotDue
+        );
+        let amount = config
+            .stream_rate
+            .checked_mul(STREAM_SETTLE_PERIOD as u64)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(
+            ctx.accounts.stream_vault.amount >= amount,
+            ErrorCode::StreamBalanceTooLow
+        );
+
+        let payment_stream = &ctx.accounts.payment_stream;
+        let stream_seeds: &[&[&[u8]]] = &[&[
+            b"payment_stream",
+            payment_stream.config.as_ref(),
+            payment_stream.user.as_ref(),
+            &[payment_stream.bump],
+        ]];
+        let (insured, burned) = route_payment(
+            config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.stream_vault.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.payment_stream.to_account_info(),
+            stream_seeds,
+            amount,
+            amount,
+            None,
+        )?;
+        record_payment(
+            Some(&mut ctx.accounts.stats_shard),
+            false,
+            amount,
+            insured,
+            burned,
+            0,
+            ctx.accounts.subscription.expires_at() == 0,
+        )?;
+
+        renew_prepaid(
+            config,
+            &mut ctx.accounts.subscription,
+            STREAM_SETTLE_PERIOD,
+            config.grace_period,
+        )?;
+        let expires_at = ctx.accounts.subscription.expires_at();
+        ctx.accounts.payment_stream.settled_at = now;
+        emit!(StreamSettled {
+            config: config.key(),
+            user: ctx.accounts.user.key(),
+            amount,
+            expires_at,
+        });
+        Ok(())
+    }
+
+    /// Hands the stream's unsettled balance back to the user and closes it;
+    /// time already settled stays on the subscription
+    pub fn withdraw_stream(ctx: Context<WithdrawStream>) -> Result<()> {
+        let payment_stream = &ctx.accounts.payment_stream;
+        let stream_seeds: &[&[&[u8]]] = &[&[
+            b"payment_stream",
+            payment_stream.config.as_ref(),
+            payment_stream.user.as_ref(),
+            &[payment_stream.bump],
+        ]];
+        let transfer_cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.stream_vault.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.payment_stream.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_cpi_accounts,
+            stream_seeds,
+        );
+        token_interface::transfer_checked(
+            transfer_ctx,
+            ctx.accounts.stream_vault.amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        let close_cpi_accounts = token_interface::CloseAccount {
+            account: ctx.accounts.stream_vault.to_account_info(),
+            destination: ctx.accounts.user.to_account_info(),
+            authority: ctx.accounts.payment_stream.to_account_info(),
+        };
+        let close_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            close_cpi_accounts,
+            stream_seeds,
+        );
+        token_interface::close_account(close_ctx)
+    }
+
+    /// Starts a free trial of the instance's `trial_duration` on a wallet that
+    /// has never been subscribed. The trial record outlives the subscription,
+    /// so closing it doesn't earn a second trial.
+    pub fn start_trial(ctx: Context<StartTrial>) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(config.trial_duration > 0, ErrorCode::TrialsDisabled);
+        enforce_not_paused(config)?;
+        require!(
+            ctx.accounts.subscription.expires_at() == 0,
+            ErrorCode::TrialUnavailable
+        );
+
+        let expires_at =
+            extend_subscription(&mut ctx.accounts.subscription, config.trial_duration)?;
+        let trial = &mut ctx.accounts.trial;
+        trial.config = config.key();
+        trial.user = ctx.accounts.user.key();
+        trial.started_at = Clock::get()?.unix_timestamp;
+        trial.bump = ctx.bumps.trial;
+
+        emit!(TrialStarted {
+            config: config.key(),
+            user: ctx.accounts.user.key(),
+            expires_at,
+        });
+        Ok(())
+    }
+
+    /// Approves the router delegate for `renewals` periods of the instance
+    /// price, or of `plan`'s, so anyone can renew the subscription once it
+    /// expires. The approval replaces any stream-funding allowance on the account.
+    pub fn enable_auto_renew(ctx: Context<EnableAutoRenew>, renewals: u32) -> Result<()> {
+        require!(renewals > 0, ErrorCode::InvalidAmount);
+        let now = Clock::get()?.unix_timestamp;
+        let price = match ctx.accounts.plan.as_ref() {
+            Some(plan) => {
+                require!(plan.active, ErrorCode::PlanInactive);
+                // The allowance is fixed up front, which a USD price can't be
+                require!(plan.usd_price == 0, ErrorCode::InvalidAutoRenew);
+                plan.price
+            }
+            None => effective_price(&ctx.accounts.config, now),
+        };
+
+        let approve_cpi_accounts = token_interface::Approve {
+            to: ctx.accounts.user_token_account.to_account_info(),
+            delegate: ctx.accounts.delegate.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let approve_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            approve_cpi_accounts,
+        );
+        let allowance = price
+            .checked_mul(renewals as u64)
+            .ok_or(ErrorCode::MathOverflow)?;
+        token_interface::approve(approve_ctx, allowance)?;
+
+        let auto_renew = &mut ctx.accounts.auto_renew;
+        auto_renew.user = ctx.accounts.user.key();
+        auto_renew.token_account = ctx.accounts.user_token_account.key();
+        auto_renew.plan = ctx
+            .accounts
+            .plan
+            .as_ref()
+            .map_or(Pubkey::default(), |plan| plan.key());
+        auto_renew.remaining_renewals = renewals;
+        auto_renew.bump = ctx.bumps.auto_renew;
+
+        ctx.accounts.subscription.version = Subscription::VERSION;
+        Ok(())
+    }
+
+    /// Permissionless: renews an expired auto-renew subscription for one
+    /// period out of the delegated allowance. A `cranker_token_account` collects
+    /// the instance's renewal bounty off the top of the payment.
+    pub fn crank_renewal(ctx: Context<CrankRenewal>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            ctx.accounts.subscription.expires_at() <= now,
+            ErrorCode::RenewalNotDue
+        );
+        require!(
+            ctx.accounts.auto_renew.remaining_renewals > 0,
+            ErrorCode::AutoRenewExhausted
+        );
+
+        let auto_renew_plan = ctx.accounts.auto_renew.plan;
+        let (price, duration) = match ctx.accounts.plan.as_ref() {
+            Some(plan) => {
+                require_keys_eq!(plan.key(), auto_renew_plan, ErrorCode::InvalidAutoRenew);
+                require!(plan.active, ErrorCode::PlanInactive);
+                require!(plan.usd_price == 0, ErrorCode::InvalidAutoRenew);
+                (plan.price, plan.duration)
+            }
+            None => {
+                require_keys_eq!(
+                    auto_renew_plan,
+                    Pubkey::default(),
+                    ErrorCode::InvalidAutoRenew
+                );
+                (
+                    effective_price(&ctx.accounts.config, now),
+                    ctx.accounts.config.subscription_duration,
+                )
+            }
+        };
+
+        // 1. The cranker's bounty comes off the top of the period's price
+        let config_key = ctx.accounts.config.key();
+        let delegate_seeds: &[&[&[u8]]] = &[&[
+            b"renew_delegate",
+            config_key.as_ref(),
+            &[ctx.bumps.delegate],
+        ]];
+        let bounty = match ctx.accounts.cranker_token_account.as_ref() {
+            Some(cranker_token_account) => {
+                let bounty = (price as u128 * ctx.accounts.config.renewal_bounty_bps as u128
+                    / 10_000) as u64;
+                if bounty > 0 {
+                    transfer_tokens(
+                        ctx.accounts.token_program.to_account_info(),
+                        ctx.accounts.user_token_account.to_account_info(),
+                        cranker_token_account.to_account_info(),
+                        Some(&ctx.accounts.mint.to_account_info()),
+                        ctx.accounts.delegate.to_account_info(),
+                        delegate_seeds,
+                        bounty,
+                    )?;
+                }
+                bounty
+            }
+            None => 0,
+        };
+
+        // 2. The rest is routed through the normal split under the renewal delegate PDA
+        let amount = price.checked_sub(bounty).unwrap();
+        let (insured, burned) = route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.delegate.to_account_info(),
+            delegate_seeds,
+            amount,
+            amount,
+            None,
+        )?;
+
+        let user = ctx.accounts.auto_renew.user;
+        ctx.accounts.auto_renew.remaining_renewals -= 1;
+        let previous_expires_at = ctx.accounts.subscription.expires_at();
+        let previous_state = ctx
+            .accounts
+            .subscription
+            .state(now, ctx.accounts.config.grace_period);
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            duration,
+            ctx.accounts.config.grace_period,
+        )?;
+        let expires_at = ctx.accounts.subscription.expires_at();
+        emit_payment(
+            config_key,
+            user,
+            user,
+            price,
+            (insured, burned, 0, 0),
+            None,
+            previous_state,
+            previous_expires_at,
+            expires_at,
+        );
+        Ok(())
+    }
+
+    /// Stops auto-renewal and revokes the router delegate's allowance
+    pub fn disable_auto_renew(ctx: Context<DisableAutoRenew>) -> Result<()> {
+        let revoke_cpi_accounts = token_interface::Revoke {
+            source: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let revoke_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            revoke_cpi_accounts,
+        );
+        token_interface::revoke(revoke_ctx)
+    }
+
+    pub fn create_sponsor_pool(
+        ctx: Context<CreateSponsorPool>,
+        pool_id: u64,
+        amount_per_draw: u64,
+        deposit: u64,
+    ) -> Result<()> {
+        require!(amount_per_draw > 0, ErrorCode::InvalidAmount);
+        require!(
+            deposit >= amount_per_draw,
+            ErrorCode::InsufficientPoolBalance
+        );
+
+        let pool = &mut ctx.accounts.sponsor_pool;
+        pool.config = ctx.accounts.config.key();
+        pool.sponsor = ctx.accounts.sponsor.key();
+        pool.pool_id = pool_id;
+        pool.amount_per_draw = amount_per_draw;
+        pool.draws_claimed = 0;
+        pool.bump = ctx.bumps.sponsor_pool;
+
+        let transfer_cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.sponsor_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.pool_vault.to_account_info(),
+            authority: ctx.accounts.sponsor.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_cpi_accounts,
+        );
+        token_interface::transfer_checked(transfer_ctx, deposit, ctx.accounts.mint.decimals)
+    }
+
+    pub fn add_sponsored_wallet(ctx: Context<AddSponsoredWallet>, wallet: Pubkey) -> Result<()> {
+        let allowance = &mut ctx.accounts.allowance;
+        allowance.pool = ctx.accounts.sponsor_pool.key();
+        allowance.wallet = wallet;
+        allowance.claimed = false;
+        allowance.bump = ctx.bumps.allowance;
+        Ok(())
+    }
+
+    pub fn draw_sponsorship(ctx: Context<DrawSponsorship>) -> Result<()> {
+        require!(
+            !ctx.accounts.allowance.claimed,
+            ErrorCode::SponsorshipClaimed
+        );
+
+        // 1. The pool pays one period through the normal split under its own PDA
+        let pool = &ctx.accounts.sponsor_pool;
+        let pool_id = pool.pool_id.to_le_bytes();
+        let pool_seeds: &[&[&[u8]]] = &[&[
+            b"sponsor_pool",
+            pool.config.as_ref(),
+            pool.sponsor.as_ref(),
+            pool_id.as_ref(),
+            &[pool.bump],
+        ]];
+        route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.pool_vault.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.sponsor_pool.to_account_info(),
+            pool_seeds,
+            pool.amount_per_draw,
+            effective_price(&ctx.accounts.config, Clock::get()?.unix_timestamp),
+            None,
+        )?;
+
+        // 2. Each allowlisted wallet draws exactly once
+        ctx.accounts.allowance.claimed = true;
+        let pool = &mut ctx.accounts.sponsor_pool;
+        pool.draws_claimed = pool.draws_claimed.checked_add(1).unwrap();
+
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            ctx.accounts.config.subscription_duration,
+            ctx.accounts.config.grace_period,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn close_sponsor_pool(ctx: Context<CloseSponsorPool>) -> Result<()> {
+        let pool = &ctx.accounts.sponsor_pool;
+        let pool_id = pool.pool_id.to_le_bytes();
+        let pool_seeds: &[&[&[u8]]] = &[&[
+            b"sponsor_pool",
+            pool.config.as_ref(),
+            pool.sponsor.as_ref(),
+            pool_id.as_ref(),
+            &[pool.bump],
+        ]];
+
+        // Return whatever the allowlist didn't draw, then reclaim the vault rent
+        let transfer_cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.pool_vault.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.sponsor_token_account.to_account_info(),
+            authority: ctx.accounts.sponsor_pool.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_cpi_accounts,
+            pool_seeds,
+        );
+        token_interface::transfer_checked(
+            transfer_ctx,
+            ctx.accounts.pool_vault.amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        let close_cpi_accounts = token::CloseAccount {
+            account: ctx.accounts.pool_vault.to_account_info(),
+            destination: ctx.accounts.sponsor.to_account_info(),
+            authority: ctx.accounts.sponsor_pool.to_account_info(),
+        };
+        let close_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            close_cpi_accounts,
+            pool_seeds,
+        );
+        token::close_account(close_ctx)
+    }
+
+    /// Sets up a shared treasury whose funds only move with both `first_signer`
+    /// and `second_signer` on the transaction, e.g. finance plus a manager
+    pub fn create_org_account(
+        ctx: Context<CreateOrgAccount>,
+        first_signer: Pubkey,
+        second_signer: Pubkey,
+    ) -> Result<()> {
+        require!(first_signer != second_signer, ErrorCode::DuplicateOrgSigner);
+
+        let org = &mut ctx.accounts.org_account;
+        org.config = ctx.accounts.config.key();
+        org.creator = ctx.accounts.creator.key();
+        org.first_signer = first_signer;
+        org.second_signer = second_signer;
+        org.bump = ctx.bumps.org_account;
+        Ok(())
+    }
+
+    /// Pays one period for `beneficiary` out of an org treasury
+    pub fn process_cosigned_payment(
+        ctx: Context<ProcessCosignedPayment>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.org_account.key(),
+            amount,
+        )?;
+
+        let org = &ctx.accounts.org_account;
+        let org_seeds: &[&[&[u8]]] = &[&[
+            b"org_account",
+            org.config.as_ref(),
+            org.creator.as_ref(),
+            &[org.bump],
+        ]];
+        let (insured, burned) = route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.org_treasury.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.org_account.to_account_info(),
+            org_seeds,
+            amount,
+            effective_price(&ctx.accounts.config, Clock::get()?.unix_timestamp),
+            None,
+        )?;
+        record_payment(
+            Some(&mut ctx.accounts.stats_shard),
+            false,
+            amount,
+            insured,
+            burned,
+            0,
+            ctx.accounts.subscription.expires_at() == 0,
+        )?;
+
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            ctx.accounts.config.subscription_duration,
+            ctx.accounts.config.grace_period,
+        )?;
+
+        Ok(())
+    }
+
+    /// Moves `amount` out of an org treasury, under the same two signatures
+    pub fn withdraw_org_treasury(ctx: Context<WithdrawOrgTreasury>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let org = &ctx.accounts.org_account;
+        let org_seeds: &[&[&[u8]]] = &[&[
+            b"org_account",
+            org.config.as_ref(),
+            org.creator.as_ref(),
+            &[org.bump],
+        ]];
+        let transfer_cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.org_treasury.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.destination.to_account_info(),
+            authority: ctx.accounts.org_account.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_cpi_accounts,
+            org_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)
+    }
+
+    /// Opens an organization for `owner` to buy member seats through, each
+    /// priced at `plan`'s price or, without one, the instance price
+    pub fn create_organization(ctx: Context<CreateOrganization>) -> Result<()> {
+        if let Some(plan) = ctx.accounts.plan.as_ref() {
+            // Seats are charged up front, which a USD price can't be
+            require!(plan.usd_price == 0, ErrorCode::InvalidOrgPlan);
+        }
+
+        let organization = &mut ctx.accounts.organization;
+        organization.config = ctx.accounts.config.key();
+        organization.owner = ctx.accounts.owner.key();
+        organization.plan = ctx
+            .accounts
+            .plan
+            .as_ref()
+            .map_or(Pubkey::default(), |plan| plan.key());
+        organization.bump = ctx.bumps.organization;
+        Ok(())
+    }
+
+    /// Pays one period for `seats` seats. Seats added while the organization
+    /// is still paid up are also charged for the rest of the current period.
+    pub fn process_org_payment(ctx: Context<ProcessOrgPayment>, seats: u32) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.config;
+        enforce_not_paused(config)?;
+        enforce_mint_cutover(config, now)?;
+
+        let organization = &ctx.accounts.organization;
+        require!(
+            seats > 0 && seats >= organization.members,
+            ErrorCode::InvalidSeatCount
+        );
+        require_keys_eq!(
+            ctx.accounts
+                .plan
+                .as_ref()
+                .map_or(Pubkey::default(), |plan| plan.key()),
+            organization.plan,
+            ErrorCode::InvalidOrgPlan
+        );
+        let (seat_price, duration) = match ctx.accounts.plan.as_ref() {
+            Some(plan) => {
+                require!(plan.active, ErrorCode::PlanInactive);
+                (plan.price, plan.duration)
+            }
+            None => (effective_price(config, now), config.subscription_duration),
+        };
+        let remaining = organization.expires_at.saturating_sub(now).max(0);
+        let top_up = (seats.saturating_sub(organization.seats) as u128)
+            .checked_mul(seat_price as u128)
+            .and_then(|value| value.checked_mul(remaining as u128))
+            .and_then(|value| value.checked_div(duration as u128))
+            .and_then(|value| u64::try_from(value).ok())
+            .ok_or(ErrorCode::MathOverflow)?;
+        let amount = (seats as u64)
+            .checked_mul(seat_price)
+            .and_then(|value| value.checked_add(top_up))
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        enforce_compliance(
+            config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.owner.key(),
+            amount,
+        )?;
+
+        let (insured, burned) = route_payment(
+            config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.owner_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.owner.to_account_info(),
+            &[],
+            amount,
+            amount,
+            None,
+        )?;
+        record_payment(
+            Some(&mut ctx.accounts.stats_shard),
+            false,
+            amount,
+            insured,
+            burned,
+            0,
+            organization.expires_at == 0,
+        )?;
+
+        let organization = &mut ctx.accounts.organization;
+        organization.seats = seats;
+        organization.expires_at = organization
+            .expires_at
+            .max(now)
+            .checked_add(duration)
+            .ok_or(ErrorCode::MathOverflow)?;
+        emit!(SeatsPurchased {
+            config: organization.config,
+            organization: organization.key(),
+            seats,
+            amount,
+            expires_at: organization.expires_at,
+        });
+        Ok(())
+    }
+
+    /// Owner only: gives `member` one of the organization's free seats
+    pub fn add_member(ctx: Context<AddMember>) -> Result<()> {
+        let organization = &mut ctx.accounts.organization;
+        require!(
+            organization.members < organization.seats,
+            ErrorCode::SeatsFull
+        );
+        organization.members += 1;
+
+        let seat = &mut ctx.accounts.seat;
+        seat.organization = organization.key();
+        seat.member = ctx.accounts.member.key();
+        seat.added_at = Clock::get()?.unix_timestamp;
+        seat.bump = ctx.bumps.seat;
+        Ok(())
+    }
+
+    /// Owner only: frees a member's seat and takes back its rent
+    pub fn remove_member(ctx: Context<RemoveMember>) -> Result<()> {
+        let organization = &mut ctx.accounts.organization;
+        organization.members = organization
+            .members
+            .checked_sub(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// The seat counterpart of `assert_subscription_active`: fails unless
+    /// `member` holds a seat in an organization that is paid up
+    pub fn assert_seat_active(ctx: Context<AssertSeatActive>) -> Result<()> {
+        require!(
+            ctx.accounts
+                .organization
+                .is_active(Clock::get()?.unix_timestamp),
+            ErrorCode::SubscriptionExpired
+        );
+        Ok(())
+    }
+
+    /// Cheap gate for partner programs to CPI at the top of their own
+    /// instructions: [`assert_active`] without the return data
+    pub fn assert_subscription_active(ctx: Context<AssertSubscriptionActive>) -> Result<()> {
+        assert_active(ctx).map(|_| ())
+    }
+
+    /// Canonical access check, also for other programs to CPI into: fails
+    /// unless `user`'s subscription is unrevoked and paid up, allowing the
+    /// instance's grace period past its expiry, and returns that expiry
+    pub fn assert_active(ctx: Context<AssertSubscriptionActive>) -> Result<i64> {
+        let subscription = &ctx.accounts.subscription;
+        require!(
+            subscription.status() != Subscription::STATUS_REVOKED,
+            ErrorCode::SubscriptionRevoked
+        );
+        require!(
+            subscription
+                .expires_at()
+                .saturating_add(ctx.accounts.config.grace_period)
+                > Clock::get()?.unix_timestamp,
+            ErrorCode::SubscriptionExpired
+        );
+        Ok(subscription.expires_at())
+    }
+
+    /// Active, Grace or Expired against the instance's grace period, in the
+    /// return data
+    pub fn subscription_state(ctx: Context<AssertSubscriptionActive>) -> Result<SubscriptionState> {
+        Ok(ctx.accounts.subscription.state(
+            Clock::get()?.unix_timestamp,
+            ctx.accounts.config.grace_period,
+        ))
+    }
+
+    /// Admin-only, e.g. after a chargeback; a revoked subscription can't be renewed
+    pub fn revoke_subscription(ctx: Context<RevokeSubscription>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "revoke_subscription");
+        ctx.accounts
+            .subscription
+            .set_status(Subscription::STATUS_REVOKED);
+        Ok(())
+    }
+
+    /// Closes the caller's expired subscription and returns its rent. Revoked
+    /// subscriptions stay open, so closing can't lift a revocation.
+    pub fn close_subscription(ctx: Context<CloseSubscription>) -> Result<()> {
+        let subscription = &ctx.accounts.subscription;
+        require!(
+            subscription.status() != Subscription::STATUS_REVOKED,
+            ErrorCode::SubscriptionRevoked
+        );
+        require!(
+            !subscription.is_active(Clock::get()?.unix_timestamp),
+            ErrorCode::SubscriptionStillActive
+        );
+        enforce_interval_elapsed(&ctx.accounts.config, subscription)?;
+
+        emit!(SubscriptionClosed {
+            config: ctx.accounts.config.key(),
+            user: ctx.accounts.user.key(),
+            closed_by: ctx.accounts.user.key(),
+            rent_returned: subscription.to_account_info().lamports(),
+            bounty: 0,
+        });
+        Ok(())
+    }
+
+    /// Permissionless: closes a subscription [`SUBSCRIPTION_CLOSE_GRACE`] past
+    /// its expiry. The cranker keeps [`SUBSCRIPTION_CLOSE_BOUNTY_BPS`] of the
+    /// rent and the rest goes back to the user.
+    pub fn reclaim_subscription(ctx: Context<ReclaimSubscription>) -> Result<()> {
+        let subscription = &ctx.accounts.subscription;
+        require!(
+            subscription.status() != Subscription::STATUS_REVOKED,
+            ErrorCode::SubscriptionRevoked
+        );
+        require!(
+            subscription
+                .expires_at()
+                .checked_add(SUBSCRIPTION_CLOSE_GRACE)
+                .unwrap()
+                <= Clock::get()?.unix_timestamp,
+            ErrorCode::SubscriptionStillActive
+        );
+        enforce_interval_elapsed(&ctx.accounts.config, subscription)?;
+
+        // The close at exit sends whatever is left to the user
+        let subscription = subscription.to_account_info();
+        let rent = subscription.lamports();
+        let bounty = rent * SUBSCRIPTION_CLOSE_BOUNTY_BPS / 10_000;
+        **subscription.try_borrow_mut_lamports()? -= bounty;
+        **ctx.accounts.cranker.try_borrow_mut_lamports()? += bounty;
+
+        emit!(SubscriptionClosed {
+            config: ctx.accounts.config.key(),
+            user: ctx.accounts.user.key(),
+            closed_by: ctx.accounts.cranker.key(),
+            rent_returned: rent - bounty,
+            bounty,
+        });
+        Ok(())
+    }
+
+    /// First step of moving the user's remaining time to the wallet `to`,
+    /// which has to accept it with `accept_transfer`
+    pub fn initiate_transfer(ctx: Context<InitiateTransfer>, to: Pubkey) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require_keys_neq!(to, ctx.accounts.user.key(), ErrorCode::InvalidTransfer);
+        require!(
+            ctx.accounts.subscription.status() != Subscription::STATUS_REVOKED,
+            ErrorCode::SubscriptionRevoked
+        );
+        require!(
+            ctx.accounts.subscription.is_active(now),
+            ErrorCode::SubscriptionExpired
+        );
+
+        let transfer = &mut ctx.accounts.subscription_transfer;
+        transfer.config = ctx.accounts.config.key();
+        transfer.from = ctx.accounts.user.key();
+        transfer.to = to;
+        transfer.initiated_at = now;
+        transfer.bump = ctx.bumps.subscription_transfer;
+
+        emit!(SubscriptionTransferInitiated {
+            config: transfer.config,
+            from: transfer.from,
+            to,
+        });
+        Ok(())
+    }
+
+    /// Drops a transfer the recipient hasn't accepted yet
+    pub fn cancel_transfer(_ctx: Context<CancelTransfer>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Second step, signed by the recipient: moves the old wallet's remaining
+    /// time onto theirs and closes the old subscription
+    pub fn accept_transfer(ctx: Context<AcceptTransfer>) -> Result<()> {
+        enforce_interval_elapsed(&ctx.accounts.config, &ctx.accounts.from_subscription)?;
+        let expires_at = move_subscription_time(
+            &ctx.accounts.from_subscription,
+            &mut ctx.accounts.to_subscription,
+        )?;
+        emit!(SubscriptionTransferred {
+            config: ctx.accounts.config.key(),
+            from: ctx.accounts.from.key(),
+            to: ctx.accounts.to.key(),
+            expires_at,
+            by_admin: false,
+        });
+        Ok(())
+    }
+
+    /// Admin only, for support cases such as a lost key: moves `from`'s
+    /// remaining time to `to` without either wallet signing
+    pub fn admin_transfer_subscription(ctx: Context<AdminTransferSubscription>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "admin_transfer_subscription");
+        require_keys_neq!(
+            ctx.accounts.from.key(),
+            ctx.accounts.to.key(),
+            ErrorCode::InvalidTransfer
+        );
+        enforce_interval_elapsed(&ctx.accounts.config, &ctx.accounts.from_subscription)?;
+        let expires_at = move_subscription_time(
+            &ctx.accounts.from_subscription,
+            &mut ctx.accounts.to_subscription,
+        )?;
+        emit!(SubscriptionTransferred {
+            config: ctx.accounts.config.key(),
+            from: ctx.accounts.from.key(),
+            to: ctx.accounts.to.key(),
+            expires_at,
+            by_admin: true,
+        });
+        Ok(())
+    }
+
+    /// Attestor-only: records that `user` passed geo/compliance checks until
+    /// `expires_at`. Re-issuing overwrites the previous attestation.
+    pub fn issue_attestation(ctx: Context<IssueAttestation>, expires_at: i64) -> Result<()> {
+        require!(
+            expires_at > Clock::get()?.unix_timestamp,
+            ErrorCode::InvalidDuration
+        );
+
+        let attestation = &mut ctx.accounts.attestation;
+        attestation.config = ctx.accounts.config.key();
+        attestation.user = ctx.accounts.user.key();
+        attestation.issuer = ctx.accounts.attestor.key();
+        attestation.expires_at = expires_at;
+        attestation.bump = ctx.bumps.attestation;
+        Ok(())
+    }
+
+    /// Attestor-only: withdraws `user`'s attestation and reclaims its rent
+    pub fn revoke_attestation(_ctx: Context<RevokeAttestation>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Gate for partner perks: fails unless `user`'s lifetime burn reaches
+    /// `min_tier` under the instance's current thresholds
+    pub fn assert_loyalty_tier(
+        ctx: Context<AssertLoyaltyTier>,
+        min_tier: LoyaltyTier,
+    ) -> Result<()> {
+        let tier = loyalty_tier(
+            &ctx.accounts.config,
+            ctx.accounts.user_stats.lifetime_burned,
+        );
+        require!(tier >= min_tier, ErrorCode::LoyaltyTierTooLow);
+        Ok(())
+    }
+
+    pub fn create_badge(
+        ctx: Context<CreateBadge>,
+        badge_id: u8,
+        threshold: u64,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "create_badge");
+        require!(threshold > 0, ErrorCode::InvalidAmount);
+
+        let badge = &mut ctx.accounts.badge;
+        badge.config = ctx.accounts.config.key();
+        badge.badge_id = badge_id;
+        badge.threshold = threshold;
+        badge.claimed = 0;
+        badge.name = name;
+        badge.symbol = symbol;
+        badge.uri = uri;
+        badge.bump = ctx.bumps.badge;
+        Ok(())
+    }
+
+    /// Mints a one-of-one badge to a payer whose lifetime burn has crossed the
+    /// badge threshold; the badge mint PDA can only be created once per user
+    pub fn claim_badge(ctx: Context<ClaimBadge>) -> Result<()> {
+        let badge = &ctx.accounts.badge;
+        require!(
+            ctx.accounts.user_stats.lifetime_burned >= badge.threshold,
+            ErrorCode::BadgeThresholdNotMet
+        );
+
+        let config_key = ctx.accounts.config.key();
+        init_mint_metadata(
+            &config_key,
+            &ctx.accounts.user,
+            &ctx.accounts.badge_mint.to_account_info(),
+            &ctx.accounts.mint_authority,
+            ctx.bumps.mint_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            badge.name.clone(),
+            badge.symbol.clone(),
+            badge.uri.clone(),
+        )?;
+
+        let authority_seeds: &[&[&[u8]]] = &[&[
+            b"mint_authority",
+            config_key.as_ref(),
+            &[ctx.bumps.mint_authority],
+        ]];
+        let mint_cpi_accounts = token_interface::MintToChecked {
+            mint: ctx.accounts.badge_mint.to_account_info(),
+            to: ctx.accounts.user_badge_account.to_account_info(),
+            authority: ctx.accounts.mint_authority.to_account_info(),
+        };
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            mint_cpi_accounts,
+            authority_seeds,
+        );
+        token_interface::mint_to_checked(mint_ctx, 1, 0)?;
+
+        // Dropping the mint authority caps the supply at the one badge
+        let authority_cpi_accounts = token_2022::SetAuthority {
+            current_authority: ctx.accounts.mint_authority.to_account_info(),
+            account_or_mint: ctx.accounts.badge_mint.to_account_info(),
+        };
+        let authority_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            authority_cpi_accounts,
+            authority_seeds,
+        );
+        token_2022::set_authority(
+            authority_ctx,
+            spl_token_2022::instruction::AuthorityType::MintTokens,
+            None,
+        )?;
+
+        let badge = &mut ctx.accounts.badge;
+        badge.claimed = badge.claimed.checked_add(1).unwrap();
+        Ok(())
+    }
+
+    pub fn register_service(
+        ctx: Context<RegisterService>,
+        service_id: u64,
+        price: u64,
+        duration: i64,
+    ) -> Result<()> {
+        require!(duration > 0, ErrorCode::InvalidDuration);
+
+        // Any dApp may register a service; it pays out through this instance's split
+        let service = &mut ctx.accounts.service;
+        service.config = ctx.accounts.config.key();
+        service.authority = ctx.accounts.authority.key();
+        service.service_id = service_id;
+        service.price = price;
+        service.duration = duration;
+        service.bump = ctx.bumps.service;
+
+        msg!(
+            "Service #{} registered on instance #{}",
+            service_id,
+            ctx.accounts.config.id
+        );
+        Ok(())
+    }
+
+    pub fn process_service_payment(ctx: Context<ProcessServicePayment>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.user.key(),
+            amount,
+        )?;
+
+        // Same split and burn as a router payment, priced by the service
+        route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.user.to_account_info(),
+            &[],
+            amount,
+            ctx.accounts.service.price,
+            None,
+        )?;
+
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            ctx.accounts.service.duration,
+            ctx.accounts.config.grace_period,
+        )?;
+
+        Ok(())
+    }
+
+    /// Pays for catalog product `product_id` under the product's own split.
+    /// Subscriptions extend the router subscription, add-ons (which need an
+    /// active one) extend their own entitlement, one-time purchases only pay.
+    pub fn purchase_product(
+        ctx: Context<PurchaseProduct>,
+        product_id: u64,
+        amount: u64,
+    ) -> Result<()> {
+        let product = &ctx.accounts.product;
+        require!(product.active, ErrorCode::ProductInactive);
+        let now = Clock::get()?.unix_timestamp;
+        let grace_period = ctx.accounts.config.grace_period;
+        enforce_not_paused(&ctx.accounts.config)?;
+        enforce_mint_cutover(&ctx.accounts.config, now)?;
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.user.key(),
+            amount,
+        )?;
+
+        match product.kind {
+            ProductKind::Subscription => {
+                let subscription = ctx
+                    .accounts
+                    .subscription
+                    .as_mut()
+                    .ok_or(ErrorCode::MissingProductAccount)?;
+                renew_prepaid(
+                    &ctx.accounts.config,
+                    subscription,
+                    product.duration,
+                    grace_period,
+                )?;
+            }
+            ProductKind::AddOn => {
+                let subscription = ctx
+                    .accounts
+                    .subscription
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingProductAccount)?;
+                require!(subscription.is_active(now), ErrorCode::SubscriptionExpired);
+                let entitlement = ctx
+                    .accounts
+                    .entitlement
+                    .as_mut()
+                    .ok_or(ErrorCode::MissingProductAccount)?;
+                renew_prepaid(
+                    &ctx.accounts.config,
+                    entitlement,
+                    product.duration,
+                    grace_period,
+                )?;
+            }
+            ProductKind::OneTime => {}
+        }
+
+        route_payment_split(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.user.to_account_info(),
+            &[],
+            amount,
+            product.price,
+            (product.insurance_bps, product.burn_bps),
+            None,
+            None,
+        )?;
+
+        msg!(
+            "Product #{} purchased by {}",
+            product_id,
+            ctx.accounts.user.key()
+        );
+        Ok(())
+    }
+
+    /// Service-authority only: bills `payer` `amount` by `due_at`. With a
+    /// non-zero `interval` the invoice recurs, falling due again every `interval`.
+    pub fn create_invoice(
+        ctx: Context<CreateInvoice>,
+        invoice_id: u64,
+        payer: Pubkey,
+        amount: u64,
+        due_at: i64,
+        interval: i64,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        require!(interval >= 0, ErrorCode::InvalidDuration);
+
+        let invoice = &mut ctx.accounts.invoice;
+        invoice.service = ctx.accounts.service.key();
+        invoice.payer = payer;
+        invoice.invoice_id = invoice_id;
+        invoice.amount = amount;
+        invoice.due_at = due_at;
+        invoice.interval = interval;
+        invoice.payments = 0;
+        invoice.paid = false;
+        invoice.bump = ctx.bumps.invoice;
+        Ok(())
+    }
+
+    /// Pays the invoice's current period through the instance split. A recurring
+    /// invoice can be paid at most one period ahead of its due date.
+    pub fn pay_invoice(ctx: Context<PayInvoice>) -> Result<()> {
+        let invoice = &ctx.accounts.invoice;
+        require!(!invoice.paid, ErrorCode::InvoiceAlreadyPaid);
+        let now = Clock::get()?.unix_timestamp;
+        if invoice.interval > 0 {
+            require!(
+                invoice.due_at <= now.saturating_add(invoice.interval),
+                ErrorCode::InvoiceNotDue
+            );
+        }
+        let amount = invoice.amount;
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.payer.key(),
+            amount,
+        )?;
+
+        route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.payer_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.payer.to_account_info(),
+            &[],
+            amount,
+            amount,
+            None,
+        )?;
+
+        let invoice = &mut ctx.accounts.invoice;
+        invoice.payments = invoice.payments.checked_add(1).unwrap();
+        if invoice.interval > 0 {
+            invoice.due_at = invoice.due_at.checked_add(invoice.interval).unwrap();
+        } else {
+            invoice.paid = true;
+        }
+        Ok(())
+    }
+
+    /// Service-authority only: withdraws an invoice and reclaims its rent
+    pub fn cancel_invoice(_ctx: Context<CancelInvoice>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn create_gift_cards(
+        ctx: Context<CreateGiftCards>,
+        price: u64,
+        duration: i64,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "create_gift_cards");
+        require!(price > 0, ErrorCode::InvalidAmount);
+        require!(duration > 0, ErrorCode::InvalidDuration);
+
+        let gift_card = &mut ctx.accounts.gift_card;
+        gift_card.config = ctx.accounts.config.key();
+        gift_card.mint = ctx.accounts.gift_card_mint.key();
+        gift_card.price = price;
+        gift_card.duration = duration;
+        gift_card.bump = ctx.bumps.gift_card;
+        Ok(())
+    }
+
+    pub fn purchase_gift_cards(ctx: Context<PurchaseGiftCards>, quantity: u64) -> Result<()> {
+        require!(quantity > 0, ErrorCode::InvalidAmount);
+        let amount = ctx
+            .accounts
+            .gift_card
+            .price
+            .checked_mul(quantity)
+            .ok_or(ErrorCode::InvalidAmount)?;
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.buyer.key(),
+            amount,
+        )?;
+
+        // 1. The purchase is split and burned up front, like any payment
+        route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.buyer_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.buyer.to_account_info(),
+            &[],
+            amount,
+            amount,
+            None,
+        )?;
+
+        // 2. Cards are plain transferable tokens until someone redeems them
+        let config_key = ctx.accounts.config.key();
+        let authority_seeds: &[&[&[u8]]] = &[&[
+            b"mint_authority",
+            config_key.as_ref(),
+            &[ctx.bumps.mint_authority],
+        ]];
+        let mint_cpi_accounts = token_interface::MintToChecked {
+            mint: ctx.accounts.gift_card_mint.to_account_info(),
+            to: ctx.accounts.buyer_card_account.to_account_info(),
+            authority: ctx.accounts.mint_authority.to_account_info(),
+        };
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            mint_cpi_accounts,
+            authority_seeds,
+        );
+        token_interface::mint_to_checked(mint_ctx, quantity, ctx.accounts.gift_card_mint.decimals)
+    }
+
+    pub fn redeem_gift_cards(ctx: Context<RedeemGiftCards>, quantity: u64) -> Result<()> {
+        require!(quantity > 0, ErrorCode::InvalidAmount);
+
+        let burn_cpi_accounts = token_interface::BurnChecked {
+            mint: ctx.accounts.gift_card_mint.to_account_info(),
+            from: ctx.accounts.holder_card_account.to_account_info(),
+            authority: ctx.accounts.holder.to_account_info(),
+        };
+        let burn_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            burn_cpi_accounts,
+        );
+        token_interface::burn_checked(burn_ctx, quantity, ctx.accounts.gift_card_mint.decimals)?;
+
+        // Each card is worth one gift-card duration
+        let duration = ctx
+            .accounts
+            .gift_card
+            .duration
+            .checked_mul(i64::try_from(quantity).map_err(|_| ErrorCode::InvalidAmount)?)
+            .ok_or(ErrorCode::InvalidAmount)?;
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            duration,
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    /// Prepays `quantity` unassigned periods into an escrowed pass batch, for
+    /// conferences and giveaways; `amount` must cover the effective price of each
+    pub fn purchase_passes(
+        ctx: Context<PurchasePasses>,
+        batch_id: u64,
+        quantity: u32,
+        amount: u64,
+    ) -> Result<()> {
+        require!(quantity > 0, ErrorCode::InvalidAmount);
+        let price = effective_price(&ctx.accounts.config, Clock::get()?.unix_timestamp)
+            .checked_mul(quantity as u64)
+            .ok_or(ErrorCode::InvalidAmount)?;
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.buyer.key(),
+            amount,
+        )?;
+
+        // The whole batch is split and burned up front, like any payment
+        route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.buyer_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            Some(ctx.accounts.mint.to_account_info()),
+            ctx.accounts.buyer.to_account_info(),
+            &[],
+            amount,
+            price,
+            None,
+        )?;
+
+        // Slots keep the period length they were bought at
+        let pass_batch = &mut ctx.accounts.pass_batch;
+        pass_batch.config = ctx.accounts.config.key();
+        pass_batch.buyer = ctx.accounts.buyer.key();
+        pass_batch.batch_id = batch_id;
+        pass_batch.quantity = quantity;
+        pass_batch.remaining = quantity;
+        pass_batch.duration = ctx.accounts.config.subscription_duration;
+        pass_batch.bump = ctx.bumps.pass_batch;
+        Ok(())
+    }
+
+    /// Binds one slot of the buyer's batch to `recipient`'s subscription
+    pub fn assign_pass(ctx: Context<AssignPass>) -> Result<()> {
+        let pass_batch = &mut ctx.accounts.pass_batch;
+        require!(pass_batch.remaining > 0, ErrorCode::NoPassesRemaining);
+        pass_batch.remaining -= 1;
+
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            pass_batch.duration,
+            0,
+        )?;
+        Ok(())
+    }
+
+    /// Registers the signer as a referrer paid into `token_account`, an
+    /// instance-mint account they own. Payers name the referrer per payment.
+    pub fn register_referrer(ctx: Context<RegisterReferrer>) -> Result<()> {
+        let referrer = &mut ctx.accounts.referrer;
+        referrer.config = ctx.accounts.config.key();
+        referrer.authority = ctx.accounts.authority.key();
+        referrer.token_account = ctx.accounts.token_account.key();
+        referrer.referred_payments = 0;
+        referrer.total_earned = 0;
+        referrer.bump = ctx.bumps.referrer;
+        Ok(())
+    }
+
+    /// Lists the seller's remaining whole days for `price`. The time moves into
+    /// the listing, so the seller's subscription lapses at the end of today.
+    pub fn list_subscription(ctx: Context<ListSubscription>, price: u64) -> Result<()> {
+        require!(price > 0, ErrorCode::InvalidAmount);
+        let subscription = &mut ctx.accounts.subscription;
+        require!(
+            subscription.status() != Subscription::STATUS_REVOKED,
+            ErrorCode::SubscriptionRevoked
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let expires_at = subscription.expires_at();
+        subscription.set_expires_at(now)?;
+        let remaining = expires_at.saturating_sub(subscription.expires_at());
+        require!(remaining > 0, ErrorCode::SubscriptionExpired);
+
+        let listing = &mut ctx.accounts.listing;
+        listing.config = ctx.accounts.config.key();
+        listing.seller = ctx.accounts.seller.key();
+        listing.price = price;
+        listing.remaining = remaining;
+        listing.listed_at = now;
+        listing.bump = ctx.bumps.listing;
+        Ok(())
+    }
+
+    /// Returns the escrowed time to the seller and closes the listing
+    pub fn cancel_listing(ctx: Context<CancelListing>) -> Result<()> {
+        extend_subscription(
+            &mut ctx.accounts.subscription,
+            ctx.accounts.listing.remaining,
+        )?;
+        Ok(())
+    }
+
+    /// Buys a listing: the royalty share of `price` goes through the instance
+    /// split, the rest to the seller, and the escrowed time onto the buyer's
+    /// subscription
+    pub fn buy_listing(ctx: Context<BuyListing>) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        require_keys_neq!(
+            ctx.accounts.buyer.key(),
+            listing.seller,
+            ErrorCode::Unauthorized
+        );
+        let price = listing.price;
+        let config = &ctx.accounts.config;
+        enforce_not_paused(config)?;
+        enforce_mint_cutover(config, Clock::get()?.unix_timestamp)?;
+        enforce_compliance(
+            config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.buyer.key(),
+            price,
+        )?;
+
+        let royalty = (price as u128)
+            .checked_mul(config.resale_royalty_bps as u128)
+            .unwrap()
+            .checked_div(10_000)
+            .unwrap() as u64;
+        if royalty > 0 {
+            route_payment(
+                config,
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.buyer_token_account.to_account_info(),
+                ctx.accounts.insurance_vault.to_account_info(),
+                Some(ctx.accounts.mint.to_account_info()),
+                ctx.accounts.buyer.to_account_info(),
+                &[],
+                royalty,
+                0,
+                None,
+            )?;
+        }
+
+        let proceeds = price.checked_sub(royalty).unwrap();
+        if proceeds > 0 {
+            let transfer_cpi_accounts = token_interface::TransferChecked {
+                from: ctx.accounts.buyer_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.seller_token_account.to_account_info(),
+                authority: ctx.accounts.buyer.to_account_info(),
+            };
+            let transfer_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                transfer_cpi_accounts,
+            );
+            token_interface::transfer_checked(transfer_ctx, proceeds, ctx.accounts.mint.decimals)?;
+        }
+
+        extend_subscription(
+            &mut ctx.accounts.subscription,
+            ctx.accounts.listing.remaining,
+        )?;
+        Ok(())
+    }
+
+    pub fn init_stats(ctx: Context<InitStats>) -> Result<()> {
+        let stats = &mut ctx.accounts.stats;
+        stats.config = ctx.accounts.config.key();
+        stats.bump = ctx.bumps.stats;
+        Ok(())
+    }
+
+    pub fn init_stats_shard(ctx: Context<InitStatsShard>, index: u8) -> Result<()> {
+        require!(index < STATS_SHARD_COUNT, ErrorCode::InvalidStatsShard);
+
+        let shard = &mut ctx.accounts.stats_shard;
+        shard.config = ctx.accounts.config.key();
+        shard.index = index;
+        shard.bump = ctx.bumps.stats_shard;
+        Ok(())
+    }
+
+    /// Permissionless: rebuilds the aggregate from every shard, passed in index
+    /// order as remaining accounts. Recomputing instead of adding deltas keeps
+    /// the crank idempotent.
+    pub fn aggregate_stats<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AggregateStats<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.remaining_accounts.len() == STATS_SHARD_COUNT as usize,
+            ErrorCode::InvalidStatsShard
+        );
+
+        let config_key = ctx.accounts.config.key();
+        let (mut payments, mut volume, mut insured, mut burned) = (0u64, 0u64, 0u64, 0u64);
+        let (mut subscribers, mut staked) = (0u64, 0u64);
+        for (index, account) in ctx.remaining_accounts.iter().enumerate() {
+            let shard = Account::<StatsShard>::try_from(account)?;
+            let expected = Pubkey::create_program_address(
+                &[
+                    b"stats_shard",
+                    config_key.as_ref(),
+                    &[index as u8],
+                    &[shard.bump],
+                ],
+                &crate::ID,
+            )
+            .map_err(|_| ErrorCode::InvalidStatsShard)?;
+            require_keys_eq!(account.key(), expected, ErrorCode::InvalidStatsShard);
+
+            payments = payments.checked_add(shard.payments).unwrap();
+            volume = volume.checked_add(shard.volume).unwrap();
+            insured = insured.checked_add(shard.insured).unwrap();
+            burned = burned.checked_add(shard.burned).unwrap();
+            subscribers = subscribers.checked_add(shard.subscribers).unwrap();
+            staked = staked.checked_add(shard.staked).unwrap();
+        }
+
+        let stats = &mut ctx.accounts.stats;
+        stats.payments = payments;
+        stats.volume = volume;
+        stats.insured = insured;
+        stats.burned = burned;
+        stats.unique_subscribers = subscribers;
+        stats.staked = staked;
+        stats.updated_at = Clock::get()?.unix_timestamp;
+
+        let config = &mut ctx.accounts.config;
+        config.curve_discount_bps = curve_discount_bps(config, burned);
+        Ok(())
+    }
+
+    pub fn init_registry_page(ctx: Context<InitRegistryPage>, kind: u8, page: u8) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "init_registry_page");
+        let mut registry_page = ctx.accounts.registry_page.load_init()?;
+        registry_page.config = ctx.accounts.config.key();
+        registry_page.kind = kind;
+        registry_page.page = page;
+        registry_page.bump = ctx.bumps.registry_page;
+        Ok(())
+    }
+
+    /// Inserts or overwrites up to `MAX_REGISTRY_CHUNK` entries on one page
+    pub fn upsert_registry_entries(
+        ctx: Context<UpdateRegistryPage>,
+        entries: Vec<RegistryEntryArgs>,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "upsert_registry_entries");
+        require!(
+            entries.len() <= MAX_REGISTRY_CHUNK,
+            ErrorCode::RegistryChunkTooLarge
+        );
+        let mut registry_page = ctx.accounts.registry_page.load_mut()?;
+        for entry in entries {
+            registry_page.upsert(entry)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_registry_entries(
+        ctx: Context<UpdateRegistryPage>,
+        keys: Vec<Pubkey>,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "remove_registry_entries");
+        require!(
+            keys.len() <= MAX_REGISTRY_CHUNK,
+            ErrorCode::RegistryChunkTooLarge
+        );
+        let mut registry_page = ctx.accounts.registry_page.load_mut()?;
+        for key in keys {
+            registry_page.remove(&key)?;
+        }
+        Ok(())
+    }
+
+    pub fn init_receipt_history(ctx: Context<InitReceiptHistory>) -> Result<()> {
+        let history = &mut ctx.accounts.receipt_history;
+        history.config = ctx.accounts.config.key();
+        history.root = history.compute_root();
+        history.bump = ctx.bumps.receipt_history;
+        Ok(())
+    }
+
+    pub fn init_outbox(ctx: Context<InitOutbox>) -> Result<()> {
+        let mut outbox = ctx.accounts.outbox.load_init()?;
+        outbox.config = ctx.accounts.config.key();
+        outbox.bump = ctx.bumps.outbox;
+        Ok(())
+    }
+
+    /// Permissionless: queues an expiring-soon notice for `user` once their
+    /// subscription is inside the notice window, at most once per expiry
+    pub fn enqueue_expiry_notice(ctx: Context<EnqueueExpiryNotice>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let expires_at = ctx.accounts.subscription.expires_at();
+        require!(
+            ctx.accounts.subscription.is_active(now)
+                && expires_at <= now.checked_add(OUTBOX_EXPIRY_NOTICE).unwrap(),
+            ErrorCode::NotExpiringSoon
+        );
+
+        let user = ctx.accounts.user.key();
+        let mut outbox = ctx.accounts.outbox.load_mut()?;
+        require!(
+            !outbox.contains(OutboxEntry::EXPIRING_SOON, &user, expires_at as u64),
+            ErrorCode::AlreadyQueued
+        );
+        outbox.push(OutboxEntry::EXPIRING_SOON, user, expires_at as u64, now);
+        Ok(())
+    }
+
+    /// Operator only: marks every entry up to and including `through_seq` as processed
+    pub fn ack_outbox(ctx: Context<AckOutbox>, through_seq: u64) -> Result<()> {
+        let mut outbox = ctx.accounts.outbox.load_mut()?;
+        require!(
+            through_seq >= outbox.acked && through_seq < outbox.head,
+            ErrorCode::InvalidOutboxAck
+        );
+        outbox.acked = through_seq.checked_add(1).unwrap();
+        Ok(())
+    }
+
+    /// Permissionless: appends each receipt (remaining accounts) past its
+    /// retention period to the instance's receipt history tree as
+    /// `hash(address || data)`, then closes it into the treasury authority
+    pub fn archive_receipts<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ArchiveReceipts<'info>>,
+    ) -> Result<()> {
+        require!(
+            !ctx.remaining_accounts.is_empty() && ctx.remaining_accounts.len() <= MAX_CRANK_BATCH,
+            ErrorCode::InvalidBatch
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let config_key = ctx.accounts.config.key();
+        let treasury = ctx.accounts.treasury_authority.to_account_info();
+        let history = &mut ctx.accounts.receipt_history;
+        for account in ctx.remaining_accounts {
+            let receipt = Account::<Receipt>::try_from(account)?;
+            require_keys_eq!(receipt.config, config_key, ErrorCode::InvalidReceipt);
+            require!(
+                receipt.paid_at.checked_add(RECEIPT_RETENTION).unwrap() <= now,
+                ErrorCode::ReceiptNotArchivable
+            );
+
+            let leaf = hashv(&[account.key().as_ref(), &account.try_borrow_data()?]).to_bytes();
+            let index = history.append(leaf)?;
+            msg!("Archived receipt {} as leaf {}", account.key(), index);
+            receipt.close(treasury.clone())?;
+        }
+        history.root = history.compute_root();
+        Ok(())
+    }
+
+    /// Moves rent reclaimed from archived receipts to the admin
+    pub fn sweep_reclaimed_rent(ctx: Context<SweepReclaimedRent>) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "sweep_reclaimed_rent");
+        let amount = ctx.accounts.treasury_authority.lamports();
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let config_key = ctx.accounts.config.key();
+        let treasury_seeds: &[&[&[u8]]] = &[&[
+            VaultKind::Treasury.seed(),
+            config_key.as_ref(),
+            &[ctx.bumps.treasury_authority],
+        ]];
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.treasury_authority.to_account_info(),
+                to: ctx.accounts.admin.to_account_info(),
+            },
+            treasury_seeds,
+        );
+        anchor_lang::system_program::transfer(transfer_ctx, amount)
+    }
+
+    /// Permissionless: brings a subscription up to the current layout at the
+    /// same address. A legacy account is rewritten packed and its freed rent
+    /// refunded to the owner; a packed account without the reserved tail is
+    /// grown to it, with the extra rent from `payer`. Expiry is preserved.
+    pub fn migrate_subscription(ctx: Context<MigrateSubscription>) -> Result<()> {
+        let subscription = ctx.accounts.subscription.to_account_info();
+        let new_len = Subscription::SPACE;
+        let is_legacy = subscription
+            .try_borrow_data()?
+            .starts_with(&LEGACY_SUBSCRIPTION_DISCRIMINATOR);
+        if !is_legacy {
+            require!(
+                subscription.data_len() < new_len,
+                ErrorCode::SubscriptionAlreadyMigrated
+            );
+            // Resizing zero-fills the tail, so the packed fields stay as they
+            // are and the payment count starts at zero
+            require!(
+                subscription
+                    .try_borrow_data()?
+                    .starts_with(&SUBSCRIPTION_DISCRIMINATOR),
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+            let rent_due = Rent::get()?
+                .minimum_balance(new_len)
+                .saturating_sub(subscription.lamports());
+            if rent_due > 0 {
+                let payer = ctx
+                    .accounts
+                    .payer
+                    .as_ref()
+                    .ok_or(ErrorCode::MigrationPayerRequired)?;
+                let fund_cpi_accounts = anchor_lang::system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: subscription.clone(),
+                };
+                let system_program = ctx
+                    .accounts
+                    .system_program
+                    .as_ref()
+                    .ok_or(ErrorCode::MigrationPayerRequired)?;
+                let fund_ctx = CpiContext::new(system_program.to_account_info(), fund_cpi_accounts);
+                anchor_lang::system_program::transfer(fund_ctx, rent_due)?;
+            }
+            subscription.resize(new_len)?;
+            return Ok(());
+        }
+
+        let legacy = {
+            let data = subscription.try_borrow_data()?;
+            LegacySubscription::try_deserialize(&mut &data[..])?
+        };
+        require_keys_eq!(
+            legacy.user,
+            ctx.accounts.user.key(),
+            ErrorCode::InvalidOwner
+        );
+
+        let mut migrated = Subscription {
+            version: Subscription::VERSION,
+            packed: 0,
+            payment_count: 0,
+            last_payment_slot: 0,
+        };
+        migrated.set_expires_at(legacy.expires_at)?;
+
+        subscription.resize(new_len)?;
+        migrated.try_serialize(&mut &mut subscription.try_borrow_mut_data()?[..])?;
+
+        let excess = subscription
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(new_len));
+        **subscription.try_borrow_mut_lamports()? -= excess;
+        **ctx.accounts.user.try_borrow_mut_lamports()? += excess;
+        Ok(())
+    }
+
+    /// Admin only: grows a config created before the reserved tail to
+    /// [`Config::SPACE`], the admin paying the extra rent. The tail is
+    /// zero-filled, so fields added into it read as off. Such a config no
+    /// longer deserializes, so only its fixed-offset id and admin are read.
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let config = ctx.accounts.config.to_account_info();
+        let (id, admin) = {
+            let data = config.try_borrow_data()?;
+            require!(
+                data.starts_with(Config::DISCRIMINATOR),
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+            let header = data
+                .get(8..48)
+                .ok_or(anchor_lang::error::ErrorCode::AccountDidNotDeserialize)?;
+            (
+                u64::from_le_bytes(header[..8].try_into().unwrap()),
+                Pubkey::new_from_array(header[8..].try_into().unwrap()),
+            )
+        };
+        let (expected, _) =
+            Pubkey::find_program_address(&[b"config", id.to_le_bytes().as_ref()], &crate::ID);
+        require_keys_eq!(
+            config.key(),
+            expected,
+            anchor_lang::error::ErrorCode::ConstraintSeeds
+        );
+        require_keys_eq!(admin, ctx.accounts.admin.key(), ErrorCode::Unauthorized);
+        emit!(AdminAction {
+            config: config.key(),
+            admin,
+            action: "migrate_config".to_string(),
+        });
+
+        let new_len = Config::SPACE;
+        require!(
+            config.data_len() < new_len,
+            ErrorCode::ConfigAlreadyMigrated
+        );
+
+        let rent_due = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(config.lamports());
+        if rent_due > 0 {
+            let fund_cpi_accounts = anchor_lang::system_program::Transfer {
+                from: ctx.accounts.admin.to_account_info(),
+                to: config.clone(),
+            };
+            let fund_ctx = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                fund_cpi_accounts,
+            );
+            anchor_lang::system_program::transfer(fund_ctx, rent_due)?;
+        }
+        config.resize(new_len)?;
+        Ok(())
+    }
+
+    pub fn process_lst_payment(ctx: Context<ProcessLstPayment>, amount: u64) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(config.lst_usd_price > 0, ErrorCode::LstPaymentsDisabled);
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        enforce_not_paused(config)?;
+        let price_lamports = usd_to_tokens(
+            config,
+            &config.sol_usd_feed_id,
+            &ctx.accounts.sol_price_update,
+            SOL_DECIMALS,
+            config.lst_usd_price,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.user.key(),
+            amount,
+        )?;
+
+        // 1. Value the LST in SOL at its current exchange rate, against the
+        //    period's USD price at the current SOL price
+        let sol_value = lst_value_in_lamports(
+            &ctx.accounts.lst_mint.key(),
+            &ctx.accounts.rate_account,
+            amount,
+        )?;
+        require!(sol_value >= price_lamports, ErrorCode::Underpayment);
+
+        // 2. LSTs can't be burned as SAKURA, so they accrue in the treasury
+        //    for off-chain conversion into the usual insurance/burn split
+        let transfer_cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.user_lst_account.to_account_info(),
+            mint: ctx.accounts.lst_mint.to_account_info(),
+            to: ctx.accounts.treasury_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_cpi_accounts,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.lst_mint.decimals)?;
+
+        // 3. Same subscription extension as a SAKURA payment
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            ctx.accounts.config.subscription_duration,
+            ctx.accounts.config.grace_period,
+        )?;
+
+        Ok(())
+    }
+
+    /// Pays for a period in wSOL. The insurance share goes to the instance's
+    /// SOL insurance vault; wSOL can't be burned as SAKURA, so the burn share
+    /// waits in the buyback escrow for `execute_buyback_burn`.
+    pub fn process_payment_sol(
+        ctx: Context<ProcessPaymentSol>,
+        amount: u64,
+        light_mode: bool,
+    ) -> Result<()> {
+        let price_lamports = ctx.accounts.config.sol_price_lamports;
+        require!(price_lamports > 0, ErrorCode::SolPaymentsDisabled);
+        let now = Clock::get()?.unix_timestamp;
+        enforce_not_paused(&ctx.accounts.config)?;
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.user.key(),
+            amount,
+        )?;
+
+        let (insured, escrowed, _) = route_payment_split(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.user_sol_account.to_account_info(),
+            ctx.accounts.sol_insurance_vault.to_account_info(),
+            Some(ctx.accounts.native_mint.to_account_info()),
+            ctx.accounts.user.to_account_info(),
+            &[],
+            amount,
+            price_lamports,
+            effective_split(&ctx.accounts.config, now),
+            Some(ctx.accounts.buyback_vault.to_account_info()),
+            None,
+        )?;
+        // Shard totals are in the instance mint, so the wSOL amounts stay out;
+        // the burn is credited once `execute_buyback_burn` swaps the escrow
+        record_payment(
+            ctx.accounts.stats_shard.as_mut(),
+            light_mode,
+            0,
+            0,
+            0,
+            0,
+            ctx.accounts.subscription.expires_at() == 0,
+        )?;
+
+        let previous_expires_at = ctx.accounts.subscription.expires_at();
+        let previous_state = ctx
+            .accounts
+            .subscription
+            .state(now, ctx.accounts.config.grace_period);
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            ctx.accounts.config.subscription_duration,
+            ctx.accounts.config.grace_period,
+        )?;
+        let expires_at = ctx.accounts.subscription.expires_at();
+
+        let config_key = ctx.accounts.config.key();
+        let user = ctx.accounts.user.key();
+        emit!(SolPaymentProcessed {
+            config: config_key,
+            user,
+            amount,
+            insurance_amount: insured,
+            buyback_amount: escrowed,
+            new_expires_at: expires_at,
+        });
+        emit!(SubscriptionExtended {
+            config: config_key,
+            user,
+            previous_state,
+            previous_expires_at,
+            new_expires_at: expires_at,
+        });
+        Ok(())
+    }
+
+    /// Admin only: swaps up to `amount_in` of the buyback escrow's wSOL for
+    /// SAKURA through `swap_program` and burns what comes back, failing unless
+    /// at least `min_burn` arrives. `swap_data` and the remaining accounts are
+    /// forwarded verbatim, with the buyback PDA signing as the swap's owner.
+    /// The burn is credited to `stats_shard` so `aggregate_stats` counts it.
+    pub fn execute_buyback_burn<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteBuybackBurn<'info>>,
+        amount_in: u64,
+        min_burn: u64,
+        swap_data: Vec<u8>,
+    ) -> Result<()> {
+        emit_admin_action(&ctx.accounts.config, "execute_buyback_burn");
+        require!(amount_in > 0 && min_burn > 0, ErrorCode::InvalidAmount);
+        require!(
+            amount_in <= ctx.accounts.buyback_sol_vault.amount,
+            ErrorCode::InvalidAmount
+        );
+
+        let config_key = ctx.accounts.config.key();
+        let buyback_authority = ctx.accounts.buyback_authority.key();
+        let buyback_seeds: &[&[&[u8]]] = &[&[
+            VaultKind::Buyback.seed(),
+            config_key.as_ref(),
+            &[ctx.bumps.buyback_authority],
+        ]];
+
+        // 1. Swap through the admin-chosen route
+        let sol_before = ctx.accounts.buyback_sol_vault.amount;
+        let sakura_before = ctx.accounts.buyback_vault.amount;
+        let swap_ix = Instruction {
+            program_id: ctx.accounts.swap_program.key(),
+            accounts: ctx
+                .remaining_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: account.key(),
+                    is_signer: account.is_signer || account.key() == buyback_authority,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: swap_data,
+        };
+        invoke_signed(&swap_ix, ctx.remaining_accounts, buyback_seeds)?;
+        ctx.accounts.buyback_sol_vault.reload()?;
+        ctx.accounts.buyback_vault.reload()?;
+
+        // 2. Hold the swap to its bounds before burning the proceeds
+        let spent = sol_before
+            .checked_sub(ctx.accounts.buyback_sol_vault.amount)
+            .ok_or(ErrorCode::BuybackSlippage)?;
+        let bought = ctx
+            .accounts
+            .buyback_vault
+            .amount
+            .checked_sub(sakura_before)
+            .ok_or(ErrorCode::BuybackSlippage)?;
+        require!(
+            spent <= amount_in && bought >= min_burn,
+            ErrorCode::BuybackSlippage
+        );
+
+        let burn_cpi_accounts = token_interface::BurnChecked {
+            mint: ctx.accounts.mint.to_account_info(),
+            from: ctx.accounts.buyback_vault.to_account_info(),
+            authority: ctx.accounts.buyback_authority.to_account_info(),
+        };
+        let burn_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            burn_cpi_accounts,
+            buyback_seeds,
+        );
+        token_interface::burn_checked(burn_ctx, bought, ctx.accounts.mint.decimals)?;
+
+        let shard = &mut ctx.accounts.stats_shard;
+        shard.burned = shard.burned.checked_add(bought).unwrap();
+        emit!(BuybackBurned {
+            config: config_key,
+            sol_spent: spent,
+            burned: bought,
+        });
+        Ok(())
+    }
+
+    /// Payment path for owners who can't sign token CPIs, such as Token-2022
+    /// accounts with CPI guard: the user first approves the payment delegate PDA
+    /// for `amount` in a top-level instruction, then the router moves it as delegate.
+    pub fn process_delegated_payment(
+        ctx: Context<ProcessDelegatedPayment>,
+        amount: u64,
+        light_mode: bool,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.user.key(),
+            amount,
+        )?;
+        enforce_jurisdiction_attestation(
+            &ctx.accounts.config,
+            ctx.accounts.attestation.as_ref(),
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        let config_key = ctx.accounts.config.key();
+        let delegate_seeds: &[&[&[u8]]] =
+            &[&[b"pay_delegate", config_key.as_ref(), &[ctx.bumps.delegate]]];
+        let (insured, burned) = route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            ctx.accounts
+                .mint
+                .as_ref()
+                .map(|mint| mint.to_account_info()),
+            ctx.accounts.delegate.to_account_info(),
+            delegate_seeds,
+            amount,
+            effective_price(&ctx.accounts.config, Clock::get()?.unix_timestamp),
+            None,
+        )?;
+        record_payment(
+            ctx.accounts.stats_shard.as_mut(),
+            light_mode,
+            amount,
+            insured,
+            burned,
+            0,
+            ctx.accounts.subscription.expires_at() == 0,
+        )?;
+        record_user_burn(
+            ctx.accounts.user_stats.as_mut(),
+            ctx.bumps.user_stats,
+            light_mode,
+            &ctx.accounts.config,
+            ctx.accounts.user.key(),
+            burned,
+        )?;
+
+        let previous_expires_at = ctx.accounts.subscription.expires_at();
+        let previous_state = ctx.accounts.subscription.state(
+            Clock::get()?.unix_timestamp,
+            ctx.accounts.config.grace_period,
+        );
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            ctx.accounts.config.subscription_duration,
+            ctx.accounts.config.grace_period,
+        )?;
+        let expires_at = ctx.accounts.subscription.expires_at();
+
+        emit_payment(
+            ctx.accounts.config.key(),
+            ctx.accounts.user.key(),
+            ctx.accounts.user.key(),
+            amount,
+            (insured, burned, 0, 0),
+            None,
+            previous_state,
+            previous_expires_at,
+            expires_at,
+        );
+        Ok(())
+    }
+
+    /// Payment path for partner programs bundling a subscription into their own
+    /// instructions: an allowlisted `caller_program` pays `amount` out of an
+    /// escrow its CPI authority PDA owns, crediting `user`'s subscription
+    pub fn process_payment_via_cpi(
+        ctx: Context<ProcessPaymentViaCpi>,
+        amount: u64,
+        light_mode: bool,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        let (caller_authority, _) = Pubkey::find_program_address(
+            &[CPI_AUTHORITY_SEED, ctx.accounts.config.key().as_ref()],
+            &ctx.accounts.caller_program.key(),
+        );
+        require_keys_eq!(
+            caller_authority,
+            ctx.accounts.caller_authority.key(),
+            ErrorCode::InvalidCpiCaller
+        );
+
+        enforce_compliance(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.user.key(),
+            amount,
+        )?;
+        enforce_jurisdiction_attestation(
+            &ctx.accounts.config,
+            ctx.accounts.attestation.as_ref(),
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        let (insured, burned) = route_payment(
+            &ctx.accounts.config,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.caller_token_account.to_account_info(),
+            ctx.accounts.insurance_vault.to_account_info(),
+            ctx.accounts
+                .mint
+                .as_ref()
+                .map(|mint| mint.to_account_info()),
+            ctx.accounts.caller_authority.to_account_info(),
+            &[],
+            amount,
+            effective_price(&ctx.accounts.config, Clock::get()?.unix_timestamp),
+            None,
+        )?;
+        record_payment(
+            ctx.accounts.stats_shard.as_mut(),
+            light_mode,
+            amount,
+            insured,
+            burned,
+            0,
+            ctx.accounts.subscription.expires_at() == 0,
+        )?;
+        record_user_burn(
+            ctx.accounts.user_stats.as_mut(),
+            ctx.bumps.user_stats,
+            light_mode,
+            &ctx.accounts.config,
+            ctx.accounts.user.key(),
+            burned,
+        )?;
+
+        let previous_expires_at = ctx.accounts.subscription.expires_at();
+        let previous_state = ctx.accounts.subscription.state(
+            Clock::get()?.unix_timestamp,
+            ctx.accounts.config.grace_period,
+        );
+        renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            ctx.accounts.config.subscription_duration,
+            ctx.accounts.config.grace_period,
+        )?;
+        let expires_at = ctx.accounts.subscription.expires_at();
+
+        emit_payment(
+            ctx.accounts.config.key(),
+            ctx.accounts.user.key(),
+            ctx.accounts.caller_authority.key(),
+            amount,
+            (insured, burned, 0, 0),
+            None,
+            previous_state,
+            previous_expires_at,
+            expires_at,
+        );
+        Ok(())
+    }
+
+    /// Pre-flight for frontends: runs every check `process_payment` would,
+    /// plus the balance and frozen checks the token program makes mid-transfer,
+    /// without moving funds. Simulate it to surface the exact failure up front.
+    pub fn validate_payment(
+        ctx: Context<ValidatePayment>,
+        amount: u64,
+        light_mode: bool,
+        quote: Option<PriceQuote>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        check_payment(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.user.key(),
+            &ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.attestation.as_ref(),
+            ctx.accounts.user_stats.as_deref(),
+            light_mode,
+            quote.as_ref(),
+            ctx.accounts.plan.as_deref(),
+            ctx.accounts.promo.as_deref().map(|promo| &**promo),
+            oracle_accounts(
+                ctx.accounts.price_update.as_ref(),
+                ctx.accounts.mint.as_ref(),
+            )?,
+            nft_discount_bps(
+                &ctx.accounts.user.key(),
+                ctx.accounts.nft_discount.as_ref(),
+                ctx.accounts.nft_token_account.as_ref(),
+                ctx.accounts.nft_metadata.as_ref(),
+            )?,
+            amount,
+            now,
+        )?;
+
+        let user_token_account = &ctx.accounts.user_token_account;
+        require!(
+            !user_token_account.is_frozen(),
+            ErrorCode::TokenAccountFrozen
+        );
+        if user_token_account.amount < amount {
+            return Err(diagnose(
+                ErrorCode::InsufficientBalance,
+                amount,
+                user_token_account.amount,
+            ));
+        }
+        let (_, burn_bps) = effective_split(&ctx.accounts.config, now);
+        require!(
+            burn_bps == 0 || ctx.accounts.mint.is_some(),
+            ErrorCode::MissingMint
+        );
+        if let Some(subscription) = &ctx.accounts.subscription {
+            require!(
+                subscription.status() != Subscription::STATUS_REVOKED,
+                ErrorCode::SubscriptionRevoked
+            );
+        }
+        let duration = ctx
+            .accounts
+            .plan
+            .as_ref()
+            .map_or(ctx.accounts.config.subscription_duration, |plan| {
+                plan.duration
+            });
+        let expires_at = ctx
+            .accounts
+            .subscription
+            .as_ref()
+            .map_or(0, |subscription| subscription.expires_at());
+        prepaid_duration(&ctx.accounts.config, expires_at, duration, now)?;
+        Ok(())
+    }
+
+    pub fn process_payment<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessPayment<'info>>,
+        amount: u64,
+        memo: Option<String>,
+        light_mode: bool,
+        quote: Option<PriceQuote>,
+        slab_id: Option<u64>,
+        reference: Option<[u8; 32]>,
+    ) -> Result<()> {
+        // 0. Everything `validate_payment` checks, then the quote nonce is spent
+        let now = Clock::get()?.unix_timestamp;
+        let price = check_payment(
+            &ctx.accounts.config,
+            &ctx.accounts.instructions,
+            &ctx.accounts.user.key(),
+            &ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.attestation.as_ref(),
+            ctx.accounts.user_stats.as_deref(),
+            light_mode,
+            quote.as_ref(),
+            ctx.accounts.plan.as_deref(),
+            ctx.accounts.promo.as_deref().map(|promo| &**promo),
+            oracle_accounts(
+                ctx.accounts.price_update.as_ref(),
+                ctx.accounts.mint.as_ref(),
+            )?,
+            nft_discount_bps(
+                &ctx.accounts.user.key(),
+                ctx.accounts.nft_discount.as_ref(),
+                ctx.accounts.nft_token_account.as_ref(),
+                ctx.accounts.nft_metadata.as_ref(),
+            )?,
+            amount,
+            now,
+        )?;
+        if let Some(quote) = quote {
+            let user_stats = ctx
+                .accounts
+                .user_stats
+                .as_mut()
+                .ok_or(ErrorCode::QuoteRequiresUserStats)?;
+            user_stats.last_quote_nonce = quote.nonce;
+        }
+        if let Some(promo) = ctx.accounts.promo.as_mut() {
+            promo.remaining_uses -= 1;
+        }
+        // A backend retrying with the same reference gets an error, not a second charge
+        match (reference, ctx.accounts.payment_reference.as_mut()) {
+            (None, None) => {}
+            (Some(reference), Some(payment_reference)) => {
+                require!(
+                    payment_reference.paid_at == 0,
+                    ErrorCode::DuplicateReference
+                );
+                payment_reference.config = ctx.accounts.config.key();
+                payment_reference.user = ctx.accounts.user.key();
+                payment_reference.reference = reference;
+                payment_reference.amount = amount;
+                payment_reference.paid_at = now;
+                payment_reference.bump = ctx.bumps.payment_reference.unwrap();
+            }
+            _ => return err!(ErrorCode::MissingPaymentReference),
+        }
+
+        // 1-3. The referrer's share comes off the top, the rest is split
+        //    between the insurance vault and the burn, or by the routing table
+        require!(
+            ctx.accounts.routing_table.is_some() == ctx.accounts.config.use_routing_table,
+            ErrorCode::InvalidRoutingTable
+        );
+        require!(
+            ctx.accounts.pending_burn_vault.is_some()
+                == (ctx.accounts.config.burn_flush_threshold > 0),
+            ErrorCode::InvalidVault
+        );
+        // A slab payment insures its slab's registered vault instead
+        match (slab_id, ctx.accounts.slab_registry.as_deref()) {
+            (None, None) => {}
+            (Some(slab_id), Some(slab_registry)) => require_keys_eq!(
+                slab_registry
+                    .active_vault(slab_id)
+                    .ok_or(ErrorCode::InvalidSlab)?,
+                ctx.accounts.insurance_vault.key(),
+                ErrorCode::InvalidVault
+            ),
+            _ => return err!(ErrorCode::InvalidSlab),
+        }
+        let referral = pay_referral(ctx.accounts, amount)?;
+        let mint = ctx
+            .accounts
+            .mint
+            .as_ref()
+            .map(|mint| mint.to_account_info());
+        let pending_burn = ctx
+            .accounts
+            .pending_burn_vault
+            .as_ref()
+            .map(|vault| vault.to_account_info());
+        let (insured, burned, staked) = match ctx.accounts.routing_table.as_deref() {
+            Some(routing_table) => {
+                let (routed, burned) = route_payment_table(
+                    &ctx.accounts.config,
+                    routing_table,
+                    ctx.accounts.token_program.to_account_info(),
+                    ctx.accounts.user_token_account.to_account_info(),
+                    mint,
+                    ctx.accounts.user.to_account_info(),
+                    ctx.remaining_accounts,
+                    amount.checked_sub(referral).unwrap(),
+                    price.saturating_sub(referral),
+                    pending_burn,
+                )?;
+                (routed, burned, 0)
+            }
+            None => {
+                // Only the two-way split stakes; a routing table names its own legs
+                let staking = match ctx.accounts.config.burn_mode {
+                    BurnMode::FullBurn => None,
+                    BurnMode::SplitWithStaking { staking_bps } => Some((
+                        ctx.accounts
+                            .staking_vault
+                            .as_ref()
+                            .ok_or(ErrorCode::MissingStakingVault)?
+                            .to_account_info(),
+                        staking_bps,
+                    )),
+                };
+                route_payment_staked(
+                    &ctx.accounts.config,
+                    ctx.accounts.token_program.to_account_info(),
+                    ctx.accounts.user_token_account.to_account_info(),
+                    ctx.accounts.insurance_vault.to_account_info(),
+                    mint,
+                    ctx.accounts.user.to_account_info(),
+                    &[],
+                    amount.checked_sub(referral).unwrap(),
+                    price.saturating_sub(referral),
+                    pending_burn,
+                    staking,
+                )?
+            }
+        };
+        record_payment(
+            ctx.accounts.stats_shard.as_mut(),
+            light_mode,
+            amount,
+            insured,
+            burned,
+            staked,
+            ctx.accounts.subscription.expires_at() == 0,
+        )?;
+        let payment_index = ctx.accounts.user_stats.as_ref().map(|stats| stats.payments);
+        record_user_burn(
+            ctx.accounts.user_stats.as_mut(),
+            ctx.bumps.user_stats,
+            light_mode,
+            &ctx.accounts.config,
+            ctx.accounts.user.key(),
+            burned,
+        )?;
+
+        // 4. Update the on-chain Option B Subscription PDA using unix_timestamp;
+        //    a plan buys its own period
+        let duration = ctx
+            .accounts
+            .plan
+            .as_ref()
+            .map_or(ctx.accounts.config.subscription_duration, |plan| {
+                plan.duration
+            });
+        let previous_expires_at = ctx.accounts.subscription.expires_at();
+        let previous_state = ctx
+            .accounts
+            .subscription
+            .state(now, ctx.accounts.config.grace_period);
+        let duration = renew_prepaid(
+            &ctx.accounts.config,
+            &mut ctx.accounts.subscription,
+            duration,
+            ctx.accounts.config.grace_period,
+        )?;
+        let expires_at = ctx.accounts.subscription.expires_at();
+        emit_payment(
+            ctx.accounts.config.key(),
+            ctx.accounts.user.key(),
+            subscriber(&ctx.accounts.user, ctx.accounts.beneficiary.as_ref()),
+            amount,
+            (insured, burned, staked, referral),
+            reference,
+            previous_state,
+            previous_expires_at,
+            expires_at,
+        );
+
+        // 5. Receipts are opt-in per payment, for integrators that don't index logs;
+        //    a compressed receipt is the cheap wallet-visible alternative
+        match ctx.accounts.receipt.as_mut() {
+            None if ctx.accounts.receipt_tree.is_some() => {
+                let accounts = &ctx.accounts;
+                let missing = || error!(ErrorCode::MissingReceiptTreeAccounts);
+                let receipt_tree = accounts.receipt_tree.as_ref().unwrap();
+                let merkle_tree = accounts.merkle_tree.as_ref().ok_or_else(missing)?;
+                require_keys_eq!(
+                    merkle_tree.key(),
+                    receipt_tree.merkle_tree,
+                    ErrorCode::InvalidReceiptTree
+                );
+                let config_key = accounts.config.key();
+                let tree_delegate_seeds: &[&[&[u8]]] = &[&[
+                    b"mint_authority",
+                    config_key.as_ref(),
+                    &[ctx.bumps.tree_delegate.ok_or_else(missing)?],
+                ]];
+                mint_compressed_receipt(
+                    receipt_tree,
+                    accounts.bubblegum_program.as_ref().ok_or_else(missing)?,
+                    accounts.tree_config.as_ref().ok_or_else(missing)?,
+                    &accounts.user,
+                    merkle_tree,
+                    accounts.tree_delegate.as_ref().ok_or_else(missing)?,
+                    accounts.log_wrapper.as_ref().ok_or_else(missing)?,
+                    accounts.compression_program.as_ref().ok_or_else(missing)?,
+                    &accounts.system_program,
+                    tree_delegate_seeds,
+                )?;
+                ctx.accounts.receipt_tree.as_mut().unwrap().minted += 1;
+            }
+            Some(receipt) => {
+                require!(!light_mode, ErrorCode::ReceiptRequiresUserStats);
+                receipt.config = ctx.accounts.config.key();
+                receipt.user = ctx.accounts.user.key();
+                receipt.subscriber =
+                    subscriber(&ctx.accounts.user, ctx.accounts.beneficiary.as_ref());
+                receipt.payment_index = payment_index.ok_or(ErrorCode::MissingUserStats)?;
+                receipt.amount = amount;
+                receipt.insurance_amount = insured;
+                receipt.burn_amount = burned;
+                receipt.refunded = 0;
+                // Routing tables and slabs pay their insurance out directly
+                receipt.escrowed = ctx.accounts.routing_table.is_none()
+                    && slab_id.is_none()
+                    && ctx.accounts.config.escrows_insurance();
+                receipt.plan_id = ctx.accounts.plan.as_ref().map(|plan| plan.plan_id);
+                receipt.slab_id = slab_id;
+                receipt.reference = reference;
+                receipt.duration = duration;
+                receipt.expires_at = expires_at;
+                receipt.paid_at = now;
+                receipt.bump = ctx.bumps.receipt.unwrap();
+            }
+            None => require!(
+                !ctx.accounts.config.require_receipts,
+                ErrorCode::MissingReceipt
+            ),
+        }
+        let subscription = &mut ctx.accounts.subscription;
+        subscription.payment_count = subscription.payment_count.checked_add(1).unwrap();
+        mint_soulbound_receipt(ctx.accounts, ctx.bumps.soulbound_authority)?;
+
+        // 6. Surface the payment reference to explorers and accounting tools
+        if let Some(memo) = memo {
+            require!(memo.len() <= MAX_MEMO_LEN, ErrorCode::MemoTooLong);
+            let memo_program = ctx
+                .accounts
+                .memo_program
+                .as_ref()
+                .ok_or(ErrorCode::MissingMemoProgram)?;
+            let memo_ctx = CpiContext::new(memo_program.to_account_info(), BuildMemo {});
+            memo::build_memo(memo_ctx, memo.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits `amount` between the instance's insurance vault and a permanent burn,
+/// pulling from `from` under `authority` (the owner, or one of the router's
+/// delegate PDAs when `signer_seeds` is set). Works with either token program. `amount` must
+/// cover `price`, the instance's effective price or a registered service's own price.
+/// `mint` may be omitted when nothing is burned, at the cost of an unchecked transfer.
+/// Passing the instance's `pending_burn` vault parks the burn share there instead.
+/// Returns `(insurance_amount, burn_amount)`.
+#[allow(clippy::too_many_arguments)]
+fn route_payment<'info>(
+    config: &Config,
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    insurance_vault: AccountInfo<'info>,
+    mint: Option<AccountInfo<'info>>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+    price: u64,
+    pending_burn: Option<AccountInfo<'info>>,
+) -> Result<(u64, u64)> {
+    route_payment_staked(
+        config,
+        token_program,
+        from,
+        insurance_vault,
+        mint,
+        authority,
+        signer_seeds,
+        amount,
+        price,
+        pending_burn,
+        None,
+    )
+    .map(|(insured, burned, _)| (insured, burned))
+}
+
+/// [`route_payment`], paying `staking_bps` of the burn share into a
+/// `(staking_vault, staking_bps)` instead of burning it.
+/// Returns `(insurance_amount, burn_amount, staking_amount)`.
+#[allow(clippy::too_many_arguments)]
+fn route_payment_staked<'info>(
+    config: &Config,
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    insurance_vault: AccountInfo<'info>,
+    mint: Option<AccountInfo<'info>>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+    price: u64,
+    pending_burn: Option<AccountInfo<'info>>,
+    staking: Option<(AccountInfo<'info>, u16)>,
+) -> Result<(u64, u64, u64)> {
+    let now = Clock::get()?.unix_timestamp;
+    enforce_not_paused(config)?;
+    // An unpriced transfer, like a resale royalty, is a cut of a payment
+    if price > 0 {
+        enforce_payment_amount(config, amount)?;
+    }
+    enforce_mint_cutover(config, now)?;
+    route_payment_split(
+        token_program,
+        from,
+        insurance_vault,
+        mint,
+        authority,
+        signer_seeds,
+        amount,
+        price,
+        effective_split(config, now),
+        pending_burn,
+        staking,
+    )
+}
+
+/// Burns `amount` out of a vault held by the instance's pending-burn PDA,
+/// which signs with `authority_bump`
+#[allow(clippy::too_many_arguments)]
+fn burn_vault<'info>(
+    config_key: &Pubkey,
+    authority_bump: u8,
+    token_program: AccountInfo<'info>,
+    vault: AccountInfo<'info>,
+    vault_authority: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    decimals: u8,
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, ErrorCode::InvalidAmount);
+    let vault_seeds: &[&[&[u8]]] = &[&[
+        VaultKind::PendingBurn.seed(),
+        config_key.as_ref(),
+        &[authority_bump],
+    ]];
+    let burn_cpi_accounts = token_interface::BurnChecked {
+        mint,
+        from: vault,
+        authority: vault_authority,
+    };
+    let burn_ctx = CpiContext::new_with_signer(token_program, burn_cpi_accounts, vault_seeds);
+    token_interface::burn_checked(burn_ctx, amount, decimals)
+}
+
+/// [`route_payment`] under an explicit `(insurance_bps, burn_bps)` split, such
+/// as a catalog product's own profile
+#[allow(clippy::too_many_arguments)]
+fn route_payment_split<'info>(
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    insurance_vault: AccountInfo<'info>,
+    mint: Option<AccountInfo<'info>>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+    price: u64,
+    (insurance_bps, burn_bps): (u16, u16),
+    pending_burn: Option<AccountInfo<'info>>,
+    staking: Option<(AccountInfo<'info>, u16)>,
+) -> Result<(u64, u64, u64)> {
+    // Enforce safe math constraints
+    validate_split(insurance_bps, burn_bps)?;
+    require!(amount > 0, ErrorCode::InvalidAmount);
+    if amount < price {
+        return Err(diagnose(ErrorCode::Underpayment, price, amount));
+    }
+
+    // 1. Calculate splits (per-instance BPS, the scheduled promo's, or a product's)
+    let insurance_amount = amount
+        .checked_mul(insurance_bps as u64)
+        .unwrap()
+        .checked_div(10_000)
+        .unwrap();
+    let burn_amount = amount.checked_sub(insurance_amount).unwrap();
+
+    // 2. Route funds to the percolator insurance vault. A transfer-fee mint
+    //    withholds its fee in the vault, so only the net counts as insured
+    let insured_amount = transfer_tokens(
+        token_program.clone(),
+        from.clone(),
+        insurance_vault,
+        mint.as_ref(),
+        authority.clone(),
+        signer_seeds,
+        insurance_amount,
+    )?;
+
+    // 3. A staking burn mode pays its part of the burn share to stakers
+    let (burn_amount, staked_amount) = match staking {
+        Some((staking_vault, staking_bps)) => {
+            let staking_amount = burn_amount
+                .checked_mul(staking_bps as u64)
+                .unwrap()
+                .checked_div(10_000)
+                .unwrap();
+            let staked_amount = transfer_tokens(
+                token_program.clone(),
+                from.clone(),
+                staking_vault,
+                mint.as_ref(),
+                authority.clone(),
+                signer_seeds,
+                staking_amount,
+            )?;
+            (burn_amount - staking_amount, staked_amount)
+        }
+        None => (burn_amount, 0),
+    };
+
+    // 4. Burn the remaining tokens out of existence permanently
+    let burn_amount = if burn_amount > 0 {
+        burn_share(
+            token_program,
+            from,
+            mint,
+            authority,
+            signer_seeds,
+            pending_burn,
+            burn_amount,
+        )?
+    } else {
+        0
+    };
+
+    Ok((insured_amount, burn_amount, staked_amount))
+}
+
+/// Splits `amount` across the routing table's routes, rounding dust into
+/// the last one. `destinations` are the non-burn routes' token accounts in
+/// table order. Returns the amount credited to destinations and the burn.
+#[allow(clippy::too_many_arguments)]
+fn route_payment_table<'info>(
+    config: &Config,
+    routing_table: &RoutingTable,
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    mint: Option<AccountInfo<'info>>,
+    authority: AccountInfo<'info>,
+    destinations: &'info [AccountInfo<'info>],
+    amount: u64,
+    price: u64,
+    pending_burn: Option<AccountInfo<'info>>,
+) -> Result<(u64, u64)> {
+    let now = Clock::get()?.unix_timestamp;
+    enforce_not_paused(config)?;
+    enforce_mint_cutover(config, now)?;
+    require!(amount > 0, ErrorCode::InvalidAmount);
+    if amount < price {
+        return Err(diagnose(ErrorCode::Underpayment, price, amount));
+    }
+
+    let routes = routing_table.routes();
+    require!(
+        destinations.len() == routes.iter().filter(|route| !route.burns()).count(),
+        ErrorCode::InvalidRoutingTable
+    );
+
+    let mut destinations = destinations.iter();
+    let mut remaining = amount;
+    let (mut routed, mut burned) = (0u64, 0u64);
+    for (index, route) in routes.iter().enumerate() {
+        let share = if index + 1 == routes.len() {
+            remaining
+        } else {
+            (amount as u128 * route.bps as u128 / 10_000) as u64
+        };
+        remaining = remaining.checked_sub(share).unwrap();
+        if share == 0 {
+            if !route.burns() {
+                destinations.next();
+            }
+            continue;
+        }
+
+        if route.burns() {
+            let share = burn_share(
+                token_program.clone(),
+                from.clone(),
+                mint.clone(),
+                authority.clone(),
+                &[],
+                pending_burn.clone(),
+                share,
+            )?;
+            burned = burned.checked_add(share).unwrap();
+            continue;
+        }
+
+        let destination = destinations.next().unwrap();
+        require_keys_eq!(
+            destination.key(),
+            route.destination,
+            ErrorCode::InvalidRoutingTable
+        );
+        require!(
+            destination.owner == token_program.key
+                && token_account_field(destination, TOKEN_ACCOUNT_MINT_OFFSET) == config.mint,
+            ErrorCode::InvalidVaultMint
+        );
+        let credited = transfer_tokens(
+            token_program.clone(),
+            from.clone(),
+            destination.clone(),
+            mint.as_ref(),
+            authority.clone(),
+            &[],
+            share,
+        )?;
+        routed = routed.checked_add(credited).unwrap();
+    }
+
+    Ok((routed, burned))
+}
+
+/// Burns `amount` out of `from`, or parks it in the instance's `pending_burn`
+/// vault for `flush_burn` to burn in bulk, which keeps the payment from
+/// write-locking the mint. Returns the amount burned or parked.
+fn burn_share<'info>(
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    mint: Option<AccountInfo<'info>>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    pending_burn: Option<AccountInfo<'info>>,
+    amount: u64,
+) -> Result<u64> {
+    if let Some(pending_burn) = pending_burn {
+        return transfer_tokens(
+            token_program,
+            from,
+            pending_burn,
+            mint.as_ref(),
+            authority,
+            signer_seeds,
+            amount,
+        );
+    }
+
+    let mint = mint.ok_or(ErrorCode::MissingMint)?;
+    let decimals = mint_decimals(&mint)?;
+    let burn_cpi_accounts = token_interface::BurnChecked {
+        mint,
+        from,
+        authority,
+    };
+    let burn_ctx = CpiContext::new_with_signer(token_program, burn_cpi_accounts, signer_seeds);
+    token_interface::burn_checked(burn_ctx, amount, decimals)?;
+    Ok(amount)
+}
+
+/// Moves `amount` of the instance mint and returns what `to` was credited
+/// after any transfer fee
+fn transfer_tokens<'info>(
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    mint: Option<&AccountInfo<'info>>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<u64> {
+    match mint {
+        Some(mint) => {
+            let transfer_cpi_accounts = token_interface::TransferChecked {
+                from,
+                mint: mint.clone(),
+                to,
+                authority,
+            };
+            let transfer_ctx =
+                CpiContext::new_with_signer(token_program, transfer_cpi_accounts, signer_seeds);
+            token_interface::transfer_checked(transfer_ctx, amount, mint_decimals(mint)?)?;
+            Ok(amount.checked_sub(transfer_fee(mint, amount)?).unwrap())
+        }
+        None => {
+            // Without the mint there are no decimals to check against; the token
+            // program still rejects this for mints whose extensions need them
+            let transfer_cpi_accounts = token_interface::Transfer {
+                from,
+                to,
+                authority,
+            };
+            let transfer_ctx =
+                CpiContext::new_with_signer(token_program, transfer_cpi_accounts, signer_seeds);
+            #[allow(deprecated)]
+            token_interface::transfer(transfer_ctx, amount)?;
+            Ok(amount)
+        }
+    }
+}
+
+/// Pays the referrer of a referred payment its `referral_bps` share of
+/// `amount` and returns the share; zero when no referrer was passed
+fn pay_referral(accounts: &mut ProcessPayment, amount: u64) -> Result<u64> {
+    let Some(referrer) = accounts.referrer.as_mut() else {
+        return Ok(0);
+    };
+    require_keys_neq!(
+        referrer.authority,
+        accounts.user.key(),
+        ErrorCode::InvalidReferral
+    );
+    let token_account = accounts
+        .referrer_token_account
+        .as_ref()
+        .ok_or(ErrorCode::InvalidReferral)?;
+    require_keys_eq!(
+        token_account.key(),
+        referrer.token_account,
+        ErrorCode::InvalidReferral
+    );
+
+    let share = (amount as u128 * accounts.config.referral_bps as u128 / 10_000) as u64;
+    if share > 0 {
+        transfer_tokens(
+            accounts.token_program.to_account_info(),
+            accounts.user_token_account.to_account_info(),
+            token_account.to_account_info(),
+            accounts
+                .mint
+                .as_ref()
+                .map(|mint| mint.to_account_info())
+                .as_ref(),
+            accounts.user.to_account_info(),
+            &[],
+            share,
+        )?;
+    }
+    referrer.referred_payments = referrer.referred_payments.checked_add(1).unwrap();
+    referrer.total_earned = referrer.total_earned.checked_add(share).unwrap();
+    Ok(share)
+}
+
+/// The fee a Token-2022 transfer-fee mint withholds from a transfer of
+/// `amount` this epoch; zero for legacy mints and mints without the extension
+fn transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if *mint.owner != token_2022::ID {
+        return Ok(0);
+    }
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let Ok(fee_config) = mint.get_extension::<TransferFeeConfig>() else {
+        return Ok(0);
+    };
+    fee_config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or_else(|| error!(ErrorCode::InvalidAmount))
+}
+
+/// Token-2022 CPI guard rejects owner-signed transfers and burns made from
+/// inside a program, so guarded accounts must pay through the delegate flow.
+fn cpi_guard_enabled(token_account: &AccountInfo) -> Result<bool> {
+    if *token_account.owner != token_2022::ID {
+        return Ok(false);
+    }
+    let data = token_account.try_borrow_data()?;
+    let account = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+    Ok(account
+        .get_extension::<CpiGuard>()
+        .map(|guard| bool::from(guard.lock_cpi))
+        .unwrap_or(false))
+}
+
+/// Payer-keyed shard index, see [`STATS_SHARD_COUNT`]
+pub fn stats_shard_index(payer: &Pubkey) -> u8 {
+    payer.as_ref()[0] % STATS_SHARD_COUNT
+}
+
+/// `user`'s subscription PDA on the instance at `config`, so clients and
+/// integrating programs don't have to repeat the seeds
+pub fn subscription_address(config: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"subscription", config.as_ref(), user.as_ref()], &ID)
+}
+
+/// Light-mode payments leave their shard out of the transaction, so
+/// latency-sensitive integrations don't contend on its write lock.
+/// `new_subscriber` marks the first payment into a subscription, which is
+/// what the best-effort unique subscriber count counts
+fn record_payment(
+    shard: Option<&mut Account<StatsShard>>,
+    light_mode: bool,
+    amount: u64,
+    insured: u64,
+    burned: u64,
+    staked: u64,
+    new_subscriber: bool,
+) -> Result<()> {
+    if light_mode {
+        return Ok(());
+    }
+    let shard = shard.ok_or(ErrorCode::MissingStatsShard)?;
+    shard.payments = shard.payments.checked_add(1).unwrap();
+    shard.volume = shard.volume.checked_add(amount).unwrap();
+    shard.insured = shard.insured.checked_add(insured).unwrap();
+    shard.burned = shard.burned.checked_add(burned).unwrap();
+    shard.staked = shard.staked.checked_add(staked).unwrap();
+    if new_subscriber {
+        shard.subscribers = shard.subscribers.checked_add(1).unwrap();
+    }
+    Ok(())
+}
+
+/// Credits the payer's lifetime burn and refreshes their loyalty tier; skipped
+/// in light mode along with the shard
+fn record_user_burn(
+    user_stats: Option<&mut Account<UserStats>>,
+    bump: Option<u8>,
+    light_mode: bool,
+    config: &Account<Config>,
+    user: Pubkey,
+    burned: u64,
+) -> Result<()> {
+    if light_mode {
+        return Ok(());
+    }
+    let user_stats = user_stats.ok_or(ErrorCode::MissingUserStats)?;
+    user_stats.config = config.key();
+    user_stats.user = user;
+    user_stats.payments = user_stats.payments.checked_add(1).unwrap();
+    user_stats.lifetime_burned = user_stats.lifetime_burned.checked_add(burned).unwrap();
+    user_stats.tier = loyalty_tier(config, user_stats.lifetime_burned);
+    user_stats.bump = bump.unwrap();
+    Ok(())
+}
+
+/// The highest tier whose non-zero threshold `lifetime_burned` reaches
+pub fn loyalty_tier(config: &Config, lifetime_burned: u64) -> LoyaltyTier {
+    let [bronze, silver, gold] = config.loyalty_thresholds;
+    let reached = |threshold: u64| threshold > 0 && lifetime_burned >= threshold;
+    if reached(gold) {
+        LoyaltyTier::Gold
+    } else if reached(silver) {
+        LoyaltyTier::Silver
+    } else if reached(bronze) {
+        LoyaltyTier::Bronze
+    } else {
+        LoyaltyTier::None
+    }
+}
+
+/// Reads a pubkey field of an SPL token account without unpacking it;
+/// anything too short to be a token account reads as the default key
+fn token_account_field(account: &AccountInfo, offset: usize) -> Pubkey {
+    account
+        .try_borrow_data()
+        .ok()
+        .and_then(|data| data.get(offset..offset + 32).map(Pubkey::try_from))
+        .and_then(|key| key.ok())
+        .unwrap_or_default()
+}
+
+fn mint_decimals(mint: &AccountInfo) -> Result<u8> {
+    let data = mint.try_borrow_data()?;
+    data.get(MINT_DECIMALS_OFFSET)
+        .copied()
+        .ok_or_else(|| error!(ErrorCode::InvalidMint))
+}
+
+fn validate_split(insurance_bps: u16, burn_bps: u16) -> Result<()> {
+    require!(
+        insurance_bps as u64 + burn_bps as u64 == 10_000,
+        ErrorCode::InvalidSplit
+    );
+    Ok(())
+}
+
+/// Checked by every payment path, whichever split it routes through. Past
+/// the sunset the failure names the successor program payers should move to.
+fn enforce_not_paused(config: &Config) -> Result<()> {
+    require!(!config.paused, ErrorCode::ProgramPaused);
+    if config.is_sunset(Clock::get()?.unix_timestamp) {
+        emit!(ProgramSunsetNotice {
+            successor_program: config.successor_program,
+            sunset_at: config.sunset_at,
+        });
+        msg!("Payments have moved to {}", config.successor_program);
+        return err!(ErrorCode::ProgramSunset);
+    }
+    Ok(())
+}
+
+/// Payments in the outgoing mint stop at the cutover, even before
+/// `complete_mint_migration` has swapped the config over
+fn enforce_mint_cutover(config: &Config, now: i64) -> Result<()> {
+    require!(
+        config.successor_mint == Pubkey::default() || now < config.mint_cutover_at,
+        ErrorCode::MintMigrationPending
+    );
+    Ok(())
+}
+
+fn clear_mint_migration(config: &mut Config) {
+    config.successor_mint = Pubkey::default();
+    config.successor_insurance_vault = Pubkey::default();
+    config.mint_cutover_at = 0;
+}
+
+/// Insurance and burn BPS at `now`; the scheduled promo split replaces the
+/// instance split inside its window
+fn effective_split(config: &Config, now: i64) -> (u16, u16) {
+    if now >= config.split_promo_starts_at && now < config.split_promo_ends_at {
+        (
+            config.split_promo_insurance_bps,
+            config.split_promo_burn_bps,
+        )
+    } else {
+        (config.insurance_bps, config.burn_bps)
+    }
+}
+
+/// Price of one period at `now`: the base price less the bonding-curve discount,
+/// with the promo discount applied on top while it runs.
+/// Zero means the instance accepts any non-zero amount.
+fn effective_price(config: &Config, now: i64) -> u64 {
+    let price = apply_discount(config.price, config.curve_discount_bps);
+    if config.promo_bps == 0 || now >= config.promo_ends_at {
+        return price;
+    }
+    apply_discount(price, config.promo_bps)
+}
+
+/// Bonding-curve discount for `burned` total instance burn, capped at the curve's maximum
+fn curve_discount_bps(config: &Config, burned: u64) -> u16 {
+    if config.curve_burn_step == 0 {
+        return 0;
+    }
+    let steps = burned / config.curve_burn_step;
+    steps
+        .saturating_mul(config.curve_step_bps as u64)
+        .min(config.curve_max_bps as u64) as u16
+}
+
+fn apply_discount(price: u64, discount_bps: u16) -> u64 {
+    let discount = (price as u128)
+        .checked_mul(discount_bps as u128)
+        .unwrap()
+        .checked_div(10_000)
+        .unwrap();
+    price.checked_sub(discount as u64).unwrap()
+}
+
+/// Discount for a payer presenting an NFT from a whitelisted collection: the
+/// token account must hold the NFT for `payer`, and the NFT's Metaplex
+/// metadata must list the collection as verified. Zero without the accounts.
+fn nft_discount_bps(
+    payer: &Pubkey,
+    nft_discount: Option<&Account<NftDiscount>>,
+    nft_token_account: Option<&UncheckedAccount>,
+    nft_metadata: Option<&UncheckedAccount>,
+) -> Result<u16> {
+    let (nft_discount, token_account, metadata) =
+        match (nft_discount, nft_token_account, nft_metadata) {
+            (None, None, None) => return Ok(0),
+            (Some(nft_discount), Some(token_account), Some(metadata)) => {
+                (nft_discount, token_account, metadata)
+            }
+            _ => return err!(ErrorCode::IncompleteNftDiscount),
+        };
+
+    require!(
+        *token_account.owner == token::ID || *token_account.owner == token_2022::ID,
+        ErrorCode::InvalidNftDiscount
+    );
+    require!(
+        token_account_field(token_account, TOKEN_ACCOUNT_OWNER_OFFSET) == *payer,
+        ErrorCode::InvalidOwner
+    );
+    let holds_nft = token_account
+        .try_borrow_data()?
+        .get(TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8)
+        .is_some_and(|amount| amount != [0; 8]);
+    require!(holds_nft, ErrorCode::InvalidNftDiscount);
+
+    // Only the metadata program writes accounts it owns, and it only creates
+    // metadata at the mint's PDA, so owner plus the mint field authenticate it
+    require!(
+        *metadata.owner == TOKEN_METADATA_PROGRAM_ID,
+        ErrorCode::InvalidNftDiscount
+    );
+    let data = metadata.try_borrow_data()?;
+    let (mint, collection) =
+        metadata_verified_collection(&data).ok_or(ErrorCode::InvalidNftDiscount)?;
+    require!(
+        mint == token_account_field(token_account, TOKEN_ACCOUNT_MINT_OFFSET)
+            && collection == nft_discount.collection,
+        ErrorCode::InvalidNftDiscount
+    );
+    Ok(nft_discount.discount_bps)
+}
+
+/// Walks a Metaplex `Metadata` account up to its collection field. Returns
+/// the mint and collection key when the collection is set and verified.
+fn metadata_verified_collection(data: &[u8]) -> Option<(Pubkey, Pubkey)> {
+    fn take<'a>(data: &'a [u8], at: &mut usize, len: usize) -> Option<&'a [u8]> {
+        let bytes = data.get(*at..at.checked_add(len)?)?;
+        *at += len;
+        Some(bytes)
+    }
+    fn take_u32(data: &[u8], at: &mut usize) -> Option<usize> {
+        let bytes = take(data, at, 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+    }
+
+    let at = &mut 0;
+    if take(data, at, 1)? != [METADATA_V1_KEY] {
+        return None;
+    }
+    take(data, at, 32)?; // update authority
+    let mint = Pubkey::try_from(take(data, at, 32)?).ok()?;
+    for _ in 0..3 {
+        // name, symbol, uri
+        let len = take_u32(data, at)?;
+        take(data, at, len)?;
+    }
+    take(data, at, 2)?; // seller fee basis points
+    if take(data, at, 1)? == [1] {
+        let creators = take_u32(data, at)?;
+        take(data, at, creators.checked_mul(METADATA_CREATOR_LEN)?)?;
+    }
+    take(data, at, 2)?; // primary sale happened, is mutable
+    for _ in 0..2 {
+        // edition nonce, token standard
+        if take(data, at, 1)? == [1] {
+            take(data, at, 1)?;
+        }
+    }
+    if take(data, at, 1)? != [1] {
+        return None;
+    }
+    let verified = take(data, at, 1)? == [1];
+    let collection = Pubkey::try_from(take(data, at, 32)?).ok()?;
+    verified.then_some((mint, collection))
+}
+
+fn write_price_feed(feed: &mut PriceFeed, config: &Account<Config>, now: i64) {
+    let promo_active = config.promo_bps > 0 && now < config.promo_ends_at;
+    feed.config = config.key();
+    feed.price = config.price;
+    feed.effective_price = effective_price(config, now);
+    feed.promo_bps = if promo_active { config.promo_bps } else { 0 };
+    feed.promo_ends_at = if promo_active {
+        config.promo_ends_at
+    } else {
+        0
+    };
+    (feed.insurance_bps, feed.burn_bps) = effective_split(config, now);
+    feed.subscription_duration = config.subscription_duration;
+    feed.lst_usd_price = config.lst_usd_price;
+    feed.updated_at = now;
+}
+
+/// Adds what is left of `from` onto `to` and returns the new expiry; `from`
+/// is closed by the caller's account constraints
+fn move_subscription_time(from: &Subscription, to: &mut Subscription) -> Result<i64> {
+    require!(
+        from.status() != Subscription::STATUS_REVOKED,
+        ErrorCode::SubscriptionRevoked
+    );
+    let remaining = from.expires_at() - Clock::get()?.unix_timestamp;
+    require!(remaining > 0, ErrorCode::SubscriptionExpired);
+    extend_subscription(to, remaining)
+}
+
+fn extend_subscription(subscription: &mut Subscription, duration: i64) -> Result<i64> {
+    renew_subscription(subscription, duration, 0)
+}
+
+/// Paid renewals: within `grace_period` of a lapsed expiry the new period
+/// carries on from that expiry, so a late renewal pays for the grace time it
+/// used instead of restarting the clock. Past the grace window it starts from now.
+fn renew_subscription(
+    subscription: &mut Subscription,
+    duration: i64,
+    grace_period: i64,
+) -> Result<i64> {
+    require!(
+        subscription.status() != Subscription::STATUS_REVOKED,
+        ErrorCode::SubscriptionRevoked
+    );
+    let current_time = Clock::get()?.unix_timestamp;
+
+    let base_time = match subscription.state(current_time, grace_period) {
+        SubscriptionState::Expired => current_time,
+        SubscriptionState::Active | SubscriptionState::Grace => subscription.expires_at(),
+    };
+    subscription.version = Subscription::VERSION;
+    subscription.set_expires_at(base_time.checked_add(duration).unwrap())?;
+
+    Ok(subscription.expires_at())
+}
+
+/// Every paid renewal: rate-limited by [`enforce_payment_interval`], with
+/// `duration` cut to the instance's prepaid cap by [`prepaid_duration`], then
+/// renewed as [`renew_subscription`] does. Returns the time bought.
+fn renew_prepaid(
+    config: &Config,
+    subscription: &mut Subscription,
+    duration: i64,
+    grace_period: i64,
+) -> Result<i64> {
+    enforce_payment_interval(config, subscription)?;
+    let now = Clock::get()?.unix_timestamp;
+    let duration = prepaid_duration(config, subscription.expires_at(), duration, now)?;
+    renew_subscription(subscription, duration, grace_period)?;
+    Ok(duration)
+}
+
+/// Mints one compressed receipt NFT to `user` through Bubblegum's `mint_v1`,
+/// signed by the tree delegate PDA. Bubblegum checks the tree config, log
+/// wrapper and compression program itself.
+#[allow(clippy::too_many_arguments)]
+fn mint_compressed_receipt<'info>(
+    receipt_tree: &ReceiptTree,
+    bubblegum_program: &UncheckedAccount<'info>,
+    tree_config: &UncheckedAccount<'info>,
+    user: &Signer<'info>,
+    merkle_tree: &UncheckedAccount<'info>,
+    tree_delegate: &UncheckedAccount<'info>,
+    log_wrapper: &UncheckedAccount<'info>,
+    compression_program: &UncheckedAccount<'info>,
+    system_program: &Program<'info, System>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    // MetadataArgs, Borsh-encoded: an immutable one-off with no creators,
+    // collection or uses
+    let mut data = BUBBLEGUM_MINT_V1_DISCRIMINATOR.to_vec();
+    receipt_tree.name.serialize(&mut data)?;
+    receipt_tree.symbol.serialize(&mut data)?;
+    receipt_tree.uri.serialize(&mut data)?;
+    0u16.serialize(&mut data)?; // seller_fee_basis_points
+    true.serialize(&mut data)?; // primary_sale_happened
+    false.serialize(&mut data)?; // is_mutable
+    None::<u8>.serialize(&mut data)?; // edition_nonce
+    Some(0u8).serialize(&mut data)?; // token_standard: NonFungible
+    data.push(0); // collection: None
+    data.push(0); // uses: None
+    0u8.serialize(&mut data)?; // token_program_version: Original
+    0u32.serialize(&mut data)?; // creators: empty
+
+    let accounts = [
+        tree_config.to_account_info(),
+        user.to_account_info(),
+        user.to_account_info(),
+        merkle_tree.to_account_info(),
+        user.to_account_info(),
+        tree_delegate.to_account_info(),
+        log_wrapper.to_account_info(),
+        compression_program.to_account_info(),
+        system_program.to_account_info(),
+    ];
+    let mint_ix = Instruction {
+        program_id: BUBBLEGUM_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(tree_config.key(), false),
+            AccountMeta::new_readonly(user.key(), false),
+            AccountMeta::new_readonly(user.key(), false),
+            AccountMeta::new(merkle_tree.key(), false),
+            AccountMeta::new_readonly(user.key(), true),
+            AccountMeta::new_readonly(tree_delegate.key(), true),
+            AccountMeta::new_readonly(log_wrapper.key(), false),
+            AccountMeta::new_readonly(compression_program.key(), false),
+            AccountMeta::new_readonly(system_program.key(), false),
+        ],
+        data,
+    };
+    require_keys_eq!(
+        bubblegum_program.key(),
+        BUBBLEGUM_PROGRAM_ID,
+        ErrorCode::InvalidReceiptTree
+    );
+    invoke_signed(&mint_ix, &accounts, signer_seeds)?;
+    Ok(())
+}
+
+/// Mints one soulbound receipt token to the payment's subscriber unless their
+/// associated account already holds one, creating the account on first use.
+/// A no-op when no soulbound mint is passed.
+fn mint_soulbound_receipt(accounts: &ProcessPayment, authority_bump: Option<u8>) -> Result<()> {
+    let Some(mint) = accounts.soulbound_mint.as_ref() else {
+        return Ok(());
+    };
+    let missing = || error!(ErrorCode::MissingSoulboundAccounts);
+    let token_account = accounts.soulbound_account.as_ref().ok_or_else(missing)?;
+    let authority = accounts.soulbound_authority.as_ref().ok_or_else(missing)?;
+    let token_program = accounts.token_2022_program.as_ref().ok_or_else(missing)?;
+    let associated_token_program = accounts
+        .associated_token_program
+        .as_ref()
+        .ok_or_else(missing)?;
+    let holder = match accounts.beneficiary.as_ref() {
+        Some(beneficiary) => beneficiary.to_account_info(),
+        None => accounts.user.to_account_info(),
+    };
+    require_keys_eq!(
+        token_account.key(),
+        get_associated_token_address_with_program_id(holder.key, mint.key, &token_2022::ID),
+        ErrorCode::InvalidSoulboundAccount
+    );
+
+    if token_account.data_is_empty() {
+        let create_cpi_accounts = associated_token::Create {
+            payer: accounts.user.to_account_info(),
+            associated_token: token_account.to_account_info(),
+            authority: holder,
+            mint: mint.to_account_info(),
+            system_program: accounts.system_program.to_account_info(),
+            token_program: token_program.to_account_info(),
+        };
+        let create_ctx = CpiContext::new(
+            associated_token_program.to_account_info(),
+            create_cpi_accounts,
+        );
+        associated_token::create(create_ctx)?;
+    } else {
+        let holds_receipt = token_account
+            .try_borrow_data()?
+            .get(TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8)
+            .is_some_and(|amount| amount != [0; 8]);
+        if holds_receipt {
+            return Ok(());
+        }
+    }
+
+    let config_key = accounts.config.key();
+    let authority_seeds: &[&[&[u8]]] = &[&[
+        b"mint_authority",
+        config_key.as_ref(),
+        &[authority_bump.ok_or_else(missing)?],
+    ]];
+    let mint_cpi_accounts = token_interface::MintToChecked {
+        mint: mint.to_account_info(),
+        to: token_account.to_account_info(),
+        authority: authority.to_account_info(),
+    };
+    let mint_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        mint_cpi_accounts,
+        authority_seeds,
+    );
+    token_interface::mint_to_checked(mint_ctx, 1, 0)
+}
+
+/// Writes the Token-2022 metadata extension onto a program-minted mint whose
+/// metadata pointer already references itself, topping up rent for the
+/// variable-length TLV entry first.
+#[allow(clippy::too_many_arguments)]
+fn init_mint_metadata<'info>(
+    config: &Pubkey,
+    payer: &Signer<'info>,
+    mint: &AccountInfo<'info>,
+    mint_authority: &UncheckedAccount<'info>,
+    mint_authority_bump: u8,
+    token_program: &Program<'info, Token2022>,
+    system_program: &Program<'info, System>,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> Result<()> {
+    let metadata = TokenMetadata {
+        mint: mint.key(),
+        name: name.clone(),
+        symbol: symbol.clone(),
+        uri: uri.clone(),
+        ..Default::default()
+    };
+    let new_len = mint.data_len() + metadata.tlv_size_of()?;
+    let rent_due = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(mint.lamports());
+    if rent_due > 0 {
+        let fund_cpi_accounts = anchor_lang::system_program::Transfer {
+            from: payer.to_account_info(),
+            to: mint.clone(),
+        };
+        let fund_ctx = CpiContext::new(system_program.to_account_info(), fund_cpi_accounts);
+        anchor_lang::system_program::transfer(fund_ctx, rent_due)?;
+    }
+
+    let signer_seeds: &[&[&[u8]]] =
+        &[&[b"mint_authority", config.as_ref(), &[mint_authority_bump]]];
+    let metadata_cpi_accounts = TokenMetadataInitialize {
+        program_id: token_program.to_account_info(),
+        metadata: mint.clone(),
+        update_authority: mint_authority.to_account_info(),
+        mint_authority: mint_authority.to_account_info(),
+        mint: mint.clone(),
+    };
+    let metadata_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        metadata_cpi_accounts,
+        signer_seeds,
+    );
+    token_metadata_initialize(metadata_ctx, name, symbol, uri)
+}
+
+/// The price update a USD plan is priced with, and the mint decimals it
+/// converts into; the mint must be passed alongside the update
+fn oracle_accounts<'a, 'info>(
+    price_update: Option<&'a UncheckedAccount<'info>>,
+    mint: Option<&'a UncheckedAccount<'info>>,
+) -> Result<Option<(&'a AccountInfo<'info>, u8)>> {
+    let Some(price_update) = price_update else {
+        return Ok(None);
+    };
+    let mint = mint.ok_or(ErrorCode::MissingMint)?;
+    Ok(Some((price_update, mint_decimals(mint)?)))
+}
+
+/// Converts `usd_price` millionths of a dollar into base units of a token with
+/// `decimals` at the price `feed_id` publishes. The price is taken at the
+/// bottom of its confidence interval and the result rounded up, so payments
+/// never come in under price.
+fn usd_to_tokens(
+    config: &Config,
+    feed_id: &[u8; 32],
+    price_update: &AccountInfo,
+    decimals: u8,
+    usd_price: u64,
+    now: i64,
+) -> Result<u64> {
+    require!(*feed_id != [0; 32], ErrorCode::InvalidPriceUpdate);
+    let data = price_update.try_borrow_data()?;
+    let read = |offset: usize, len: usize| -> Result<&[u8]> {
+        data.get(offset..offset + len)
+            .ok_or_else(|| error!(ErrorCode::InvalidPriceUpdate))
+    };
+    require!(
+        read(0, 8)? == PRICE_UPDATE_V2_DISCRIMINATOR
+            && read(PRICE_UPDATE_VERIFICATION_OFFSET, 1)?[0] == PRICE_UPDATE_FULLY_VERIFIED
+            && read(PRICE_UPDATE_FEED_ID_OFFSET, 32)? == feed_id,
+        ErrorCode::InvalidPriceUpdate
+    );
+    let price = i64::from_le_bytes(read(PRICE_UPDATE_PRICE_OFFSET, 8)?.try_into().unwrap());
+    let conf = u64::from_le_bytes(read(PRICE_UPDATE_CONF_OFFSET, 8)?.try_into().unwrap());
+    let exponent = i32::from_le_bytes(read(PRICE_UPDATE_EXPONENT_OFFSET, 4)?.try_into().unwrap());
+    let publish_time = i64::from_le_bytes(
+        read(PRICE_UPDATE_PUBLISH_TIME_OFFSET, 8)?
+            .try_into()
+            .unwrap(),
+    );
+
+    let oldest = now.saturating_sub(config.max_oracle_age);
+    if publish_time < oldest {
+        return Err(diagnose(
+            ErrorCode::StalePriceUpdate,
+            oldest as u64,
+            publish_time as u64,
+        ));
+    }
+    require!(price > 0, ErrorCode::InvalidPriceUpdate);
+    let price = price as u64;
+    require!(
+        conf as u128 * 10_000 <= price as u128 * config.max_oracle_conf_bps as u128,
+        ErrorCode::PriceConfidenceTooWide
+    );
+
+    // tokens = usd / (price * 10^exponent) * 10^decimals, all in integers
+    let overflow = || error!(ErrorCode::InvalidPriceUpdate);
+    let pow10 = |exp: u32| 10u128.checked_pow(exp).ok_or_else(overflow);
+    let mut numerator = (usd_price as u128)
+        .checked_mul(pow10(decimals as u32)?)
+        .ok_or_else(overflow)?;
+    let mut denominator = ((price - conf) as u128)
+        .checked_mul(pow10(USD_PRICE_DECIMALS)?)
+        .ok_or_else(overflow)?;
+    if exponent < 0 {
+        numerator = numerator
+            .checked_mul(pow10(exponent.unsigned_abs())?)
+            .ok_or_else(overflow)?;
+    } else {
+        denominator = denominator
+            .checked_mul(pow10(exponent as u32)?)
+            .ok_or_else(overflow)?;
+    }
+    u64::try_from(numerator.div_ceil(denominator)).map_err(|_| overflow())
+}
+
+/// Converts an LST amount into lamports using the issuer's on-chain rate.
+/// Only the supported mints are accepted, each pinned to its rate account.
+fn lst_value_in_lamports(
+    lst_mint: &Pubkey,
+    rate_account: &AccountInfo,
+    amount: u64,
+) -> Result<u64> {
+    let data = rate_account.try_borrow_data()?;
+    let read_u64 = |offset: usize| -> Result<u64> {
+        let bytes = data
+            .get(offset..offset + 8)
+            .ok_or(ErrorCode::InvalidRateAccount)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    };
+
+    let value = if *lst_mint == MSOL_MINT {
+        require_keys_eq!(
+            rate_account.key(),
+            MARINADE_STATE,
+            ErrorCode::InvalidRateAccount
+        );
+        let msol_price = read_u64(MARINADE_MSOL_PRICE_OFFSET)?;
+        (amount as u128)
+            .checked_mul(msol_price as u128)
+            .and_then(|value| value.checked_div(MARINADE_PRICE_DENOMINATOR))
+            .ok_or(ErrorCode::InvalidRateAccount)?
+    } else if *lst_mint == JITOSOL_MINT {
+        require_keys_eq!(
+            rate_account.key(),
+            JITO_STAKE_POOL,
+            ErrorCode::InvalidRateAccount
+        );
+        // A pool that hasn't been updated this epoch reports a stale rate
+        let last_update_epoch = read_u64(STAKE_POOL_LAST_UPDATE_EPOCH_OFFSET)?;
+        require!(
+            last_update_epoch == Clock::get()?.epoch,
+            ErrorCode::StaleExchangeRate
+        );
+        let total_lamports = read_u64(STAKE_POOL_TOTAL_LAMPORTS_OFFSET)?;
+        let pool_token_supply = read_u64(STAKE_POOL_TOKEN_SUPPLY_OFFSET)?;
+        require!(pool_token_supply > 0, ErrorCode::InvalidRateAccount);
+        (amount as u128)
+            .checked_mul(total_lamports as u128)
+            .and_then(|value| value.checked_div(pool_token_supply as u128))
+            .ok_or(ErrorCode::InvalidRateAccount)?
+    } else {
+        return err!(ErrorCode::UnsupportedLst);
+    };
+
+    u64::try_from(value).map_err(|_| error!(ErrorCode::InvalidRateAccount))
+}
+
+fn enforce_compliance(
+    config: &Config,
+    instructions: &AccountInfo,
+    payer: &Pubkey,
+    amount: u64,
+) -> Result<()> {
+    match config.compliance_mode {
+        ComplianceMode::Disabled => {}
+        ComplianceMode::Ed25519Attestation => {
+            let mut message = [0u8; 40];
+            message[..32].copy_from_slice(payer.as_ref());
+            message[32..].copy_from_slice(&amount.to_le_bytes());
+            verify_ed25519_attestation(instructions, &config.attestor, &message)?;
+        }
+        ComplianceMode::MemoAttestation => verify_memo_attestation(instructions, &config.attestor)?,
+    }
+    Ok(())
+}
+
+/// On instances that require it, the payer's attestation must come from the
+/// current jurisdiction attestor and not have expired. The account's seeds
+/// already tie it to this instance and payer.
+fn enforce_jurisdiction_attestation(
+    config: &Config,
+    attestation: Option<&Account<JurisdictionAttestation>>,
+    now: i64,
+) -> Result<()> {
+    if !config.require_jurisdiction_attestation {
+        return Ok(());
+    }
+    let attestation = attestation.ok_or(ErrorCode::MissingJurisdictionAttestation)?;
+    require!(
+        attestation.issuer == config.jurisdiction_attestor,
+        ErrorCode::InvalidJurisdictionAttestation
+    );
+    if attestation.expires_at <= now {
+        return Err(diagnose(
+            ErrorCode::JurisdictionAttestationExpired,
+            now as u64,
+            attestation.expires_at as u64,
+        ));
+    }
+    Ok(())
+}
+
+/// Rate-limits paid renewals per subscription to one every
+/// `min_payment_interval_slots`, then records this payment's slot
+fn enforce_payment_interval(config: &Config, subscription: &mut Subscription) -> Result<()> {
+    let slot = Clock::get()?.slot;
+    require!(
+        !subscription.rate_limited(config.min_payment_interval_slots, slot),
+        ErrorCode::PaymentRateLimited
+    );
+    subscription.last_payment_slot = slot;
+    Ok(())
+}
+
+/// Every routed payment covers the instance's `min_payment_amount`
+fn enforce_payment_amount(config: &Config, amount: u64) -> Result<()> {
+    if amount < config.min_payment_amount {
+        return Err(diagnose(
+            ErrorCode::PaymentBelowMinimum,
+            config.min_payment_amount,
+            amount,
+        ));
+    }
+    Ok(())
+}
+
+/// A reopened subscription starts without a last payment slot, so one stays
+/// open until its payment interval has run out
+fn enforce_interval_elapsed(config: &Config, subscription: &Subscription) -> Result<()> {
+    require!(
+        !subscription.rate_limited(config.min_payment_interval_slots, Clock::get()?.slot),
+        ErrorCode::PaymentRateLimited
+    );
+    Ok(())
+}
+
+/// Builds `error` after emitting a [`PaymentDiagnostic`] with the values the
+/// failed check compared, so integrators don't have to guess at the gap
+fn diagnose(error: ErrorCode, expected: u64, actual: u64) -> Error {
+    emit!(PaymentDiagnostic {
+        error_code: error.into(),
+        expected,
+        actual,
+    });
+    error.into()
+}
+
+/// Whose subscription a payment extends: the beneficiary's when it is a gift
+fn subscriber(user: &Signer, beneficiary: Option<&UncheckedAccount>) -> Pubkey {
+    beneficiary.map_or(user.key(), |beneficiary| beneficiary.key())
+}
+
+/// Mint, insurance vault and vault authority of instance #0; mainnet pins the
+/// SAKURA deployment and ignores the optional accounts
+#[cfg(not(any(feature = "devnet", feature = "localnet")))]
+fn instance_zero_vault(_accounts: &InitializeConfig) -> Result<(Pubkey, Pubkey, Pubkey)> {
+    Ok((
+        SAKURA_MINT,
+        PERCOLATOR_INSURANCE_VAULT,
+        PERCOLATOR_VAULT_AUTHORITY,
+    ))
+}
+
+/// Mint, insurance vault and vault authority of instance #0, read from the
+/// accounts on clusters without a canonical deployment
+#[cfg(any(feature = "devnet", feature = "localnet"))]
+fn instance_zero_vault(accounts: &InitializeConfig) -> Result<(Pubkey, Pubkey, Pubkey)> {
+    let mint = accounts
+        .mint
+        .as_ref()
+        .ok_or(ErrorCode::InstanceVaultRequired)?;
+    let insurance_vault = accounts
+        .insurance_vault
+        .as_ref()
+        .ok_or(ErrorCode::InstanceVaultRequired)?;
+    require_keys_eq!(
+        insurance_vault.mint,
+        mint.key(),
+        ErrorCode::InvalidVaultMint
+    );
+    require_keys_eq!(
+        *insurance_vault.to_account_info().owner,
+        *mint.to_account_info().owner,
+        ErrorCode::InvalidVaultOwner
+    );
+    Ok((mint.key(), insurance_vault.key(), insurance_vault.owner))
+}
+
+/// Logs an admin instruction by name, so every governance action shows up in
+/// the event stream
+fn emit_admin_action(config: &Account<Config>, action: &str) {
+    emit!(AdminAction {
+        config: config.key(),
+        admin: config.admin,
+        action: action.to_string(),
+    });
+}
+
+/// Logs a completed payment and the subscription change it bought, for
+/// indexers that subscribe to program events instead of parsing instructions
+#[allow(clippy::too_many_arguments)]
+fn emit_payment(
+    config: Pubkey,
+    user: Pubkey,
+    beneficiary: Pubkey,
+    amount: u64,
+    (insurance_amount, burn_amount, staking_amount, referral_amount): (u64, u64, u64, u64),
+    reference: Option<[u8; 32]>,
+    previous_state: SubscriptionState,
+    previous_expires_at: i64,
+    new_expires_at: i64,
+) {
+    emit!(PaymentProcessed {
+        config,
+        user,
+        beneficiary,
+        amount,
+        insurance_amount,
+        burn_amount,
+        staking_amount,
+        referral_amount,
+        reference,
+        new_expires_at,
+    });
+    emit!(SubscriptionExtended {
+        config,
+        user: beneficiary,
+        previous_state,
+        previous_expires_at,
+        new_expires_at,
+    });
+}
+
+/// The part of a `duration` payment on a subscription expiring at `expires_at`
+/// that fits under the instance's prepaid cap; zero `max_prepaid_seconds` is
+/// no cap. Past the cap the payment fails, or with `clamp_prepaid` buys only
+/// the time up to it. Expiry rounds up to whole days, so the time bought
+/// always stops at the last day boundary under the cap.
+fn prepaid_duration(config: &Config, expires_at: i64, duration: i64, now: i64) -> Result<i64> {
+    if config.max_prepaid_seconds == 0 {
+        return Ok(duration);
+    }
+    let base_time = std::cmp::max(now, expires_at);
+    let limit = now
+        .checked_add(config.max_prepaid_seconds)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let end = base_time
+        .checked_add(duration)
+        .ok_or(ErrorCode::MathOverflow)?;
+    require!(
+        end <= limit || config.clamp_prepaid,
+        ErrorCode::PrepaidLimitExceeded
+    );
+    let cap = limit - limit.rem_euclid(SECONDS_PER_DAY);
+    require!(base_time < cap, ErrorCode::PrepaidLimitExceeded);
+    Ok(end.min(cap) - base_time)
+}
+
+/// The checks a payment of `amount` passes before any funds move. Returns
+/// the price it has to cover: the signed quote's, or the plan's or effective
+/// price less `discount_bps` and then the promo code's discount. `oracle` is
+/// the Pyth price update and mint decimals a USD plan is converted with.
+#[allow(clippy::too_many_arguments)]
+fn check_payment(
+    config: &Account<Config>,
+    instructions: &AccountInfo,
+    payer: &Pubkey,
+    token_account: &AccountInfo,
+    attestation: Option<&Account<JurisdictionAttestation>>,
+    user_stats: Option<&UserStats>,
+    light_mode: bool,
+    quote: Option<&PriceQuote>,
+    plan: Option<&Plan>,
+    promo: Option<&Promo>,
+    oracle: Option<(&AccountInfo, u8)>,
+    discount_bps: u16,
+    amount: u64,
+    now: i64,
+) -> Result<u64> {
+    require!(amount > 0, ErrorCode::InvalidAmount);
+    enforce_payment_amount(config, amount)?;
+    require!(
+        !cpi_guard_enabled(token_account)?,
+        ErrorCode::CpiGuardEnabled
+    );
+    enforce_not_paused(config)?;
+    enforce_mint_cutover(config, now)?;
+
+    // Gated jurisdictions require an attestor co-signature on the payment
+    enforce_compliance(config, instructions, payer, amount)?;
+    enforce_jurisdiction_attestation(config, attestation, now)?;
+
+    // Quoted instances price each payment from an off-chain signed quote
+    let price = match quote {
+        Some(quote) => {
+            require!(!light_mode, ErrorCode::QuoteRequiresUserStats);
+            require!(plan.is_none(), ErrorCode::QuotedPlan);
+            require!(promo.is_none(), ErrorCode::QuotedPromo);
+            let last_nonce = user_stats.map_or(0, |stats| stats.last_quote_nonce);
+            verify_price_quote(config, instructions, payer, quote, last_nonce, now)?
+        }
+        None => {
+            require!(
+                config.quote_authority == Pubkey::default(),
+                ErrorCode::MissingQuote
+            );
+            let price = match plan {
+                Some(plan) => {
+                    require!(plan.active, ErrorCode::PlanInactive);
+                    if plan.usd_price > 0 {
+                        let (price_update, decimals) =
+                            oracle.ok_or(ErrorCode::MissingPriceUpdate)?;
+                        usd_to_tokens(
+                            config,
+                            &config.pyth_feed_id,
+                            price_update,
+                            decimals,
+                            plan.usd_price,
+                            now,
+                        )?
+                    } else {
+                        plan.price
+                    }
+                }
+                None => effective_price(config, now),
+            };
+            let price = apply_discount(price, discount_bps);
+            match promo {
+                Some(promo) => {
+                    if promo.expires_at <= now {
+                        return Err(diagnose(
+                            ErrorCode::PromoExpired,
+                            now as u64,
+                            promo.expires_at as u64,
+                        ));
+                    }
+                    require!(promo.remaining_uses > 0, ErrorCode::PromoExhausted);
+                    apply_discount(price, promo.discount_bps)
+                }
+                None => price,
+            }
+        }
+    };
+    if amount < price {
+        return Err(diagnose(ErrorCode::Underpayment, price, amount));
+    }
+    Ok(price)
+}
+
+/// Checks that the quote authority signed `quote` for this payer via the
+/// ed25519 program earlier in the transaction, with a nonce past `last_nonce`
+/// so it can't be replayed. Returns the quoted price.
+fn verify_price_quote(
+    config: &Account<Config>,
+    instructions: &AccountInfo,
+    payer: &Pubkey,
+    quote: &PriceQuote,
+    last_nonce: u64,
+    now: i64,
+) -> Result<u64> {
+    require!(
+        config.quote_authority != Pubkey::default(),
+        ErrorCode::QuotingDisabled
+    );
+    if quote.expires_at <= now {
+        return Err(diagnose(
+            ErrorCode::QuoteExpired,
+            now as u64,
+            quote.expires_at as u64,
+        ));
+    }
+    // Nonces only move forward per payer, so each quote pays at most once
+    if quote.nonce <= last_nonce {
+        return Err(diagnose(
+            ErrorCode::QuoteReplayed,
+            last_nonce.saturating_add(1),
+            quote.nonce,
+        ));
+    }
+
+    let message = quote.message(&config.key(), payer);
+    verify_ed25519_attestation(instructions, &config.quote_authority, &message)?;
+    Ok(quote.price)
+}
+
+/// Scans the instructions preceding the current one for a non-empty memo that
+/// lists `attestor` as a signer. The memo program fails the transaction unless
+/// every listed signer actually signed, so the attestor has co-signed this payment.
+fn verify_memo_attestation(instructions: &AccountInfo, attestor: &Pubkey) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)?;
+    for index in 0..current_index {
+        let ix = load_instruction_at_checked(index as usize, instructions)?;
+        let signed_by_attestor = ix
+            .accounts
+            .iter()
+            .any(|account| account.pubkey == *attestor && account.is_signer);
+        if ix.program_id == memo::ID && !ix.data.is_empty() && signed_by_attestor {
+            return Ok(());
+        }
+    }
+    err!(ErrorCode::MissingMemoAttestation)
+}
+
+/// Scans the instructions preceding the current one for a native ed25519
+/// verification of `message` signed by `signer`. The ed25519 program has already
+/// rejected the transaction if the signature itself is invalid, so only the
+/// signed content needs to be matched here.
+fn verify_ed25519_attestation(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)?;
+    for index in 0..current_index {
+        let ix = load_instruction_at_checked(index as usize, instructions)?;
+        if ed25519_ix_matches(&ix, signer, message) {
+            return Ok(());
+        }
+    }
+    err!(ErrorCode::MissingAttestation)
+}
+
+fn ed25519_ix_matches(ix: &Instruction, signer: &Pubkey, message: &[u8]) -> bool {
+    if ix.program_id != ed25519_program::ID || !ix.accounts.is_empty() {
+        return false;
+    }
+
+    let data = &ix.data;
+    // Exactly one signature, with its offsets header immediately after the count
+    if data.len() < ED25519_OFFSETS_START + ED25519_OFFSETS_LEN || data[0] != 1 {
+        return false;
+    }
+
+    let read_u16 = |at: usize| {
+        u16::from_le_bytes([
+            data[ED25519_OFFSETS_START + at],
+            data[ED25519_OFFSETS_START + at + 1],
+        ])
+    };
+    let signature_ix_index = read_u16(2);
+    let pubkey_offset = read_u16(4) as usize;
+    let pubkey_ix_index = read_u16(6);
+    let message_offset = read_u16(8) as usize;
+    let message_size = read_u16(10) as usize;
+    let message_ix_index = read_u16(12);
+
+    // All referenced data must live inside the ed25519 instruction itself,
+    // otherwise the verified bytes could differ from the ones we inspect
+    if signature_ix_index != u16::MAX || pubkey_ix_index != u16::MAX || message_ix_index != u16::MAX
+    {
+        return false;
+    }
+
+    let pubkey = match data.get(pubkey_offset..pubkey_offset + ED25519_PUBKEY_LEN) {
+        Some(pubkey) => pubkey,
+        None => return false,
+    };
+    let signed_message = match data.get(message_offset..message_offset + message_size) {
+        Some(signed_message) => signed_message,
+        None => return false,
+    };
+
+    pubkey == signer.as_ref() && signed_message == message
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + RouterRegistry::INIT_SPACE,
+        seeds = [b"registry"],
+        bump
+    )]
+    pub registry: Account<'info, RouterRegistry>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = Config::SPACE,
+        seeds = [b"config", SAKURA_INSTANCE_ID.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    // Only the upgrade authority may claim the admin role
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::SakuraFeeRouter>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    // Instance #0's mint and insurance vault on devnet and localnet; mainnet
+    // uses the SAKURA deployment and ignores them
+    pub mint: Option<InterfaceAccount<'info, token_interface::Mint>>,
+    pub insurance_vault: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint)]
+    pub insurance_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + PriceFeed::INIT_SPACE,
+        seeds = [b"price_feed", config.key().as_ref()],
+        bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateInstance<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(mut, seeds = [b"registry"], bump = registry.bump)]
+    pub registry: Account<'info, RouterRegistry>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = Config::SPACE,
+        seeds = [b"config", registry.instance_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    // Either token program; Token-2022 mints are paid through the interface CPIs
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        constraint = insurance_vault.mint == mint.key() @ ErrorCode::InvalidVaultMint,
+        owner = *mint.to_account_info().owner @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// `admin` is only ever checked as a signer, so it can be a multisig or
+// governance PDA signing through CPI as well as a keypair
+#[derive(Accounts)]
+pub struct AdminConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        constraint = config.pending_admin != Pubkey::default() @ ErrorCode::Unauthorized,
+        has_one = pending_admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct SetRoutingTable<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + RoutingTable::INIT_SPACE,
+        seeds = [b"routing_table", config.key().as_ref()],
+        bump
+    )]
+    pub routing_table: Account<'info, RoutingTable>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterSlab<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + SlabRegistry::INIT_SPACE,
+        seeds = [b"slab_registry", config.key().as_ref()],
+        bump
+    )]
+    pub slab_registry: Account<'info, SlabRegistry>,
+
+    #[account(constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint)]
+    pub insurance_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeactivateSlab<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"slab_registry", config.key().as_ref()],
+        bump = slab_registry.bump
+    )]
+    pub slab_registry: Account<'info, SlabRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct ClearRoutingTable<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"routing_table", config.key().as_ref()],
+        bump = routing_table.bump
+    )]
+    pub routing_table: Account<'info, RoutingTable>,
+}
+
+#[derive(Accounts)]
+pub struct SetPricing<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + PriceFeed::INIT_SPACE,
+        seeds = [b"price_feed", config.key().as_ref()],
+        bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshPriceFeed<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"price_feed", config.key().as_ref()],
+        bump = price_feed.bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+}
+
+#[cfg(feature = "test-mode")]
+#[derive(Accounts)]
+pub struct TestFaucet<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    // Its own mint authority, so anyone can draw from the faucet
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"test_mint"],
+        bump,
+        mint::decimals = 6,
+        mint::authority = test_mint
+    )]
+    pub test_mint: Account<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = test_mint,
+        associated_token::authority = user
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(feature = "test-mode")]
+#[derive(Accounts)]
+pub struct TestSetVault<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub insurance_vault: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction(kind: VaultKind)]
+pub struct CreateProgramVault<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA that owns the vault, it never holds data
+    #[account(seeds = [kind.seed(), config.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub mint: Account<'info, Mint>,
+
+    /// CHECK: created (or left untouched) by the associated token program
+    #[account(
+        mut,
+        address = get_associated_token_address(&vault_authority.key(), &mint.key())
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BurnPending<'info> {
+    pub operator: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = operator @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA that owns the vault, it never holds data
+    #[account(seeds = [VaultKind::PendingBurn.seed(), config.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault_authority
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(mut, address = config.mint @ ErrorCode::InvalidMint)]
+    pub mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetBurnBatching<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA that owns the vault, it never holds data
+    #[account(seeds = [VaultKind::PendingBurn.seed(), config.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = pending_burn_vault.owner == vault_authority.key() @ ErrorCode::InvalidVaultAuthority,
+        constraint = pending_burn_vault.mint == config.mint @ ErrorCode::InvalidVaultMint
+    )]
+    pub pending_burn_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct FlushBurn<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA that owns the vault, it never holds data
+    #[account(seeds = [VaultKind::PendingBurn.seed(), config.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut, address = config.pending_burn_vault @ ErrorCode::InvalidVault)]
+    pub pending_burn_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetInsuranceEscrow<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA that owns the escrow, it never holds data
+    #[account(seeds = [VaultKind::InsuranceEscrow.seed(), config.key().as_ref()], bump)]
+    pub escrow_authority: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = insurance_escrow.owner == escrow_authority.key() @ ErrorCode::InvalidVaultAuthority,
+        constraint = insurance_escrow.mint == config.mint @ ErrorCode::InvalidVaultMint
+    )]
+    pub insurance_escrow: InterfaceAccount<'info, token_interface::TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SweepInsuranceEscrow<'info> {
+    #[account(mut, seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA that owns the escrow, it never holds data
+    #[account(seeds = [VaultKind::InsuranceEscrow.seed(), config.key().as_ref()], bump)]
+    pub escrow_authority: UncheckedAccount<'info>,
+
+    #[account(mut, address = config.insurance_escrow @ ErrorCode::InvalidVault)]
+    pub insurance_escrow: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut, address = config.insurance_vault @ ErrorCode::InvalidVault)]
+    pub insurance_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        address = config.mint @ ErrorCode::InvalidMint,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct Refund<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA that owns the escrow, it never holds data
+    #[account(seeds = [VaultKind::InsuranceEscrow.seed(), config.key().as_ref()], bump)]
+    pub escrow_authority: UncheckedAccount<'info>,
+
+    #[account(mut, address = config.insurance_escrow @ ErrorCode::InvalidVault)]
+    pub insurance_escrow: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// CHECK: only used to derive the subscription PDA
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    // The payment being refunded, which extended `user`'s subscription
+    #[account(
+        mut,
+        constraint = receipt.config == config.key() @ ErrorCode::InvalidReceipt,
+        constraint = receipt.subscriber == user.key() @ ErrorCode::InvalidReceipt
+    )]
+    pub receipt: Account<'info, Receipt>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        address = config.mint @ ErrorCode::InvalidMint,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    // Queues a notice for the user's notifier when passed
+    #[account(mut, seeds = [b"outbox", config.key().as_ref()], bump)]
+    pub outbox: Option<AccountLoader<'info, Outbox>>,
+}
+
+#[derive(Accounts)]
+pub struct SweepTreasury<'info> {
+    pub operator: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = operator @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA that owns the treasury vaults, it never holds data
+    #[account(seeds = [VaultKind::Treasury.seed(), config.key().as_ref()], bump)]
+    pub treasury_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = treasury_vault.mint,
+        associated_token::authority = treasury_authority
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+
+    #[account(address = treasury_vault.mint @ ErrorCode::InvalidMint)]
+    pub mint: Account<'info, Mint>,
+
+    // Sweeps only ever land with governance, never with the operator key
+    #[account(
+        mut,
+        constraint = destination.owner == config.admin @ ErrorCode::InvalidOwner,
+        constraint = destination.mint == treasury_vault.mint @ ErrorCode::InvalidMint
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(incident_id: u64)]
+pub struct PublishIncident<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Incident::INIT_SPACE,
+        seeds = [b"incident", config.key().as_ref(), incident_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub incident: Account<'info, Incident>,
+
+    pub stable_mint: Account<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CompensateSubscriber<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"incident",
+            config.key().as_ref(),
+            incident.incident_id.to_le_bytes().as_ref()
+        ],
+        bump = incident.bump,
+        has_one = config,
+        has_one = stable_mint @ ErrorCode::InvalidMint
+    )]
+    pub incident: Box<Account<'info, Incident>>,
+
+    /// CHECK: the affected subscriber
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + CompensationClaim::INIT_SPACE,
+        seeds = [b"compensation", incident.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub compensation_claim: Account<'info, CompensationClaim>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// CHECK: PDA that owns the treasury vaults, it never holds data
+    #[account(seeds = [VaultKind::Treasury.seed(), config.key().as_ref()], bump)]
+    pub treasury_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = stable_mint,
+        associated_token::authority = treasury_authority
+    )]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+
+    pub stable_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        associated_token::mint = stable_mint,
+        associated_token::authority = user
+    )]
+    pub user_stable_account: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ScheduleMintMigration<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    // Either token program, as for `create_instance`
+    pub successor_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        constraint = successor_insurance_vault.mint == successor_mint.key() @ ErrorCode::InvalidVaultMint,
+        constraint = successor_insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = *successor_mint.to_account_info().owner @ ErrorCode::InvalidVaultOwner
+    )]
+    pub successor_insurance_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CompleteMintMigration<'info> {
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendSubscriptions<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Box<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateReceiptMint<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA acting as mint and metadata update authority, it never holds data
+    #[account(seeds = [b"mint_authority", config.key().as_ref()], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    // Name, symbol and image live on the mint itself via the metadata extension
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"receipt_mint", config.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = mint_authority,
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = mint_authority,
+        extensions::metadata_pointer::metadata_address = receipt_mint,
+    )]
+    pub receipt_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateSoulboundMint<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA acting as mint authority, permanent delegate and metadata
+    /// update authority, it never holds data
+    #[account(seeds = [b"mint_authority", config.key().as_ref()], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    /// CHECK: created and initialized as a non-transferable mint in the handler
+    #[account(mut, seeds = [b"soulbound_mint", config.key().as_ref()], bump)]
+    pub soulbound_mint: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeExpiredReceipt<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: only used to derive the holder's subscription and token account
+    pub holder: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"subscription", config.key().as_ref(), holder.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// CHECK: PDA acting as the soulbound mint's permanent delegate, it never holds data
+    #[account(seeds = [b"mint_authority", config.key().as_ref()], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"soulbound_mint", config.key().as_ref()],
+        bump,
+        mint::token_program = token_program
+    )]
+    pub soulbound_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = soulbound_mint,
+        associated_token::authority = holder,
+        associated_token::token_program = token_program
+    )]
+    pub soulbound_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct SetReceiptTree<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + ReceiptTree::INIT_SPACE,
+        seeds = [b"receipt_tree", config.key().as_ref()],
+        bump
+    )]
+    pub receipt_tree: Account<'info, ReceiptTree>,
+
+    /// CHECK: a Bubblegum tree; its delegate is only checked when minting
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(pool_id: u64)]
+pub struct CreateSponsorPool<'info> {
+    #[account(mut)]
+    pub sponsor: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = sponsor,
+        space = 8 + SponsorPool::INIT_SPACE,
+        seeds = [
+            b"sponsor_pool",
+            config.key().as_ref(),
+            sponsor.key().as_ref(),
+            pool_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub sponsor_pool: Account<'info, SponsorPool>,
+
+    #[account(
+        init,
+        payer = sponsor,
+        associated_token::mint = mint,
+        associated_token::authority = sponsor_pool
+    )]
+    pub pool_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = sponsor_token_account.owner == sponsor.key() @ ErrorCode::InvalidOwner,
+        constraint = sponsor_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub sponsor_token_account: Account<'info, TokenAccount>,
+
+    #[account(address = config.mint @ ErrorCode::InvalidMint)]
+    pub mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct AddSponsoredWallet<'info> {
+    #[account(mut)]
+    pub sponsor: Signer<'info>,
+
+    #[account(has_one = sponsor @ ErrorCode::Unauthorized)]
+    pub sponsor_pool: Account<'info, SponsorPool>,
+
+    #[account(
+        init,
+        payer = sponsor,
+        space = 8 + SponsorAllowance::INIT_SPACE,
+        seeds = [b"sponsor_allowance", sponsor_pool.key().as_ref(), wallet.as_ref()],
+        bump
+    )]
+    pub allowance: Account<'info, SponsorAllowance>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DrawSponsorship<'info> {
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"sponsor_pool",
+            config.key().as_ref(),
+            sponsor_pool.sponsor.as_ref(),
+            sponsor_pool.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = sponsor_pool.bump
+    )]
+    pub sponsor_pool: Account<'info, SponsorPool>,
+
+    #[account(
+        mut,
+        seeds = [b"sponsor_allowance", sponsor_pool.key().as_ref(), wallet.key().as_ref()],
+        bump = allowance.bump
+    )]
+    pub allowance: Account<'info, SponsorAllowance>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = sponsor_pool
+    )]
+    pub pool_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = wallet,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), wallet.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSponsorPool<'info> {
+    #[account(mut)]
+    pub sponsor: Signer<'info>,
+
+    #[account(
+        mut,
+        close = sponsor,
+        has_one = sponsor @ ErrorCode::Unauthorized
+    )]
+    pub sponsor_pool: Account<'info, SponsorPool>,
+
+    #[account(
+        mut,
+        associated_token::mint = pool_vault.mint,
+        associated_token::authority = sponsor_pool
+    )]
+    pub pool_vault: Account<'info, TokenAccount>,
+
+    #[account(address = pool_vault.mint @ ErrorCode::InvalidMint)]
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = sponsor_token_account.owner == sponsor.key() @ ErrorCode::InvalidOwner,
+        constraint = sponsor_token_account.mint == pool_vault.mint @ ErrorCode::InvalidMint
+    )]
+    pub sponsor_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct AssertSubscriptionActive<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: only used to derive the subscription being checked
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSubscription<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: only used to derive the subscription being revoked
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSubscription<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimSubscription<'info> {
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: the subscription's wallet, it only receives the remaining rent
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+#[derive(Accounts)]
+pub struct InitiateTransfer<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    // One pending transfer per wallet; cancel it to pick another recipient
+    #[account(
+        init,
+        payer = user,
+        space = 8 + SubscriptionTransfer::INIT_SPACE,
+        seeds = [b"subscription_transfer", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription_transfer: Account<'info, SubscriptionTransfer>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTransfer<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"subscription_transfer", config.key().as_ref(), user.key().as_ref()],
+        bump = subscription_transfer.bump
+    )]
+    pub subscription_transfer: Account<'info, SubscriptionTransfer>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptTransfer<'info> {
+    // Pays rent for the new subscription if it doesn't exist yet
+    #[account(mut)]
+    pub to: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: the old wallet; it only gets back the rent of what closes
+    #[account(mut)]
+    pub from: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = from,
+        seeds = [b"subscription_transfer", config.key().as_ref(), from.key().as_ref()],
+        bump = subscription_transfer.bump,
+        has_one = to @ ErrorCode::InvalidTransfer
+    )]
+    pub subscription_transfer: Account<'info, SubscriptionTransfer>,
+
+    #[account(
+        mut,
+        close = from,
+        seeds = [b"subscription", config.key().as_ref(), from.key().as_ref()],
+        bump
+    )]
+    pub from_subscription: Account<'info, Subscription>,
+
+    #[account(
+        init_if_needed,
+        payer = to,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), to.key().as_ref()],
+        bump
+    )]
+    pub to_subscription: Account<'info, Subscription>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminTransferSubscription<'info> {
+    // Pays rent for the new subscription if it doesn't exist yet
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: the old wallet; it gets back the old subscription's rent
+    #[account(mut)]
+    pub from: UncheckedAccount<'info>,
+
+    /// CHECK: any wallet; only used to derive the subscription it receives
+    pub to: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = from,
+        seeds = [b"subscription", config.key().as_ref(), from.key().as_ref()],
+        bump
+    )]
+    pub from_subscription: Account<'info, Subscription>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), to.key().as_ref()],
+        bump
+    )]
+    pub to_subscription: Account<'info, Subscription>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(collection: Pubkey)]
+pub struct SetNftDiscount<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + NftDiscount::INIT_SPACE,
+        seeds = [b"nft_discount", config.key().as_ref(), collection.as_ref()],
+        bump
+    )]
+    pub nft_discount: Account<'info, NftDiscount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(product_id: u64)]
+pub struct SetProduct<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + Product::INIT_SPACE,
+        seeds = [b"product", config.key().as_ref(), product_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub product: Account<'info, Product>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(plan_id: u64)]
+pub struct CreatePlan<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Plan::INIT_SPACE,
+        seeds = [b"plan", config.key().as_ref(), plan_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub plan: Account<'info, Plan>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(code_hash: [u8; 32])]
+pub struct CreatePromo<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Promo::INIT_SPACE,
+        seeds = [b"promo", config.key().as_ref(), code_hash.as_ref()],
+        bump
+    )]
+    pub promo: Account<'info, Promo>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPlanStatus<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"plan", config.key().as_ref(), plan.plan_id.to_le_bytes().as_ref()],
+        bump = plan.bump
+    )]
+    pub plan: Account<'info, Plan>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveNftDiscount<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"nft_discount", config.key().as_ref(), nft_discount.collection.as_ref()],
+        bump = nft_discount.bump,
+        has_one = config
+    )]
+    pub nft_discount: Account<'info, NftDiscount>,
+}
+
+#[derive(Accounts)]
+pub struct IssueAttestation<'info> {
+    #[account(mut)]
+    pub attestor: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        constraint = config.jurisdiction_attestor == attestor.key() @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: the wallet being attested
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = attestor,
+        space = 8 + JurisdictionAttestation::INIT_SPACE,
+        seeds = [b"attestation", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub attestation: Account<'info, JurisdictionAttestation>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAttestation<'info> {
+    #[account(mut)]
+    pub attestor: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        constraint = config.jurisdiction_attestor == attestor.key() @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: only used to derive the attestation being revoked
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = attestor,
+        seeds = [b"attestation", config.key().as_ref(), user.key().as_ref()],
+        bump = attestation.bump
+    )]
+    pub attestation: Account<'info, JurisdictionAttestation>,
+}
+
+#[derive(Accounts)]
+pub struct AssertLoyaltyTier<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: only used to derive the stats being checked
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"user_stats", config.key().as_ref(), user.key().as_ref()],
+        bump = user_stats.bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+}
+
+#[derive(Accounts)]
+#[instruction(badge_id: u8)]
+pub struct CreateBadge<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Badge::INIT_SPACE,
+        seeds = [b"badge", config.key().as_ref(), &[badge_id]],
+        bump
+    )]
+    pub badge: Account<'info, Badge>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimBadge<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [b"badge", config.key().as_ref(), &[badge.badge_id]],
+        bump = badge.bump,
+        has_one = config
+    )]
+    pub badge: Box<Account<'info, Badge>>,
+
+    #[account(
+        seeds = [b"user_stats", config.key().as_ref(), user.key().as_ref()],
+        bump = user_stats.bump
+    )]
+    pub user_stats: Box<Account<'info, UserStats>>,
+
+    /// CHECK: PDA acting as mint and metadata update authority, it never holds data
+    #[account(seeds = [b"mint_authority", config.key().as_ref()], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"badge_mint", badge.key().as_ref(), user.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = mint_authority,
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = mint_authority,
+        extensions::metadata_pointer::metadata_address = badge_mint,
+    )]
+    pub badge_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        init,
+        payer = user,
+        associated_token::mint = badge_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_badge_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateOrgAccount<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + OrgAccount::INIT_SPACE,
+        seeds = [b"org_account", config.key().as_ref(), creator.key().as_ref()],
+        bump
+    )]
+    pub org_account: Account<'info, OrgAccount>,
+
+    #[account(
+        init,
+        payer = creator,
+        associated_token::mint = mint,
+        associated_token::authority = org_account
+    )]
+    pub org_treasury: Account<'info, TokenAccount>,
+
+    #[account(address = config.mint @ ErrorCode::InvalidMint)]
+    pub mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateOrganization<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Organization::INIT_SPACE,
+        seeds = [b"organization", config.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub organization: Account<'info, Organization>,
+
+    // Prices every seat instead of the instance defaults
+    #[account(
+        seeds = [b"plan", config.key().as_ref(), plan.plan_id.to_le_bytes().as_ref()],
+        bump = plan.bump
+    )]
+    pub plan: Option<Account<'info, Plan>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessOrgPayment<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [b"organization", config.key().as_ref(), owner.key().as_ref()],
+        bump = organization.bump,
+        has_one = owner @ ErrorCode::Unauthorized
+    )]
+    pub organization: Account<'info, Organization>,
+
+    #[account(
+        seeds = [b"plan", config.key().as_ref(), plan.plan_id.to_le_bytes().as_ref()],
+        bump = plan.bump
+    )]
+    pub plan: Option<Account<'info, Plan>>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token_program.key() @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint,
+        mint::token_program = token_program
+    )]
+    pub mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"stats_shard", config.key().as_ref(), &[stats_shard_index(&owner.key())]],
+        bump = stats_shard.bump
+    )]
+    pub stats_shard: Account<'info, StatsShard>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AddMember<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"organization", config.key().as_ref(), owner.key().as_ref()],
+        bump = organization.bump,
+        has_one = owner @ ErrorCode::Unauthorized
+    )]
+    pub organization: Account<'info, Organization>,
+
+    /// CHECK: any wallet; only used to derive its seat
+    pub member: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Seat::INIT_SPACE,
+        seeds = [b"seat", organization.key().as_ref(), member.key().as_ref()],
+        bump
+    )]
+    pub seat: Account<'info, Seat>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveMember<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"organization", config.key().as_ref(), owner.key().as_ref()],
+        bump = organization.bump,
+        has_one = owner @ ErrorCode::Unauthorized
+    )]
+    pub organization: Account<'info, Organization>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"seat", organization.key().as_ref(), seat.member.as_ref()],
+        bump = seat.bump
+    )]
+    pub seat: Account<'info, Seat>,
+}
+
+#[derive(Accounts)]
+pub struct AssertSeatActive<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [b"organization", config.key().as_ref(), organization.owner.as_ref()],
+        bump = organization.bump
+    )]
+    pub organization: Account<'info, Organization>,
+
+    /// CHECK: only used to derive the seat being checked
+    pub member: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"seat", organization.key().as_ref(), member.key().as_ref()],
+        bump = seat.bump
+    )]
+    pub seat: Account<'info, Seat>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessCosignedPayment<'info> {
+    // Pays rent for a new subscription
+    #[account(mut)]
+    pub first_signer: Signer<'info>,
+    pub second_signer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        seeds = [b"org_account", config.key().as_ref(), org_account.creator.as_ref()],
+        bump = org_account.bump,
+        has_one = first_signer @ ErrorCode::Unauthorized,
+        has_one = second_signer @ ErrorCode::Unauthorized
+    )]
+    pub org_account: Account<'info, OrgAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = org_account
+    )]
+    pub org_treasury: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: any wallet; only used to derive the subscription it receives
+    pub beneficiary: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = first_signer,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), beneficiary.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"stats_shard", config.key().as_ref(), &[stats_shard_index(&org_account.key())]],
+        bump = stats_shard.bump
+    )]
+    pub stats_shard: Account<'info, StatsShard>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawOrgTreasury<'info> {
+    pub first_signer: Signer<'info>,
+    pub second_signer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [b"org_account", config.key().as_ref(), org_account.creator.as_ref()],
+        bump = org_account.bump,
+        has_one = first_signer @ ErrorCode::Unauthorized,
+        has_one = second_signer @ ErrorCode::Unauthorized
+    )]
+    pub org_account: Account<'info, OrgAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = org_account
+    )]
+    pub org_treasury: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = mint)]
+    pub destination: Account<'info, TokenAccount>,
+
+    #[account(address = config.mint @ ErrorCode::InvalidMint)]
+    pub mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: u64)]
+pub struct RegisterService<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Service::INIT_SPACE,
+        seeds = [b"service", config.key().as_ref(), service_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessServicePayment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        seeds = [b"service", config.key().as_ref(), service.service_id.to_le_bytes().as_ref()],
+        bump = service.bump
+    )]
+    pub service: Account<'info, Service>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    // Scoped to the service, separate from the router-wide subscription
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"service_subscription", service.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(product_id: u64)]
+pub struct PurchaseProduct<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        seeds = [b"product", config.key().as_ref(), product_id.to_le_bytes().as_ref()],
+        bump = product.bump
+    )]
+    pub product: Account<'info, Product>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    // The router-wide subscription; required for subscription and add-on products
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Option<Account<'info, Subscription>>,
+
+    // Scoped to the product; required for add-ons
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"product_subscription", product.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub entitlement: Option<Account<'info, Subscription>>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(invoice_id: u64)]
+pub struct CreateInvoice<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"service", service.config.as_ref(), service.service_id.to_le_bytes().as_ref()],
+        bump = service.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub service: Account<'info, Service>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Invoice::INIT_SPACE,
+        seeds = [b"invoice", service.key().as_ref(), invoice_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub invoice: Account<'info, Invoice>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PayInvoice<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        constraint = service.config == config.key() @ ErrorCode::InvalidInvoice
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        seeds = [b"service", config.key().as_ref(), service.service_id.to_le_bytes().as_ref()],
+        bump = service.bump
+    )]
+    pub service: Account<'info, Service>,
+
+    #[account(
+        mut,
+        seeds = [b"invoice", service.key().as_ref(), invoice.invoice_id.to_le_bytes().as_ref()],
+        bump = invoice.bump,
+        has_one = service @ ErrorCode::InvalidInvoice,
+        has_one = payer @ ErrorCode::Unauthorized
+    )]
+    pub invoice: Account<'info, Invoice>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.owner == payer.key() @ ErrorCode::InvalidOwner,
+        constraint = payer_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub payer_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelInvoice<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"service", service.config.as_ref(), service.service_id.to_le_bytes().as_ref()],
+        bump = service.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub service: Account<'info, Service>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"invoice", service.key().as_ref(), invoice.invoice_id.to_le_bytes().as_ref()],
+        bump = invoice.bump,
+        has_one = service @ ErrorCode::InvalidInvoice
+    )]
+    pub invoice: Account<'info, Invoice>,
+}
+
+#[derive(Accounts)]
+pub struct CreateGiftCards<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + GiftCard::INIT_SPACE,
+        seeds = [b"gift_card", config.key().as_ref()],
+        bump
+    )]
+    pub gift_card: Account<'info, GiftCard>,
+
+    /// CHECK: PDA acting as mint authority, it never holds data
+    #[account(seeds = [b"mint_authority", config.key().as_ref()], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"gift_card_mint", config.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = mint_authority
+    )]
+    pub gift_card_mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PurchaseGiftCards<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        seeds = [b"gift_card", config.key().as_ref()],
+        bump = gift_card.bump,
+        has_one = config
+    )]
+    pub gift_card: Account<'info, GiftCard>,
+
+    #[account(mut, address = gift_card.mint @ ErrorCode::InvalidMint)]
+    pub gift_card_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = gift_card_mint,
+        associated_token::authority = buyer
+    )]
+    pub buyer_card_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: PDA acting as mint authority, it never holds data
+    #[account(seeds = [b"mint_authority", config.key().as_ref()], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == buyer.key() @ ErrorCode::InvalidOwner,
+        constraint = buyer_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub buyer_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemGiftCards<'info> {
+    #[account(mut)]
+    pub holder: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [b"gift_card", config.key().as_ref()],
+        bump = gift_card.bump,
+        has_one = config
+    )]
+    pub gift_card: Account<'info, GiftCard>,
+
+    #[account(mut, address = gift_card.mint @ ErrorCode::InvalidMint)]
+    pub gift_card_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = gift_card_mint,
+        token::authority = holder
+    )]
+    pub holder_card_account: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = holder,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), holder.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(batch_id: u64)]
+pub struct PurchasePasses<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + PassBatch::INIT_SPACE,
+        seeds = [
+            b"pass_batch",
+            config.key().as_ref(),
+            buyer.key().as_ref(),
+            batch_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub pass_batch: Account<'info, PassBatch>,
+
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == buyer.key() @ ErrorCode::InvalidOwner,
+        constraint = buyer_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub buyer_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AssignPass<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pass_batch",
+            config.key().as_ref(),
+            buyer.key().as_ref(),
+            pass_batch.batch_id.to_le_bytes().as_ref()
+        ],
+        bump = pass_batch.bump,
+        has_one = config,
+        has_one = buyer @ ErrorCode::Unauthorized
+    )]
+    pub pass_batch: Account<'info, PassBatch>,
+
+    /// CHECK: any wallet can receive a pass
+    pub recipient: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), recipient.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterReferrer<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Referrer::INIT_SPACE,
+        seeds = [b"referrer", config.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub referrer: Account<'info, Referrer>,
+
+    #[account(
+        constraint = token_account.owner == authority.key() @ ErrorCode::InvalidOwner,
+        constraint = token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ListSubscription<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", config.key().as_ref(), seller.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        init,
+        payer = seller,
+        space = 8 + Listing::INIT_SPACE,
+        seeds = [b"listing", config.key().as_ref(), seller.key().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, Listing>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", config.key().as_ref(), seller.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        close = seller,
+        seeds = [b"listing", config.key().as_ref(), seller.key().as_ref()],
+        bump = listing.bump
+    )]
+    pub listing: Account<'info, Listing>,
+}
+
+#[derive(Accounts)]
+pub struct BuyListing<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: receives the listing rent; matched against the listing
+    #[account(mut)]
+    pub seller: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = seller,
+        seeds = [b"listing", config.key().as_ref(), seller.key().as_ref()],
+        bump = listing.bump,
+        has_one = seller @ ErrorCode::InvalidListing
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == buyer.key() @ ErrorCode::InvalidOwner,
+        constraint = buyer_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub buyer_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner,
+        constraint = seller_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub seller_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitStats<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Stats::INIT_SPACE,
+        seeds = [b"stats", config.key().as_ref()],
+        bump
+    )]
+    pub stats: Account<'info, Stats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(index: u8)]
+pub struct InitStatsShard<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + StatsShard::INIT_SPACE,
+        seeds = [b"stats_shard", config.key().as_ref(), &[index]],
+        bump
+    )]
+    pub stats_shard: Account<'info, StatsShard>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AggregateStats<'info> {
+    // Writable for the bonding-curve discount, derived from the totals below
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"stats", config.key().as_ref()],
+        bump = stats.bump
+    )]
+    pub stats: Account<'info, Stats>,
+}
+
+#[derive(Accounts)]
+#[instruction(kind: u8, page: u8)]
+pub struct InitRegistryPage<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<RegistryPage>(),
+        seeds = [b"registry", config.key().as_ref(), &[kind], &[page]],
+        bump
+    )]
+    pub registry_page: AccountLoader<'info, RegistryPage>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRegistryPage<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(mut, constraint = registry_page.load()?.config == config.key() @ ErrorCode::InvalidRegistryPage)]
+    pub registry_page: AccountLoader<'info, RegistryPage>,
+}
+
+#[derive(Accounts)]
+pub struct InitReceiptHistory<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ReceiptHistory::INIT_SPACE,
+        seeds = [b"receipt_history", config.key().as_ref()],
+        bump
+    )]
+    pub receipt_history: Box<Account<'info, ReceiptHistory>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitOutbox<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<Outbox>(),
+        seeds = [b"outbox", config.key().as_ref()],
+        bump
+    )]
+    pub outbox: AccountLoader<'info, Outbox>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EnqueueExpiryNotice<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(mut, seeds = [b"outbox", config.key().as_ref()], bump)]
+    pub outbox: AccountLoader<'info, Outbox>,
+
+    /// CHECK: only used to derive the subscription PDA
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+#[derive(Accounts)]
+pub struct AckOutbox<'info> {
+    pub operator: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = operator @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(mut, seeds = [b"outbox", config.key().as_ref()], bump)]
+    pub outbox: AccountLoader<'info, Outbox>,
+}
+
+#[derive(Accounts)]
+pub struct ArchiveReceipts<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [b"receipt_history", config.key().as_ref()],
+        bump = receipt_history.bump
+    )]
+    pub receipt_history: Box<Account<'info, ReceiptHistory>>,
+
+    /// CHECK: PDA that owns the treasury vaults, it never holds data
+    #[account(mut, seeds = [VaultKind::Treasury.seed(), config.key().as_ref()], bump)]
+    pub treasury_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SweepReclaimedRent<'info> {
+    pub operator: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = operator @ ErrorCode::Unauthorized,
+        has_one = admin @ ErrorCode::InvalidOwner
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: PDA that owns the treasury vaults, it never holds data
+    #[account(mut, seeds = [VaultKind::Treasury.seed(), config.key().as_ref()], bump)]
+    pub treasury_authority: UncheckedAccount<'info>,
+
+    /// CHECK: pinned to the instance admin, only receives lamports
+    #[account(mut)]
+    pub admin: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateSubscription<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: must match the owner recorded in the legacy account, receives the freed rent
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+
+    /// CHECK: legacy or short packed layout, checked and rewritten by the handler
+    #[account(
+        mut,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        owner = crate::ID
+    )]
+    pub subscription: UncheckedAccount<'info>,
+
+    // Only needed to fund growing a packed account to the reserved size
+    #[account(mut)]
+    pub payer: Option<Signer<'info>>,
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: may be too short to deserialize; discriminator, address and
+    /// admin are checked by the handler
+    #[account(mut, owner = crate::ID)]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessLstPayment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    pub lst_mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: pinned per mint to the Marinade state or SPL stake pool in the handler
+    pub rate_account: UncheckedAccount<'info>,
+
+    /// CHECK: a Pyth price update, layout and feed checked against the
+    /// instance's SOL/USD feed when pricing the period
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceUpdate)]
+    pub sol_price_update: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = user_lst_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_lst_account.mint == lst_mint.key() @ ErrorCode::InvalidMint
+    )]
+    pub user_lst_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: PDA that owns the treasury vault, it never holds data
+    #[account(seeds = [VaultKind::Treasury.seed(), config.key().as_ref()], bump)]
+    pub treasury_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        address = get_associated_token_address(&treasury_authority.key(), &lst_mint.key()) @ ErrorCode::InvalidVault
+    )]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetBurnMode<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    // Required when splitting with staking
+    #[account(constraint = staking_vault.mint == config.mint @ ErrorCode::InvalidVaultMint)]
+    pub staking_vault: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct SetSolPayments<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(constraint = sol_insurance_vault.mint == token::spl_token::native_mint::ID @ ErrorCode::InvalidVaultMint)]
+    pub sol_insurance_vault: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessPaymentSol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(address = token::spl_token::native_mint::ID @ ErrorCode::InvalidMint)]
+    pub native_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        constraint = user_sol_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_sol_account.mint == native_mint.key() @ ErrorCode::InvalidMint
+    )]
+    pub user_sol_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, address = config.sol_insurance_vault @ ErrorCode::InvalidVault)]
+    pub sol_insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: PDA that owns the buyback escrow, it never holds data
+    #[account(seeds = [VaultKind::Buyback.seed(), config.key().as_ref()], bump)]
+    pub buyback_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        address = get_associated_token_address(&buyback_authority.key(), &native_mint.key()) @ ErrorCode::InvalidVault
+    )]
+    pub buyback_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    // Only required outside light mode
+    #[account(
+        mut,
+        seeds = [b"stats_shard", config.key().as_ref(), &[stats_shard_index(&user.key())]],
+        bump = stats_shard.bump
+    )]
+    pub stats_shard: Option<Account<'info, StatsShard>>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteBuybackBurn<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: PDA that owns both buyback vaults, it never holds data
+    #[account(seeds = [VaultKind::Buyback.seed(), config.key().as_ref()], bump)]
+    pub buyback_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        address = get_associated_token_address(&buyback_authority.key(), &token::spl_token::native_mint::ID) @ ErrorCode::InvalidVault
+    )]
+    pub buyback_sol_vault: Box<Account<'info, TokenAccount>>,
+
+    // Receives the swap's SAKURA, which is burned straight back out
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = buyback_authority
+    )]
+    pub buyback_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, address = config.mint @ ErrorCode::InvalidMint)]
+    pub mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"stats_shard", config.key().as_ref(), &[stats_shard.index]],
+        bump = stats_shard.bump
+    )]
+    pub stats_shard: Account<'info, StatsShard>,
+
+    /// CHECK: any program the admin routes the swap through; the handler
+    /// bounds what the swap may spend and must return
+    #[account(executable)]
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessDelegatedPayment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// CHECK: pinned to the instance vault; mint and authority are read in place
+    /// instead of deserializing the whole account on the hot path
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = token_account_field(&insurance_vault, TOKEN_ACCOUNT_MINT_OFFSET) == config.mint @ ErrorCode::InvalidVaultMint,
+        // The TokenAccount.owner field represents the SPL token authority over the vault
+        constraint = config.escrows_insurance() || token_account_field(&insurance_vault, TOKEN_ACCOUNT_OWNER_OFFSET) == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        // The token program natively owns the token accounts
+        owner = token_program.key() @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: UncheckedAccount<'info>,
+
+    /// CHECK: pinned to the instance mint; only its decimals are read. Only
+    /// required when part of the payment is burned.
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint,
+        owner = token_program.key() @ ErrorCode::InvalidMint
+    )]
+    pub mint: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: PDA the user approved as delegate, it never holds data
+    #[account(seeds = [b"pay_delegate", config.key().as_ref()], bump)]
+    pub delegate: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"stats_shard", config.key().as_ref(), &[stats_shard_index(&user.key())]],
+        bump = stats_shard.bump
+    )]
+    // Only required outside light mode
+    pub stats_shard: Option<Account<'info, StatsShard>>,
+
+    // Only required outside light mode
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserStats::INIT_SPACE,
+        seeds = [b"user_stats", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Option<Account<'info, UserStats>>,
+
+    // Only required when the instance requires jurisdiction attestations
+    #[account(
+        seeds = [b"attestation", config.key().as_ref(), user.key().as_ref()],
+        bump = attestation.bump
+    )]
+    pub attestation: Option<Account<'info, JurisdictionAttestation>>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessPaymentViaCpi<'info> {
+    // Pays rent for a new subscription or user stats
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // The caller program's PDA, checked against `caller_program` in the handler
+    pub caller_authority: Signer<'info>,
+
+    /// CHECK: only its address is used, to derive `caller_authority`
+    #[account(
+        executable,
+        constraint = config.allows_cpi_caller(&caller_program.key()) @ ErrorCode::InvalidCpiCaller
+    )]
+    pub caller_program: UncheckedAccount<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: the end user credited with the subscription; any wallet
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = caller_token_account.owner == caller_authority.key() @ ErrorCode::InvalidOwner,
+        constraint = caller_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub caller_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// CHECK: pinned to the instance vault like in `ProcessPayment`
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = token_account_field(&insurance_vault, TOKEN_ACCOUNT_MINT_OFFSET) == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || token_account_field(&insurance_vault, TOKEN_ACCOUNT_OWNER_OFFSET) == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token_program.key() @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: UncheckedAccount<'info>,
+
+    /// CHECK: pinned to the instance mint; only its decimals are read. Only
+    /// required when part of the payment is burned.
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint,
+        owner = token_program.key() @ ErrorCode::InvalidMint
+    )]
+    pub mint: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"stats_shard", config.key().as_ref(), &[stats_shard_index(&user.key())]],
+        bump = stats_shard.bump
+    )]
+    // Only required outside light mode
+    pub stats_shard: Option<Account<'info, StatsShard>>,
+
+    // Only required outside light mode
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + UserStats::INIT_SPACE,
+        seeds = [b"user_stats", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Option<Account<'info, UserStats>>,
+
+    // Only required when the instance requires jurisdiction attestations
+    #[account(
+        seeds = [b"attestation", config.key().as_ref(), user.key().as_ref()],
+        bump = attestation.bump
+    )]
+    pub attestation: Option<Account<'info, JurisdictionAttestation>>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ValidatePayment<'info> {
+    /// CHECK: not a signer, so frontends can simulate before asking for a signature
+    pub user: UncheckedAccount<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        constraint = user_token_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// CHECK: pinned to the instance vault like in `ProcessPayment`
+    #[account(
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = token_account_field(&insurance_vault, TOKEN_ACCOUNT_MINT_OFFSET) == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || token_account_field(&insurance_vault, TOKEN_ACCOUNT_OWNER_OFFSET) == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token_program.key() @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: UncheckedAccount<'info>,
+
+    /// CHECK: pinned to the instance mint; required when part of the payment is burned
+    #[account(
+        address = config.mint @ ErrorCode::InvalidMint,
+        owner = token_program.key() @ ErrorCode::InvalidMint
+    )]
+    pub mint: Option<UncheckedAccount<'info>>,
+
+    // Omitted for first-time payers
+    #[account(
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Option<Account<'info, Subscription>>,
+
+    // Omitted for first-time payers; a quote's nonce is checked against it
+    #[account(
+        seeds = [b"user_stats", config.key().as_ref(), user.key().as_ref()],
+        bump = user_stats.bump
+    )]
+    pub user_stats: Option<Account<'info, UserStats>>,
+
+    // Only required when the instance requires jurisdiction attestations
+    #[account(
+        seeds = [b"attestation", config.key().as_ref(), user.key().as_ref()],
+        bump = attestation.bump
+    )]
+    pub attestation: Option<Account<'info, JurisdictionAttestation>>,
+
+    // Prices and times the payment by the plan instead of the instance defaults
+    #[account(
+        seeds = [b"plan", config.key().as_ref(), plan.plan_id.to_le_bytes().as_ref()],
+        bump = plan.bump
+    )]
+    pub plan: Option<Account<'info, Plan>>,
+
+    #[account(
+        seeds = [b"promo", config.key().as_ref(), promo.code_hash.as_ref()],
+        bump = promo.bump
+    )]
+    pub promo: Option<Box<Account<'info, Promo>>>,
+
+    /// CHECK: a Pyth price update, layout and feed checked when pricing a USD
+    /// plan; the mint has to be passed with it
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceUpdate)]
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    // Only for payers claiming an NFT-collection discount; all three or none
+    #[account(
+        seeds = [b"nft_discount", config.key().as_ref(), nft_discount.collection.as_ref()],
+        bump = nft_discount.bump
+    )]
+    pub nft_discount: Option<Account<'info, NftDiscount>>,
+    /// CHECK: the payer's token account holding the NFT, read in place
+    pub nft_token_account: Option<UncheckedAccount<'info>>,
+    /// CHECK: the NFT's Metaplex metadata, owner and layout checked in the handler
+    pub nft_metadata: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    amount: u64,
+    memo: Option<String>,
+    light_mode: bool,
+    quote: Option<PriceQuote>,
+    slab_id: Option<u64>,
+    reference: Option<[u8; 32]>
+)]
+pub struct ProcessPayment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// CHECK: pinned to the instance vault or its escrow, or to the slab's
+    /// registered vault in the handler; mint and authority are read in place instead of
+    /// deserializing the whole account on the hot path
+    #[account(
+        mut,
+        constraint = slab_registry.is_some() || insurance_vault.key() == config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = token_account_field(&insurance_vault, TOKEN_ACCOUNT_MINT_OFFSET) == config.mint @ ErrorCode::InvalidVaultMint,
+        // The TokenAccount.owner field represents the SPL token authority over the
+        // vault; each slab vault has its own
+        constraint = slab_registry.is_some() || config.escrows_insurance() || token_account_field(&insurance_vault, TOKEN_ACCOUNT_OWNER_OFFSET) == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        // The token program natively owns the token accounts
+        owner = token_program.key() @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: UncheckedAccount<'info>,
+
+    // Required with a `slab_id`
+    #[account(
+        seeds = [b"slab_registry", config.key().as_ref()],
+        bump = slab_registry.bump
+    )]
+    pub slab_registry: Option<Box<Account<'info, SlabRegistry>>>,
+
+    /// CHECK: pinned to the instance mint; only its decimals are read. Only
+    /// required when part of the payment is burned.
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint,
+        owner = token_program.key() @ ErrorCode::InvalidMint
+    )]
+    pub mint: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: pinned to the instance's pending-burn vault, required while it
+    /// batches burns; the burn share is parked here instead of burned
+    #[account(mut, address = config.pending_burn_vault @ ErrorCode::InvalidVault)]
+    pub pending_burn_vault: Option<UncheckedAccount<'info>>,
+
+    // Required while the instance routes through its routing table; the
+    // non-burn destinations follow as remaining accounts
+    #[account(
+        seeds = [b"routing_table", config.key().as_ref()],
+        bump = routing_table.bump
+    )]
+    pub routing_table: Option<Box<Account<'info, RoutingTable>>>,
+
+    /// CHECK: any wallet; gifts the period to its subscription instead of the payer's
+    pub beneficiary: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [
+            b"subscription",
+            config.key().as_ref(),
+            subscriber(&user, beneficiary.as_ref()).as_ref()
+        ],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"stats_shard", config.key().as_ref(), &[stats_shard_index(&user.key())]],
+        bump = stats_shard.bump
+    )]
+    // Only required outside light mode
+    pub stats_shard: Option<Account<'info, StatsShard>>,
+
+    // Only required outside light mode
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserStats::INIT_SPACE,
+        seeds = [b"user_stats", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Option<Account<'info, UserStats>>,
+
+    // Only required when the instance requires jurisdiction attestations
+    #[account(
+        seeds = [b"attestation", config.key().as_ref(), user.key().as_ref()],
+        bump = attestation.bump
+    )]
+    pub attestation: Option<Account<'info, JurisdictionAttestation>>,
+
+    // Prices and times the payment by the plan instead of the instance defaults
+    #[account(
+        seeds = [b"plan", config.key().as_ref(), plan.plan_id.to_le_bytes().as_ref()],
+        bump = plan.bump
+    )]
+    pub plan: Option<Account<'info, Plan>>,
+
+    // Redeems one use of a discount code
+    #[account(
+        mut,
+        seeds = [b"promo", config.key().as_ref(), promo.code_hash.as_ref()],
+        bump = promo.bump
+    )]
+    pub promo: Option<Box<Account<'info, Promo>>>,
+
+    /// CHECK: a Pyth price update, layout and feed checked when pricing a USD
+    /// plan; the mint has to be passed with it
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceUpdate)]
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    // Only for payers claiming an NFT-collection discount; all three or none
+    #[account(
+        seeds = [b"nft_discount", config.key().as_ref(), nft_discount.collection.as_ref()],
+        bump = nft_discount.bump
+    )]
+    pub nft_discount: Option<Account<'info, NftDiscount>>,
+    /// CHECK: the payer's token account holding the NFT, read in place
+    pub nft_token_account: Option<UncheckedAccount<'info>>,
+    /// CHECK: the NFT's Metaplex metadata, owner and layout checked in the handler
+    pub nft_metadata: Option<UncheckedAccount<'info>>,
+
+    // Only for referred payments; both or neither
+    #[account(
+        mut,
+        seeds = [b"referrer", config.key().as_ref(), referrer.authority.as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Option<Box<Account<'info, Referrer>>>,
+    /// CHECK: matched against the referrer's registered account in the handler
+    #[account(mut)]
+    pub referrer_token_account: Option<UncheckedAccount<'info>>,
+
+    // Seeded by the payer's lifetime payment count, which unlike the
+    // subscription survives a close, so receipts never collide and can be
+    // derived without indexing
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Receipt::INIT_SPACE,
+        seeds = [
+            b"receipt",
+            config.key().as_ref(),
+            user.key().as_ref(),
+            user_stats.as_ref().map_or(0, |stats| stats.payments).to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub receipt: Option<Account<'info, Receipt>>,
+
+    // Required with a `reference`; one per reference and payer
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PaymentReference::INIT_SPACE,
+        seeds = [
+            b"payment_reference",
+            config.key().as_ref(),
+            user.key().as_ref(),
+            reference.unwrap_or_default().as_ref()
+        ],
+        bump
+    )]
+    pub payment_reference: Option<Box<Account<'info, PaymentReference>>>,
+
+    /// CHECK: pinned to the staking vault `set_burn_mode` validated; required
+    /// while the burn mode splits with staking
+    #[account(mut, address = config.staking_vault @ ErrorCode::InvalidVault)]
+    pub staking_vault: Option<UncheckedAccount<'info>>,
+
+    // Compressed receipts, used when no receipt account is passed; all or none
+    #[account(
+        mut,
+        seeds = [b"receipt_tree", config.key().as_ref()],
+        bump = receipt_tree.bump
+    )]
+    pub receipt_tree: Option<Box<Account<'info, ReceiptTree>>>,
+    /// CHECK: matched against the receipt tree in the handler
+    #[account(mut)]
+    pub merkle_tree: Option<UncheckedAccount<'info>>,
+    /// CHECK: Bubblegum's tree config, checked by Bubblegum
+    #[account(mut)]
+    pub tree_config: Option<UncheckedAccount<'info>>,
+    /// CHECK: PDA set as the tree delegate, it never holds data
+    #[account(seeds = [b"mint_authority", config.key().as_ref()], bump)]
+    pub tree_delegate: Option<UncheckedAccount<'info>>,
+    /// CHECK: address is checked against the Bubblegum program id
+    pub bubblegum_program: Option<UncheckedAccount<'info>>,
+    /// CHECK: the noop program the tree logs through, checked by Bubblegum
+    pub log_wrapper: Option<UncheckedAccount<'info>>,
+    /// CHECK: the tree's compression program, checked by Bubblegum
+    pub compression_program: Option<UncheckedAccount<'info>>,
+
+    // Mints the subscriber a soulbound receipt token unless they hold one; all or none
+    /// CHECK: owned by Token-2022 once the admin has created it
+    #[account(
+        mut,
+        seeds = [b"soulbound_mint", config.key().as_ref()],
+        bump,
+        owner = token_2022::ID @ ErrorCode::MissingSoulboundAccounts
+    )]
+    pub soulbound_mint: Option<UncheckedAccount<'info>>,
+    /// CHECK: matched against the subscriber's associated account in the handler
+    #[account(mut)]
+    pub soulbound_account: Option<UncheckedAccount<'info>>,
+    /// CHECK: PDA acting as the soulbound mint authority, it never holds data
+    #[account(seeds = [b"mint_authority", config.key().as_ref()], bump)]
+    pub soulbound_authority: Option<UncheckedAccount<'info>>,
+    pub token_2022_program: Option<Program<'info, Token2022>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+
+    /// CHECK: address is checked against the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    // Only required when a payment memo is supplied
+    pub memo_program: Option<Program<'info, Memo>>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterStreamFunding<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: only the owning program is validated, the stream layout is Streamflow's
+    #[account(owner = STREAMFLOW_PROGRAM_ID @ ErrorCode::InvalidStream)]
+    pub stream: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + StreamFunding::INIT_SPACE,
+        seeds = [b"stream_funding", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub stream_funding: Account<'info, StreamFunding>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: PDA used only as the token delegate, it never holds data
+    #[account(seeds = [b"stream_delegate", config.key().as_ref()], bump)]
+    pub delegate: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CrankStreamFunding<'info> {
+    // Permissionless: anyone may crank a due renewal
+    pub cranker: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [b"stream_funding", config.key().as_ref(), stream_funding.user.as_ref()],
+        bump = stream_funding.bump
+    )]
+    pub stream_funding: Account<'info, StreamFunding>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", config.key().as_ref(), stream_funding.user.as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        address = stream_funding.token_account @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: PDA used only as the token delegate, it never holds data
+    #[account(seeds = [b"stream_delegate", config.key().as_ref()], bump)]
+    pub delegate: UncheckedAccount<'info>,
+
+    /// CHECK: address is checked against the Streamflow program id
+    #[account(address = STREAMFLOW_PROGRAM_ID @ ErrorCode::InvalidStream)]
+    pub stream_program: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CrankStreamFundingBatch<'info> {
+    // Permissionless: anyone may crank due renewals
+    pub cranker: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token::ID @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: PDA used only as the token delegate, it never holds data
+    #[account(seeds = [b"stream_delegate", config.key().as_ref()], bump)]
+    pub delegate: UncheckedAccount<'info>,
+
+    // Receives failed renewals when the instance runs an outbox
+    #[account(mut, seeds = [b"outbox", config.key().as_ref()], bump)]
+    pub outbox: Option<AccountLoader<'info, Outbox>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelStreamFunding<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"stream_funding", config.key().as_ref(), user.key().as_ref()],
+        bump = stream_funding.bump,
+        has_one = user @ ErrorCode::InvalidOwner
+    )]
+    pub stream_funding: Account<'info, StreamFunding>,
+
+    #[account(
+        mut,
+        address = stream_funding.token_account @ ErrorCode::InvalidOwner
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct DepositStream<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PaymentStream::INIT_SPACE,
+        seeds = [b"payment_stream", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub payment_stream: Account<'info, PaymentStream>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = payment_stream,
+        associated_token::token_program = token_program
+    )]
+    pub stream_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = user
+    )]
+    pub user_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    // Created here so settling never has to pay rent
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        address = config.mint @ ErrorCode::InvalidMint,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleStream<'info> {
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: only used to derive the stream and subscription PDAs
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"payment_stream", config.key().as_ref(), user.key().as_ref()],
+        bump = payment_stream.bump
+    )]
+    pub payment_stream: Account<'info, PaymentStream>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = payment_stream,
+        associated_token::token_program = token_program
+    )]
+    pub stream_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = insurance_vault.mint == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || insurance_vault.owner == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token_program.key() @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint,
+        mint::token_program = token_program
+    )]
+    pub mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"stats_shard", config.key().as_ref(), &[stats_shard_index(&user.key())]],
+        bump = stats_shard.bump
+    )]
+    pub stats_shard: Account<'info, StatsShard>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStream<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"payment_stream", config.key().as_ref(), user.key().as_ref()],
+        bump = payment_stream.bump
+    )]
+    pub payment_stream: Account<'info, PaymentStream>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = payment_stream,
+        associated_token::token_program = token_program
+    )]
+    pub stream_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut, token::mint = mint)]
+    pub user_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        address = config.mint @ ErrorCode::InvalidMint,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct StartTrial<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    // One per wallet: a second claim fails to re-create it
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Trial::INIT_SPACE,
+        seeds = [b"trial", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub trial: Account<'info, Trial>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EnableAutoRenew<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + AutoRenew::INIT_SPACE,
+        seeds = [b"auto_renew", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub auto_renew: Account<'info, AutoRenew>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Subscription::SPACE,
+        seeds = [b"subscription", config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    // Renews into this plan instead of the instance defaults
+    #[account(
+        seeds = [b"plan", config.key().as_ref(), plan.plan_id.to_le_bytes().as_ref()],
+        bump = plan.bump
+    )]
+    pub plan: Option<Account<'info, Plan>>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// CHECK: PDA used only as the token delegate, it never holds data
+    #[account(seeds = [b"renew_delegate", config.key().as_ref()], bump)]
+    pub delegate: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CrankRenewal<'info> {
+    // Permissionless: anyone may crank an expired auto-renewal
+    pub cranker: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [b"auto_renew", config.key().as_ref(), auto_renew.user.as_ref()],
+        bump = auto_renew.bump
+    )]
+    pub auto_renew: Account<'info, AutoRenew>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", config.key().as_ref(), auto_renew.user.as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    // Required when auto-renew was enabled for a plan
+    pub plan: Option<Account<'info, Plan>>,
+
+    #[account(
+        mut,
+        address = auto_renew.token_account @ ErrorCode::InvalidOwner,
+        constraint = user_token_account.mint == config.mint @ ErrorCode::InvalidMint
+    )]
+    pub user_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// CHECK: pinned to the instance vault like in `ProcessPayment`
+    #[account(
+        mut,
+        address = config.insurance_destination() @ ErrorCode::InvalidVault,
+        constraint = token_account_field(&insurance_vault, TOKEN_ACCOUNT_MINT_OFFSET) == config.mint @ ErrorCode::InvalidVaultMint,
+        constraint = config.escrows_insurance() || token_account_field(&insurance_vault, TOKEN_ACCOUNT_OWNER_OFFSET) == config.vault_authority @ ErrorCode::InvalidVaultAuthority,
+        owner = token_program.key() @ ErrorCode::InvalidVaultOwner
+    )]
+    pub insurance_vault: UncheckedAccount<'info>,
+
+    /// CHECK: pinned to the instance mint; only its decimals are read
+    #[account(
+        mut,
+        address = config.mint @ ErrorCode::InvalidMint,
+        owner = token_program.key() @ ErrorCode::InvalidMint
+    )]
+    pub mint: UncheckedAccount<'info>,
+
+    /// CHECK: any instance-mint account; the token program checks the mint
+    #[account(mut)]
+    pub cranker_token_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: PDA used only as the token delegate, it never holds data
+    #[account(seeds = [b"renew_delegate", config.key().as_ref()], bump)]
+    pub delegate: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct DisableAutoRenew<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(seeds = [b"config", config.id.to_le_bytes().as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"auto_renew", config.key().as_ref(), user.key().as_ref()],
+        bump = auto_renew.bump,
+        has_one = user @ ErrorCode::InvalidOwner
+    )]
+    pub auto_renew: Account<'info, AutoRenew>,
+
+    #[account(
+        mut,
+        address = auto_renew.token_account @ ErrorCode::InvalidOwner
+    )]
+    pub user_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// A subscription, bit-packed to keep rent low across many accounts. The owner
+/// is implied by the PDA seeds. Accounts created before the packed layout are
+/// [`LegacySubscription`]s until `migrate_subscription` rewrites them in place.
+/// New accounts carry [`Subscription::RESERVED`] zeroed bytes past the struct
+/// for later fields; `migrate_subscription` grows older packed accounts too.
+#[account(discriminator = &SUBSCRIPTION_DISCRIMINATOR)]
+#[derive(InitSpace)]
+pub struct Subscription {
+    pub version: u8,
+    // Bits 0-19: expiry in whole days since the unix epoch, 20-21: status,
+    // 22-25: tier, 26-31: flags
+    pub packed: u32,
+    // `process_payment` payments since the subscription was opened
+    pub payment_count: u32,
+    // Slot of the last `process_payment`, for the instance's payment interval
+    pub last_payment_slot: u64,
+}
+
+impl Subscription {
+    pub const VERSION: u8 = 3;
+    pub const RESERVED: usize = 4;
+    // Allocated size, discriminator and reserved tail included
+    pub const SPACE: usize = 8 + Self::INIT_SPACE + Self::RESERVED;
+
+    pub const STATUS_ACTIVE: u8 = 0;
+    pub const STATUS_REVOKED: u8 = 1;
+
+    const STATUS_SHIFT: u32 = 20;
+    const TIER_SHIFT: u32 = 22;
+    const FLAGS_SHIFT: u32 = 26;
+    const EXPIRY_MASK: u32 = (1 << Self::STATUS_SHIFT) - 1;
+    const STATUS_MASK: u32 = 0b11;
+    const TIER_MASK: u32 = 0b1111;
+    const FLAGS_MASK: u32 = 0b11_1111;
+
+    pub fn expires_at(&self) -> i64 {
+        (self.packed & Self::EXPIRY_MASK) as i64 * SECONDS_PER_DAY
+    }
+
+    /// Rounds up to the next whole day, so a payer never loses time
+    pub fn set_expires_at(&mut self, expires_at: i64) -> Result<()> {
+        let days = expires_at.max(0).checked_add(SECONDS_PER_DAY - 1).unwrap() / SECONDS_PER_DAY;
+        require!(days <= Self::EXPIRY_MASK as i64, ErrorCode::InvalidDuration);
+        self.packed = (self.packed & !Self::EXPIRY_MASK) | days as u32;
+        Ok(())
+    }
+
+    pub fn status(&self) -> u8 {
+        self.bits(Self::STATUS_SHIFT, Self::STATUS_MASK)
+    }
+
+    /// Paid up at unix time `now` and not revoked
+    pub fn is_active(&self, now: i64) -> bool {
+        self.status() != Self::STATUS_REVOKED && self.expires_at() > now
+    }
+
+    /// Where the subscription stands at unix time `now` given the instance's
+    /// `grace_period`; a revoked subscription is always expired
+    pub fn state(&self, now: i64, grace_period: i64) -> SubscriptionState {
+        if self.status() == Self::STATUS_REVOKED {
+            SubscriptionState::Expired
+        } else if self.expires_at() > now {
+            SubscriptionState::Active
+        } else if self.expires_at().saturating_add(grace_period) > now {
+            SubscriptionState::Grace
+        } else {
+            SubscriptionState::Expired
+        }
+    }
+
+    /// Whether a payment at `slot` would come within `min_interval_slots` of
+    /// the last one
+    pub fn rate_limited(&self, min_interval_slots: u64, slot: u64) -> bool {
+        self.last_payment_slot > 0
+            && slot < self.last_payment_slot.saturating_add(min_interval_slots)
+    }
+
+    pub fn set_status(&mut self, status: u8) {
+        self.set_bits(Self::STATUS_SHIFT, Self::STATUS_MASK, status);
+    }
+
+    pub fn tier(&self) -> u8 {
+        self.bits(Self::TIER_SHIFT, Self::TIER_MASK)
+    }
+
+    pub fn set_tier(&mut self, tier: u8) {
+        self.set_bits(Self::TIER_SHIFT, Self::TIER_MASK, tier);
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.bits(Self::FLAGS_SHIFT, Self::FLAGS_MASK)
+    }
+
+    pub fn set_flags(&mut self, flags: u8) {
+        self.set_bits(Self::FLAGS_SHIFT, Self::FLAGS_MASK, flags);
+    }
+
+    fn bits(&self, shift: u32, mask: u32) -> u8 {
+        ((self.packed >> shift) & mask) as u8
+    }
+
+    fn set_bits(&mut self, shift: u32, mask: u32, value: u8) {
+        self.packed = (self.packed & !(mask << shift)) | ((value as u32 & mask) << shift);
+    }
+}
+
+/// The original unpacked subscription layout, kept only for migration
+#[account(discriminator = &LEGACY_SUBSCRIPTION_DISCRIMINATOR)]
+pub struct LegacySubscription {
+    pub user: Pubkey,
+    pub expires_at: i64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct RouterRegistry {
+    // Next instance id; instance #0 is the SAKURA deployment
+    pub instance_count: u64,
+    pub bump: u8,
+}
+
+/// One router instance: its own mint, vault, split and subscription duration.
+/// New accounts carry [`Config::RESERVED`] zeroed bytes past the struct so
+/// later fields decode as zero; `migrate_config` grows older accounts to match.
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub id: u64,
+    pub admin: Pubkey,
+    // Runs sweeps and burns; cannot change economic parameters
+    pub operator: Pubkey,
+    pub mint: Pubkey,
+    pub insurance_vault: Pubkey,
+    // SPL token authority expected on the insurance vault
+    pub vault_authority: Pubkey,
+    pub insurance_bps: u16,
+    pub burn_bps: u16,
+    // Seconds added to a subscription per paid period
+    pub subscription_duration: i64,
+    pub compliance_mode: ComplianceMode,
+    // Key that must attest every payment while compliance mode is enabled
+    pub attestor: Pubkey,
+    // Price of one period paid in a liquid-staking token, in millionths of a
+    // dollar converted at the `sol_usd_feed_id` price; zero turns LST payments off
+    pub lst_usd_price: u64,
+    // Minimum SAKURA-mint amount per period, zero accepts any amount
+    pub price: u64,
+    // Discount off `price` until `promo_ends_at`
+    pub promo_bps: u16,
+    pub promo_ends_at: i64,
+    // Reject payments that don't create a Receipt
+    pub require_receipts: bool,
+    // Lifetime burn for Bronze, Silver and Gold, zero disables a tier
+    pub loyalty_thresholds: [u64; 3],
+    // Temporary split applied from `split_promo_starts_at` until `split_promo_ends_at`
+    pub split_promo_insurance_bps: u16,
+    pub split_promo_burn_bps: u16,
+    pub split_promo_starts_at: i64,
+    pub split_promo_ends_at: i64,
+    // Signs the price quotes `process_payment` requires, default when quoting is off
+    pub quote_authority: Pubkey,
+    // Payers must present an unexpired attestation from `jurisdiction_attestor`
+    pub require_jurisdiction_attestation: bool,
+    pub jurisdiction_attestor: Pubkey,
+    // Scheduled mint migration; default while none is pending
+    pub successor_mint: Pubkey,
+    pub successor_insurance_vault: Pubkey,
+    pub mint_cutover_at: i64,
+    // Bonding curve: `curve_step_bps` off per `curve_burn_step` burned, capped at
+    // `curve_max_bps`; `curve_discount_bps` is the current step, kept by `aggregate_stats`
+    pub curve_burn_step: u64,
+    pub curve_step_bps: u16,
+    pub curve_max_bps: u16,
+    pub curve_discount_bps: u16,
+    // Share of each subscription resale routed through the split and burn
+    pub resale_royalty_bps: u16,
+    // Share of a referred `process_payment` paid to the referrer before the split
+    pub referral_bps: u16,
+    // Share of each auto-renewal paid to whoever cranks it
+    pub renewal_bounty_bps: u16,
+    // Circuit breaker: every payment path fails while set
+    pub paused: bool,
+    // `process_payment` routes through the instance routing table instead of
+    // the two-way split
+    pub use_routing_table: bool,
+    // Pyth SAKURA/USD feed that prices USD plans; all zero disables them.
+    // Updates older than `max_oracle_age` seconds, or whose confidence
+    // interval is wider than `max_oracle_conf_bps` of the price, are rejected
+    pub pyth_feed_id: [u8; 32],
+    pub max_oracle_age: i64,
+    pub max_oracle_conf_bps: u16,
+    // Once non-zero, `process_payment` parks burn shares in the
+    // `pending_burn_vault` and `flush_burn` burns them once this many
+    // base units have accrued
+    pub burn_flush_threshold: u64,
+    pub pending_burn_vault: Pubkey,
+    // `process_payment` can't push an expiry more than this far past now; zero
+    // is no cap. `clamp_prepaid` shortens the period instead of failing.
+    pub max_prepaid_seconds: i64,
+    pub clamp_prepaid: bool,
+    // Free trial length for `start_trial`; zero turns trials off
+    pub trial_duration: i64,
+    // Tokens per second a pay-as-you-go stream is charged; zero turns new
+    // deposits off
+    pub stream_rate: u64,
+    // Programs allowed to CPI into `process_payment_via_cpi`; default slots are free
+    pub cpi_callers: [Pubkey; MAX_CPI_CALLERS],
+    // Program-owned account the insurance share lands in until swept to the
+    // insurance vault, so it can still be refunded; default when off
+    pub insurance_escrow: Pubkey,
+    // Proposed by the admin and in charge once it accepts; default when none
+    pub pending_admin: Pubkey,
+    // Seconds past expiry a paid renewal still carries on from the old expiry
+    pub grace_period: i64,
+    // Price of one period paid in wSOL, zero when SOL payments are off
+    pub sol_price_lamports: u64,
+    // wSOL account the insurance share of SOL payments is routed to
+    pub sol_insurance_vault: Pubkey,
+    // Smallest payment amount accepted, against dust spam
+    pub min_payment_amount: u64,
+    // Slots that must pass between two payments on one subscription
+    pub min_payment_interval_slots: u64,
+    // Where payments go once this router winds down at `sunset_at`; zero
+    // `sunset_at` while no sunset is scheduled
+    pub successor_program: Pubkey,
+    pub sunset_at: i64,
+    // How `process_payment` treats the burn share of the instance split
+    pub burn_mode: BurnMode,
+    // Instance-mint account the staking part of the burn share is paid into
+    pub staking_vault: Pubkey,
+    pub bump: u8,
+    // Fields from here on live in what was the reserved tail, and read as
+    // zero on configs grown by `migrate_config`
+    // Pyth SOL/USD feed that prices LST payments
+    pub sol_usd_feed_id: [u8; 32],
+    // Last `sweep_insurance_escrow`; payments escrowed before it can't be refunded
+    pub escrow_swept_at: i64,
+}
+
+impl Config {
+    // Shrinks by whatever the fields added past `bump` take, so `SPACE` holds
+    pub const RESERVED: usize = 216;
+    // Allocated size, discriminator and reserved tail included
+    pub const SPACE: usize = 8 + Self::INIT_SPACE + Self::RESERVED;
+
+    /// A fresh instance with the default split and duration and every optional
+    /// feature off; the admin starts out as operator too
+    pub fn init_defaults(
+        id: u64,
+        admin: Pubkey,
+        mint: Pubkey,
+        insurance_vault: Pubkey,
+        vault_authority: Pubkey,
+        bump: u8,
+    ) -> Self {
+        Self {
+            id,
+            admin,
+            operator: admin,
+            mint,
+            insurance_vault,
+            vault_authority,
+            insurance_bps: INSURANCE_BPS as u16,
+            burn_bps: BURN_BPS as u16,
+            subscription_duration: SUBSCRIPTION_TIME,
+            compliance_mode: ComplianceMode::Disabled,
+            attestor: Pubkey::default(),
+            lst_usd_price: 0,
+            price: 0,
+            promo_bps: 0,
+            promo_ends_at: 0,
+            require_receipts: false,
+            loyalty_thresholds: [0; 3],
+            split_promo_insurance_bps: 0,
+            split_promo_burn_bps: 0,
+            split_promo_starts_at: 0,
+            split_promo_ends_at: 0,
+            quote_authority: Pubkey::default(),
+            require_jurisdiction_attestation: false,
+            jurisdiction_attestor: Pubkey::default(),
+            successor_mint: Pubkey::default(),
+            successor_insurance_vault: Pubkey::default(),
+            mint_cutover_at: 0,
+            curve_burn_step: 0,
+            curve_step_bps: 0,
+            curve_max_bps: 0,
+            curve_discount_bps: 0,
+            resale_royalty_bps: 0,
+            referral_bps: 0,
+            renewal_bounty_bps: 0,
+            paused: false,
+            use_routing_table: false,
+            pyth_feed_id: [0; 32],
+            max_oracle_age: 0,
+            max_oracle_conf_bps: 0,
+            burn_flush_threshold: 0,
+            pending_burn_vault: Pubkey::default(),
+            max_prepaid_seconds: 0,
+            clamp_prepaid: false,
+            trial_duration: 0,
+            stream_rate: 0,
+            cpi_callers: [Pubkey::default(); MAX_CPI_CALLERS],
+            insurance_escrow: Pubkey::default(),
+            pending_admin: Pubkey::default(),
+            grace_period: 0,
+            sol_price_lamports: 0,
+            sol_insurance_vault: Pubkey::default(),
+            min_payment_amount: 0,
+            min_payment_interval_slots: 0,
+            successor_program: Pubkey::default(),
+            sunset_at: 0,
+            burn_mode: BurnMode::FullBurn,
+            staking_vault: Pubkey::default(),
+            bump,
+            sol_usd_feed_id: [0; 32],
+            escrow_swept_at: 0,
+        }
+    }
+
+    /// Where instance payments route their insurance share
+    pub fn insurance_destination(&self) -> Pubkey {
+        if self.escrows_insurance() {
+            self.insurance_escrow
+        } else {
+            self.insurance_vault
+        }
+    }
+
+    pub fn escrows_insurance(&self) -> bool {
+        self.insurance_escrow != Pubkey::default()
+    }
+
+    pub fn allows_cpi_caller(&self, program: &Pubkey) -> bool {
+        *program != Pubkey::default() && self.cpi_callers.contains(program)
+    }
+
+    /// Whether new payments have stopped at unix time `now`
+    pub fn is_sunset(&self, now: i64) -> bool {
+        self.sunset_at > 0 && now >= self.sunset_at
+    }
+}
+
+/// Snapshot of an instance's pricing that partners and frontends read in one fetch
+#[account]
+#[derive(InitSpace)]
+pub struct PriceFeed {
+    pub config: Pubkey,
+    pub price: u64,
+    // What a payment has to cover right now, promo included
+    pub effective_price: u64,
+    // Zeroed once the promo has ended
+    pub promo_bps: u16,
+    pub promo_ends_at: i64,
+    // Split applied right now, scheduled split promo included
+    pub insurance_bps: u16,
+    pub burn_bps: u16,
+    pub subscription_duration: i64,
+    pub lst_usd_price: u64,
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct StreamFunding {
+    pub user: Pubkey,
+    // Streamflow stream whose recipient token account funds the renewals
+    pub stream: Pubkey,
+    pub token_account: Pubkey,
+    pub amount_per_period: u64,
+    pub last_funded_at: i64,
+    pub bump: u8,
+}
+
+/// A user's pay-as-you-go balance, held in the associated token account of
+/// this PDA until `settle_stream` draws it
+#[account]
+#[derive(InitSpace)]
+pub struct PaymentStream {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    // Last time a period was settled; zero before the first
+    pub settled_at: i64,
+    pub bump: u8,
+}
+
+/// A subscription move `from` has offered and `to` has yet to accept
+#[account]
+#[derive(InitSpace)]
+pub struct SubscriptionTransfer {
+    pub config: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub initiated_at: i64,
+    pub bump: u8,
+}
 
-pub const PERCOLATOR_VAULT_AUTHORITY: Pubkey = pubkey!("11111111111111111111111111111111");
+/// Marks a wallet's free trial as claimed
+#[account]
+#[derive(InitSpace)]
+pub struct Trial {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub started_at: i64,
+    pub bump: u8,
+}
 
-pub const INSURANCE_BPS: u64 = 5000;
-pub const BURN_BPS: u64 = 5000;
+/// A subscription renewed by crank out of a delegated allowance
+#[account]
+#[derive(InitSpace)]
+pub struct AutoRenew {
+    pub user: Pubkey,
+    pub token_account: Pubkey,
+    // Default key when renewing at the instance price
+    pub plan: Pubkey,
+    pub remaining_renewals: u32,
+    pub bump: u8,
+}
 
-// 30 days subscription in seconds
-pub const SUBSCRIPTION_TIME: i64 = 30 * 24 * 60 * 60;
+/// SAKURA escrowed by a sponsor; each allowlisted wallet may draw one period
+#[account]
+#[derive(InitSpace)]
+pub struct SponsorPool {
+    pub config: Pubkey,
+    pub sponsor: Pubkey,
+    pub pool_id: u64,
+    pub amount_per_draw: u64,
+    pub draws_claimed: u64,
+    pub bump: u8,
+}
 
-#[program]
-pub mod sakura_fee_router {
-    use super::*;
+#[account]
+#[derive(InitSpace)]
+pub struct SponsorAllowance {
+    pub pool: Pubkey,
+    pub wallet: Pubkey,
+    pub claimed: bool,
+    pub bump: u8,
+}
 
-    pub fn process_payment(ctx: Context<ProcessPayment>, amount: u64) -> Result<()> {
-        // Enforce safe math constraints
-        require!(INSURANCE_BPS + BURN_BPS == 10_000, ErrorCode::InvalidSplit);
-        require!(amount > 0, ErrorCode::InvalidAmount);
+/// A shared treasury for a company paying through the router; funds move only
+/// with both signers on the transaction
+#[account]
+#[derive(InitSpace)]
+pub struct OrgAccount {
+    pub config: Pubkey,
+    pub creator: Pubkey,
+    pub first_signer: Pubkey,
+    pub second_signer: Pubkey,
+    pub bump: u8,
+}
 
-        // 1. Calculate splits (immutable BPS)
-        let insurance_amount = amount
-            .checked_mul(INSURANCE_BPS)
-            .unwrap()
-            .checked_div(10_000)
-            .unwrap();
-        let burn_amount = amount.checked_sub(insurance_amount).unwrap();
+/// One wallet paying for `seats` member seats at a time
+#[account]
+#[derive(InitSpace)]
+pub struct Organization {
+    pub config: Pubkey,
+    pub owner: Pubkey,
+    // Plan every seat is priced at; default for the instance price
+    pub plan: Pubkey,
+    pub seats: u32,
+    // Seats currently given out
+    pub members: u32,
+    pub expires_at: i64,
+    pub bump: u8,
+}
 
-        // 2. Route funds to the percolator insurance vault
-        let transfer_cpi_accounts = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.insurance_vault.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
+impl Organization {
+    /// Paid up at unix time `now`, which every seat inherits
+    pub fn is_active(&self, now: i64) -> bool {
+        self.expires_at > now
+    }
+}
+
+/// A member's seat in an organization, valid while the organization is paid up
+#[account]
+#[derive(InitSpace)]
+pub struct Seat {
+    pub organization: Pubkey,
+    pub member: Pubkey,
+    pub added_at: i64,
+    pub bump: u8,
+}
+
+/// A third-party service selling its own subscriptions over an instance's rails
+#[account]
+#[derive(InitSpace)]
+pub struct Service {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub service_id: u64,
+    // Minimum amount per period, zero accepts any amount
+    pub price: u64,
+    // Seconds added to a service subscription per paid period
+    pub duration: i64,
+    pub bump: u8,
+}
+
+/// Multi-destination split for `process_payment`, see [`MAX_ROUTES`]
+#[account]
+#[derive(InitSpace)]
+pub struct RoutingTable {
+    pub config: Pubkey,
+    // Only the first `route_count` are set
+    pub routes: [Route; MAX_ROUTES],
+    pub route_count: u8,
+    pub bump: u8,
+}
+
+impl RoutingTable {
+    pub fn routes(&self) -> &[Route] {
+        &self.routes[..self.route_count as usize]
+    }
+}
+
+/// One routing table entry: `bps` of each payment to `destination`, an
+/// instance-mint token account, or burned when it is the default key
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct Route {
+    pub destination: Pubkey,
+    pub bps: u16,
+}
+
+impl Route {
+    pub fn burns(&self) -> bool {
+        self.destination == Pubkey::default()
+    }
+}
+
+/// Percolator slabs the instance can insure, see [`MAX_SLABS`]
+#[account]
+#[derive(InitSpace)]
+pub struct SlabRegistry {
+    pub config: Pubkey,
+    // Only the first `slab_count` are set
+    pub slabs: [Slab; MAX_SLABS],
+    pub slab_count: u8,
+    pub bump: u8,
+}
+
+impl SlabRegistry {
+    pub fn slabs(&self) -> &[Slab] {
+        &self.slabs[..self.slab_count as usize]
+    }
+
+    /// The insurance vault of slab `slab_id`, unless it is unknown or deactivated
+    pub fn active_vault(&self, slab_id: u64) -> Option<Pubkey> {
+        self.slabs()
+            .iter()
+            .find(|slab| slab.slab_id == slab_id && slab.active)
+            .map(|slab| slab.insurance_vault)
+    }
+}
+
+/// One Percolator slab and the insurance vault its share of fees goes to
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct Slab {
+    pub slab_id: u64,
+    pub insurance_vault: Pubkey,
+    pub active: bool,
+}
+
+/// A subscription plan: one payment of `price` buys `duration`
+#[account]
+#[derive(InitSpace)]
+pub struct Plan {
+    pub config: Pubkey,
+    pub plan_id: u64,
+    pub duration: i64,
+    pub price: u64,
+    // Millionths of a dollar; nonzero prices the plan through the oracle
+    pub usd_price: u64,
+    pub active: bool,
+    pub bump: u8,
+}
+
+/// A discount code, keyed by the hash of the code itself
+#[account]
+#[derive(InitSpace)]
+pub struct Promo {
+    pub config: Pubkey,
+    pub code_hash: [u8; 32],
+    pub discount_bps: u16,
+    pub remaining_uses: u32,
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+/// A catalog SKU billed through the router under its own split
+#[account]
+#[derive(InitSpace)]
+pub struct Product {
+    pub config: Pubkey,
+    pub product_id: u64,
+    pub kind: ProductKind,
+    pub price: u64,
+    // Period bought per purchase; zero for one-time purchases
+    pub duration: i64,
+    pub insurance_bps: u16,
+    pub burn_bps: u16,
+    pub active: bool,
+    pub bump: u8,
+}
+
+/// A service's bill to `payer`, fulfilled through the instance split
+#[account]
+#[derive(InitSpace)]
+pub struct Invoice {
+    pub service: Pubkey,
+    pub payer: Pubkey,
+    pub invoice_id: u64,
+    pub amount: u64,
+    // Due date of the period being billed; advances by `interval` per payment
+    pub due_at: i64,
+    // Zero for a one-off invoice
+    pub interval: i64,
+    pub payments: u32,
+    // Set once a one-off invoice is paid
+    pub paid: bool,
+    pub bump: u8,
+}
+
+/// Transferable gift cards for an instance; one card redeems for `duration` seconds
+#[account]
+#[derive(InitSpace)]
+pub struct GiftCard {
+    pub config: Pubkey,
+    pub mint: Pubkey,
+    // Instance-mint amount paid per card, split and burned at purchase
+    pub price: u64,
+    pub duration: i64,
+    pub bump: u8,
+}
+
+/// A wallet paid a share of the payments it refers, with leaderboard counters
+#[account]
+#[derive(InitSpace)]
+pub struct Referrer {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub token_account: Pubkey,
+    pub referred_payments: u64,
+    pub total_earned: u64,
+    pub bump: u8,
+}
+
+/// Subscription time a seller has put up for resale, escrowed out of their subscription
+#[account]
+#[derive(InitSpace)]
+pub struct Listing {
+    pub config: Pubkey,
+    pub seller: Pubkey,
+    pub price: u64,
+    // Escrowed seconds, always whole days
+    pub remaining: i64,
+    pub listed_at: i64,
+    pub bump: u8,
+}
+
+/// Prepaid, not yet assigned subscription periods held for their buyer
+#[account]
+#[derive(InitSpace)]
+pub struct PassBatch {
+    pub config: Pubkey,
+    pub buyer: Pubkey,
+    pub batch_id: u64,
+    pub quantity: u32,
+    pub remaining: u32,
+    // Seconds each assigned pass adds, fixed at purchase
+    pub duration: i64,
+    pub bump: u8,
+}
+
+/// A published SLA incident and what each affected subscriber receives
+#[account]
+#[derive(InitSpace)]
+pub struct Incident {
+    pub config: Pubkey,
+    pub incident_id: u64,
+    // Paid out of the treasury vault for this mint
+    pub stable_mint: Pubkey,
+    pub amount: u64,
+    // Seconds of subscription time added per subscriber
+    pub extension: i64,
+    pub compensated: u64,
+    pub bump: u8,
+}
+
+/// Marks `user` as compensated for `incident`
+#[account]
+#[derive(InitSpace)]
+pub struct CompensationClaim {
+    pub incident: Pubkey,
+    pub user: Pubkey,
+    pub claimed_at: i64,
+    pub bump: u8,
+}
+
+/// Records that `user` has paid under `reference`, so a retried payment with
+/// the same reference fails instead of charging twice
+#[account]
+#[derive(InitSpace)]
+pub struct PaymentReference {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub reference: [u8; 32],
+    pub amount: u64,
+    pub paid_at: i64,
+    pub bump: u8,
+}
+
+/// Instance-wide payment totals, rebuilt from the shards by `aggregate_stats`
+#[account]
+#[derive(InitSpace)]
+pub struct Stats {
+    pub config: Pubkey,
+    pub payments: u64,
+    pub volume: u64,
+    pub insured: u64,
+    pub burned: u64,
+    // Best effort: subscriptions whose first payment was recorded in a shard,
+    // so first payments made in light mode are missed
+    pub unique_subscribers: u64,
+    pub updated_at: i64,
+    // Burn share paid to the staking vault instead of burned
+    pub staked: u64,
+    pub bump: u8,
+}
+
+/// A whitelisted partner collection and the discount its holders get
+#[account]
+#[derive(InitSpace)]
+pub struct NftDiscount {
+    pub config: Pubkey,
+    // Metaplex verified collection mint
+    pub collection: Pubkey,
+    pub discount_bps: u16,
+    pub bump: u8,
+}
+
+/// Proof that `user` passed the instance's geo/compliance checks, issued
+/// by the jurisdiction attestor
+#[account]
+#[derive(InitSpace)]
+pub struct JurisdictionAttestation {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    // Attestor at issue time; rotating the attestor invalidates old attestations
+    pub issuer: Pubkey,
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+/// A payer's own counters; the loyalty tier follows their lifetime burn
+#[account]
+#[derive(InitSpace)]
+pub struct UserStats {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub payments: u64,
+    pub lifetime_burned: u64,
+    // As of the last payment; `loyalty_tier` recomputes it under current thresholds
+    pub tier: LoyaltyTier,
+    // Nonce of the last signed quote this payer paid with
+    pub last_quote_nonce: u64,
+    pub bump: u8,
+}
+
+/// A collectible for payers whose lifetime burn reaches `threshold`; each
+/// claim mints a fresh one-of-one Token-2022 mint carrying this metadata
+#[account]
+#[derive(InitSpace)]
+pub struct Badge {
+    pub config: Pubkey,
+    pub badge_id: u8,
+    pub threshold: u64,
+    pub claimed: u64,
+    #[max_len(32)]
+    pub name: String,
+    #[max_len(10)]
+    pub symbol: String,
+    #[max_len(200)]
+    pub uri: String,
+    pub bump: u8,
+}
+
+/// The Bubblegum tree an instance mints compressed receipts into, and the
+/// metadata every receipt leaf carries
+#[account]
+#[derive(InitSpace)]
+pub struct ReceiptTree {
+    pub config: Pubkey,
+    pub merkle_tree: Pubkey,
+    #[max_len(32)]
+    pub name: String,
+    #[max_len(10)]
+    pub symbol: String,
+    #[max_len(200)]
+    pub uri: String,
+    pub minted: u64,
+    pub bump: u8,
+}
+
+/// One slice of the payment counters, written directly by payments
+#[account]
+#[derive(InitSpace)]
+pub struct StatsShard {
+    pub config: Pubkey,
+    pub index: u8,
+    pub payments: u64,
+    pub volume: u64,
+    pub insured: u64,
+    pub burned: u64,
+    pub subscribers: u64,
+    pub staked: u64,
+    pub bump: u8,
+}
+
+/// One payment's amount, split, plan and slab, for integrators and accounting
+/// that don't index transaction logs
+#[account]
+#[derive(InitSpace)]
+pub struct Receipt {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    // Whose subscription the payment extended; the payer unless it was a gift
+    pub subscriber: Pubkey,
+    // The payer's `UserStats::payments` before this payment
+    pub payment_index: u64,
+    pub amount: u64,
+    pub insurance_amount: u64,
+    pub burn_amount: u64,
+    // Insurance share returned by `refund` so far
+    pub refunded: u64,
+    // The insurance share went to the instance escrow, so `refund` can return it
+    pub escrowed: bool,
+    pub plan_id: Option<u64>,
+    pub slab_id: Option<u64>,
+    // External reference the payment was made under, if any
+    pub reference: Option<[u8; 32]>,
+    // Time the payment bought, after any prepaid cap
+    pub duration: i64,
+    // Subscription expiry after this payment
+    pub expires_at: i64,
+    pub paid_at: i64,
+    pub bump: u8,
+}
+
+/// One page of an instance registry. `kind` namespaces registries within an
+/// instance and `page` chains pages once one fills up; only the first `len`
+/// entries are live.
+#[account(zero_copy)]
+pub struct RegistryPage {
+    pub config: Pubkey,
+    pub entries: [RegistryEntry; REGISTRY_PAGE_CAPACITY],
+    pub len: u16,
+    pub kind: u8,
+    pub page: u8,
+    pub bump: u8,
+    pub _padding: [u8; 3],
+}
+
+/// Ring of notable occurrences for off-chain workers. Entries `acked..head`
+/// are pending; sequence `seq` lives in slot `seq % OUTBOX_CAPACITY`.
+#[account(zero_copy)]
+pub struct Outbox {
+    pub config: Pubkey,
+    // Sequence number the next entry gets
+    pub head: u64,
+    // Every entry below this sequence number has been processed
+    pub acked: u64,
+    // Pending entries overwritten before they were acked
+    pub dropped: u64,
+    pub entries: [OutboxEntry; OUTBOX_CAPACITY],
+    pub bump: u8,
+    pub _padding: [u8; 7],
+}
+
+/// `value` depends on `kind`: the subscription expiry for expiry notices and
+/// failed renewals, the amount for refunds
+#[zero_copy]
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct OutboxEntry {
+    pub seq: u64,
+    pub user: Pubkey,
+    pub value: u64,
+    pub queued_at: i64,
+    pub kind: u8,
+    pub _padding: [u8; 7],
+}
+
+impl OutboxEntry {
+    pub const EXPIRING_SOON: u8 = 0;
+    pub const PAYMENT_FAILED: u8 = 1;
+    pub const REFUND_ISSUED: u8 = 2;
+}
+
+impl Outbox {
+    pub fn push(&mut self, kind: u8, user: Pubkey, value: u64, now: i64) -> u64 {
+        let seq = self.head;
+        if seq - self.acked == OUTBOX_CAPACITY as u64 {
+            self.acked += 1;
+            self.dropped += 1;
+        }
+        self.entries[seq as usize % OUTBOX_CAPACITY] = OutboxEntry {
+            seq,
+            user,
+            value,
+            queued_at: now,
+            kind,
+            _padding: [0; 7],
         };
-        let transfer_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            transfer_cpi_accounts,
-        );
-        token::transfer(transfer_ctx, insurance_amount)?;
+        self.head = seq.checked_add(1).unwrap();
+        seq
+    }
 
-        // 3. Burn the remaining tokens out of existence permanently
-        let burn_cpi_accounts = Burn {
-            mint: ctx.accounts.mint.to_account_info(),
-            from: ctx.accounts.user_token_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
+    /// Whether a matching entry is still held in the ring, acked or not
+    pub fn contains(&self, kind: u8, user: &Pubkey, value: u64) -> bool {
+        let held = (self.head as usize).min(OUTBOX_CAPACITY);
+        self.entries[..held]
+            .iter()
+            .any(|entry| entry.kind == kind && entry.user == *user && entry.value == value)
+    }
+}
+
+/// A registered key with a registry-specific value, e.g. a bps or an id
+#[zero_copy]
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct RegistryEntry {
+    pub key: Pubkey,
+    pub value: u64,
+}
+
+/// A [`RegistryEntry`] as `upsert_registry_entries` takes it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegistryEntryArgs {
+    pub key: Pubkey,
+    pub value: u64,
+}
+
+impl RegistryPage {
+    pub fn entries(&self) -> &[RegistryEntry] {
+        &self.entries[..self.len as usize]
+    }
+
+    pub fn find(&self, key: &Pubkey) -> Option<&RegistryEntry> {
+        self.entries().iter().find(|entry| entry.key == *key)
+    }
+
+    pub fn upsert(&mut self, entry: RegistryEntryArgs) -> Result<()> {
+        let entry = RegistryEntry {
+            key: entry.key,
+            value: entry.value,
         };
-        let burn_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            burn_cpi_accounts,
+        let len = self.len as usize;
+        match self.entries[..len].iter().position(|e| e.key == entry.key) {
+            Some(index) => self.entries[index] = entry,
+            None => {
+                require!(len < REGISTRY_PAGE_CAPACITY, ErrorCode::RegistryPageFull);
+                self.entries[len] = entry;
+                self.len += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Swaps the last live entry into the hole, so order isn't preserved
+    pub fn remove(&mut self, key: &Pubkey) -> Result<()> {
+        let len = self.len as usize;
+        let index = self.entries[..len]
+            .iter()
+            .position(|entry| entry.key == *key)
+            .ok_or(ErrorCode::RegistryEntryNotFound)?;
+        self.entries[index] = self.entries[len - 1];
+        self.entries[len - 1] = RegistryEntry::default();
+        self.len -= 1;
+        Ok(())
+    }
+}
+
+/// Looks `key` up across a registry's pages, passed in page order, loading one
+/// page at a time so the whole registry never has to sit on the heap
+pub fn registry_lookup<'info>(
+    pages: &'info [AccountInfo<'info>],
+    config: &Pubkey,
+    kind: u8,
+    key: &Pubkey,
+) -> Result<Option<RegistryEntry>> {
+    for (index, info) in pages.iter().enumerate() {
+        let loader = AccountLoader::<RegistryPage>::try_from(info)?;
+        let page = loader.load()?;
+        require!(
+            page.config == *config && page.kind == kind && page.page as usize == index,
+            ErrorCode::InvalidRegistryPage
         );
-        token::burn(burn_ctx, burn_amount)?;
+        if let Some(entry) = page.find(key) {
+            return Ok(Some(*entry));
+        }
+    }
+    Ok(None)
+}
+
+/// Archived receipts as an append-only Merkle tree. Only the frontier is kept
+/// on chain; leaves are logged by `archive_receipts` for proofs off chain.
+#[account]
+#[derive(InitSpace)]
+pub struct ReceiptHistory {
+    pub config: Pubkey,
+    pub count: u64,
+    pub root: [u8; 32],
+    // Rightmost filled node at each level
+    pub frontier: [[u8; 32]; RECEIPT_HISTORY_DEPTH],
+    pub bump: u8,
+}
 
-        // 4. Update the on-chain Option B Subscription PDA using unix_timestamp
-        let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp;
+impl ReceiptHistory {
+    /// Returns the new leaf's index; call `compute_root` once a batch is in
+    pub fn append(&mut self, leaf: [u8; 32]) -> Result<u64> {
+        let index = self.count;
+        require!(
+            index < 1 << RECEIPT_HISTORY_DEPTH,
+            ErrorCode::ReceiptHistoryFull
+        );
 
-        let subscription = &mut ctx.accounts.subscription;
+        let mut node = leaf;
+        let mut size = index;
+        for level in 0..RECEIPT_HISTORY_DEPTH {
+            if size & 1 == 0 {
+                self.frontier[level] = node;
+                break;
+            }
+            node = hashv(&[&self.frontier[level], &node]).to_bytes();
+            size >>= 1;
+        }
+        self.count = index.checked_add(1).unwrap();
+        Ok(index)
+    }
 
-        let base_time = std::cmp::max(current_time, subscription.expires_at);
-        subscription.expires_at = base_time.checked_add(SUBSCRIPTION_TIME).unwrap();
+    /// Root over the first `count` leaves, empty leaves hashing as zeroes
+    pub fn compute_root(&self) -> [u8; 32] {
+        let mut node = [0u8; 32];
+        let mut zero = [0u8; 32];
+        let mut size = self.count;
+        for level in 0..RECEIPT_HISTORY_DEPTH {
+            node = if size & 1 == 1 {
+                hashv(&[&self.frontier[level], &node]).to_bytes()
+            } else {
+                hashv(&[&node, &zero]).to_bytes()
+            };
+            zero = hashv(&[&zero, &zero]).to_bytes();
+            size >>= 1;
+        }
+        node
+    }
+}
 
-        subscription.user = ctx.accounts.user.key();
+/// Program-owned token vaults, each the associated token account of its own PDA
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VaultKind {
+    PendingBurn,
+    Treasury,
+    StakingPool,
+    InsuranceEscrow,
+    // Holds the wSOL burn share of SOL payments until it is bought back
+    Buyback,
+}
 
-        Ok(())
+impl VaultKind {
+    pub fn seed(&self) -> &'static [u8] {
+        match self {
+            VaultKind::PendingBurn => b"pending_burn",
+            VaultKind::Treasury => b"treasury",
+            VaultKind::StakingPool => b"staking_pool",
+            VaultKind::InsuranceEscrow => b"insurance_escrow",
+            VaultKind::Buyback => b"buyback",
+        }
     }
 }
 
-#[derive(Accounts)]
-pub struct ProcessPayment<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, InitSpace,
+)]
+pub enum LoyaltyTier {
+    None,
+    Bronze,
+    Silver,
+    Gold,
+}
 
-    #[account(
-        mut,
-        constraint = user_token_account.owner == user.key() @ ErrorCode::InvalidOwner,
-        constraint = user_token_account.mint == SAKURA_MINT @ ErrorCode::InvalidMint
-    )]
-    pub user_token_account: Account<'info, TokenAccount>,
+/// Price for one payment, signed off-chain by the instance's quote authority
+/// over `config || payer || price || expires_at || nonce`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PriceQuote {
+    pub price: u64,
+    pub expires_at: i64,
+    pub nonce: u64,
+}
 
-    #[account(
-        mut,
-        address = PERCOLATOR_INSURANCE_VAULT @ ErrorCode::InvalidVault,
-        constraint = insurance_vault.mint == SAKURA_MINT @ ErrorCode::InvalidVaultMint,
-        // The TokenAccount.owner field represents the SPL token authority over the vault
-        constraint = insurance_vault.owner == PERCOLATOR_VAULT_AUTHORITY @ ErrorCode::InvalidVaultAuthority,
-        // The token program natively owns the token accounts
-        owner = token::ID @ ErrorCode::InvalidVaultOwner
-    )]
-    pub insurance_vault: Account<'info, TokenAccount>,
+impl PriceQuote {
+    /// The bytes the quote authority signs for `payer` on instance `config`
+    pub fn message(&self, config: &Pubkey, payer: &Pubkey) -> [u8; 88] {
+        let mut message = [0u8; 88];
+        message[..32].copy_from_slice(config.as_ref());
+        message[32..64].copy_from_slice(payer.as_ref());
+        message[64..72].copy_from_slice(&self.price.to_le_bytes());
+        message[72..80].copy_from_slice(&self.expires_at.to_le_bytes());
+        message[80..].copy_from_slice(&self.nonce.to_le_bytes());
+        message
+    }
+}
 
-    #[account(
-        mut,
-        address = SAKURA_MINT @ ErrorCode::InvalidMint
-    )]
-    pub mint: Account<'info, Mint>,
+/// Emitted by every completed router payment
+#[event]
+pub struct PaymentProcessed {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    // The payer itself unless the period was gifted
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    pub insurance_amount: u64,
+    pub burn_amount: u64,
+    // Part of the burn share paid to the staking vault instead
+    pub staking_amount: u64,
+    // Paid to the referrer before the split, zero for unreferred payments
+    pub referral_amount: u64,
+    // The caller's external reference, e.g. a billing backend's invoice id
+    pub reference: Option<[u8; 32]>,
+    pub new_expires_at: i64,
+}
 
-    #[account(
-        init_if_needed,
-        payer = user,
-        space = 8 + 32 + 8, // discriminator + pubkey + i64
-        seeds = [b"subscription", user.key().as_ref()],
-        bump
-    )]
-    pub subscription: Account<'info, Subscription>,
+/// Emitted whenever a payment moves a subscription's expiry
+#[event]
+pub struct SubscriptionExtended {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    // Grace when the renewal carried on from the lapsed expiry, Expired when
+    // it restarted from now
+    pub previous_state: SubscriptionState,
+    // Zero for a first payment
+    pub previous_expires_at: i64,
+    pub new_expires_at: i64,
+}
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+/// Emitted when a wallet claims its free trial
+#[event]
+pub struct TrialStarted {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub expires_at: i64,
 }
 
-#[account]
-pub struct Subscription {
+/// Emitted when a subscription is closed and its rent handed back
+#[event]
+pub struct SubscriptionClosed {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    // The user, or the cranker that reclaimed it
+    pub closed_by: Pubkey,
+    pub rent_returned: u64,
+    pub bounty: u64,
+}
+
+/// Emitted when escrowed insurance is handed back to a subscriber
+#[event]
+pub struct RefundIssued {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    // After the rollback
+    pub expires_at: i64,
+}
+
+/// Emitted when an organization pays for a period of seats
+#[event]
+pub struct SeatsPurchased {
+    pub config: Pubkey,
+    pub organization: Pubkey,
+    pub seats: u32,
+    pub amount: u64,
+    pub expires_at: i64,
+}
+
+/// Emitted when a pay-as-you-go stream pays for another settle period
+#[event]
+pub struct StreamSettled {
+    pub config: Pubkey,
     pub user: Pubkey,
+    pub amount: u64,
     pub expires_at: i64,
 }
 
+/// Emitted when a wallet offers its subscription to another
+#[event]
+pub struct SubscriptionTransferInitiated {
+    pub config: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+}
+
+/// Emitted when a subscription's remaining time moves to another wallet
+#[event]
+pub struct SubscriptionTransferred {
+    pub config: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    // The recipient's expiry with the moved time added
+    pub expires_at: i64,
+    pub by_admin: bool,
+}
+
+/// Emitted by every admin instruction
+#[event]
+pub struct AdminAction {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    // The instruction's name, e.g. "set_pricing"
+    pub action: String,
+}
+
+/// Emitted when the admin proposes a successor, or withdraws the proposal
+#[event]
+pub struct AdminProposed {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+/// Emitted when a proposed admin takes over
+#[event]
+pub struct AdminAccepted {
+    pub config: Pubkey,
+    pub previous_admin: Pubkey,
+    pub admin: Pubkey,
+}
+
+/// Emitted by every `process_payment_sol`; amounts are in lamports
+#[event]
+pub struct SolPaymentProcessed {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub insurance_amount: u64,
+    // Parked in the buyback escrow for a later buyback-and-burn
+    pub buyback_amount: u64,
+    pub new_expires_at: i64,
+}
+
+/// Emitted when escrowed SOL is swapped for SAKURA and burned
+#[event]
+pub struct BuybackBurned {
+    pub config: Pubkey,
+    pub sol_spent: u64,
+    pub burned: u64,
+}
+
+/// Emitted when the admin schedules or calls off the router's sunset
+#[event]
+pub struct SunsetScheduled {
+    pub config: Pubkey,
+    pub successor_program: Pubkey,
+    // Zero when the sunset was called off
+    pub sunset_at: i64,
+}
+
+/// Emitted just before a payment fails with `ProgramSunset`, so wallets can
+/// send the payer on to the successor
+#[event]
+pub struct ProgramSunsetNotice {
+    pub successor_program: Pubkey,
+    pub sunset_at: i64,
+}
+
+/// Emitted just before a payment check fails. Failed transactions and
+/// simulations still carry their logs, so the event survives the revert.
+/// `expected` is the bound the check required: the price for an underpayment,
+/// the amount for a low balance, the clock for an expiry, the lowest nonce
+/// still accepted for a replayed quote. `actual` is what was provided.
+#[event]
+pub struct PaymentDiagnostic {
+    pub error_code: u32,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum BurnMode {
+    FullBurn,
+    // `staking_bps` of the burn share rewards stakers instead of being burned
+    SplitWithStaking { staking_bps: u16 },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SubscriptionState {
+    Active,
+    // Past expiry but inside the instance's grace period
+    Grace,
+    Expired,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum ProductKind {
+    Subscription,
+    OneTime,
+    // Extends its own entitlement, and needs an active router subscription
+    AddOn,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ComplianceMode {
+    Disabled,
+    Ed25519Attestation,
+    // An attestor-signed memo must precede the payment in the same transaction
+    MemoAttestation,
+}
+
 #[error_code]
 pub enum ErrorCode {
     #[msg("Invalid split percentages, must sum to 10000 BPS")]
@@ -129,7 +10863,7 @@ pub enum ErrorCode {
     InvalidAmount,
     #[msg("Invalid user token account owner")]
     InvalidOwner,
-    #[msg("Invalid token mint, must be the official SAKURA_MINT")]
+    #[msg("Invalid token mint, must be the router instance mint")]
     InvalidMint,
     #[msg("Invalid insurance vault, must match the designated Percolator vault")]
     InvalidVault,
@@ -139,4 +10873,238 @@ pub enum ErrorCode {
     InvalidVaultOwner,
     #[msg("Invalid insurance vault authority")]
     InvalidVaultAuthority,
+    #[msg("Signer is not authorized for this action")]
+    Unauthorized,
+    #[msg("Compliance mode requires a non-default attestor key")]
+    InvalidAttestor,
+    #[msg("Missing ed25519 attestation over the payer and amount")]
+    MissingAttestation,
+    #[msg("Payment memo exceeds the maximum length")]
+    MemoTooLong,
+    #[msg("Memo program account is required when a memo is supplied")]
+    MissingMemoProgram,
+    #[msg("Delegate allowance must cover at least one period")]
+    InsufficientAllowance,
+    #[msg("Invalid stream account or stream program")]
+    InvalidStream,
+    #[msg("Subscription is not yet due for renewal")]
+    RenewalNotDue,
+    #[msg("Liquid-staking token payments are disabled")]
+    LstPaymentsDisabled,
+    #[msg("Liquid-staking token is not supported")]
+    UnsupportedLst,
+    #[msg("Invalid exchange rate account for this liquid-staking token")]
+    InvalidRateAccount,
+    #[msg("Liquid-staking exchange rate has not been updated this epoch")]
+    StaleExchangeRate,
+    #[msg("Payment is below the required price")]
+    Underpayment,
+    #[msg("Subscription duration must be positive")]
+    InvalidDuration,
+    #[msg("Sponsor pool balance does not cover a draw")]
+    InsufficientPoolBalance,
+    #[msg("Sponsorship has already been claimed by this wallet")]
+    SponsorshipClaimed,
+    #[msg("Promo discount must not exceed 10000 BPS")]
+    InvalidPromo,
+    #[msg("Missing attestor-signed memo before the payment")]
+    MissingMemoAttestation,
+    #[msg("Token account has CPI guard enabled; approve the router delegate and use process_delegated_payment")]
+    CpiGuardEnabled,
+    #[msg("Invalid or out-of-order stats shard")]
+    InvalidStatsShard,
+    #[msg("Batch entries must be account triples, at most MAX_CRANK_BATCH of them")]
+    InvalidBatch,
+    #[msg("The mint account is required when part of the payment is burned")]
+    MissingMint,
+    #[msg("This instance requires a receipt account on every payment")]
+    MissingReceipt,
+    #[msg("The payer's stats shard is required outside light mode")]
+    MissingStatsShard,
+    #[msg("Receipt belongs to another instance")]
+    InvalidReceipt,
+    #[msg("Receipt is still inside its retention period")]
+    ReceiptNotArchivable,
+    #[msg("Receipt history is full")]
+    ReceiptHistoryFull,
+    #[msg("Registry page belongs to another registry or is out of order")]
+    InvalidRegistryPage,
+    #[msg("Registry page is full")]
+    RegistryPageFull,
+    #[msg("Registry entry not found")]
+    RegistryEntryNotFound,
+    #[msg("Too many registry entries for one instruction")]
+    RegistryChunkTooLarge,
+    #[msg("An org account needs two distinct signers")]
+    DuplicateOrgSigner,
+    #[msg("Subscription has expired")]
+    SubscriptionExpired,
+    #[msg("Subscription has been revoked")]
+    SubscriptionRevoked,
+    #[msg("The payer's user stats are required outside light mode")]
+    MissingUserStats,
+    #[msg("Loyalty thresholds must not decrease from Bronze to Gold")]
+    InvalidLoyaltyThresholds,
+    #[msg("Loyalty tier is below the required tier")]
+    LoyaltyTierTooLow,
+    #[msg("Lifetime burn is below the badge threshold")]
+    BadgeThresholdNotMet,
+    #[msg("Split promo must be a valid split over a future, non-empty window")]
+    InvalidSplitPromo,
+    #[msg("Every pass in this batch has been assigned")]
+    NoPassesRemaining,
+    #[msg("This instance prices payments by signed quote")]
+    MissingQuote,
+    #[msg("This instance does not accept price quotes")]
+    QuotingDisabled,
+    #[msg("Price quote has expired")]
+    QuoteExpired,
+    #[msg("Price quote nonce has already been used")]
+    QuoteReplayed,
+    #[msg("Quoted payments record their nonce in user stats and can't use light mode")]
+    QuoteRequiresUserStats,
+    #[msg("This instance requires a jurisdiction attestation")]
+    MissingJurisdictionAttestation,
+    #[msg("Jurisdiction attestation was not issued by the current attestor")]
+    InvalidJurisdictionAttestation,
+    #[msg("Jurisdiction attestation has expired")]
+    JurisdictionAttestationExpired,
+    #[msg("Token account is frozen")]
+    TokenAccountFrozen,
+    #[msg("Token account balance is below the payment amount")]
+    InsufficientBalance,
+    #[msg("NFT discounts need the discount, NFT token account and metadata together")]
+    IncompleteNftDiscount,
+    #[msg("NFT is not a verified member of the discounted collection")]
+    InvalidNftDiscount,
+    #[msg("Invoice belongs to another service")]
+    InvalidInvoice,
+    #[msg("Invoice has already been paid")]
+    InvoiceAlreadyPaid,
+    #[msg("Recurring invoice can't be paid more than one period ahead")]
+    InvoiceNotDue,
+    #[msg("Product is not on sale")]
+    ProductInactive,
+    #[msg("Account required by this product kind wasn't provided")]
+    MissingProductAccount,
+    #[msg("Invalid mint migration")]
+    InvalidMintMigration,
+    #[msg("Mint migration cutover hasn't been reached")]
+    MintMigrationNotDue,
+    #[msg("Mint migration cutover has passed; complete the migration first")]
+    MintMigrationPending,
+    #[msg("Subscription isn't inside the expiry notice window")]
+    NotExpiringSoon,
+    #[msg("An identical outbox entry is already queued")]
+    AlreadyQueued,
+    #[msg("Acknowledged sequence must be pending")]
+    InvalidOutboxAck,
+    #[msg("Listing belongs to another seller")]
+    InvalidListing,
+    #[msg("Compressed receipts need every receipt tree account")]
+    MissingReceiptTreeAccounts,
+    #[msg("Account doesn't match the instance receipt tree")]
+    InvalidReceiptTree,
+    #[msg("Plan is not on sale")]
+    PlanInactive,
+    #[msg("Plans can't be paid with a signed quote")]
+    QuotedPlan,
+    #[msg("Invalid referrer or referral share")]
+    InvalidReferral,
+    #[msg("Auto-renew doesn't match its plan or bounty settings")]
+    InvalidAutoRenew,
+    #[msg("No auto-renewals left")]
+    AutoRenewExhausted,
+    #[msg("Payments are paused")]
+    ProgramPaused,
+    #[msg("Invalid routing table or destinations")]
+    InvalidRoutingTable,
+    #[msg("Promo codes can't be combined with a signed quote")]
+    QuotedPromo,
+    #[msg("Promo code has expired")]
+    PromoExpired,
+    #[msg("Promo code has no uses left")]
+    PromoExhausted,
+    #[msg("USD plans need a Pyth price update")]
+    MissingPriceUpdate,
+    #[msg("Price update isn't a verified update for the instance feed")]
+    InvalidPriceUpdate,
+    #[msg("Price update is too old")]
+    StalePriceUpdate,
+    #[msg("Price update confidence interval is too wide")]
+    PriceConfidenceTooWide,
+    #[msg("Soulbound receipts need every soulbound account")]
+    MissingSoulboundAccounts,
+    #[msg("Account isn't the holder's soulbound receipt account")]
+    InvalidSoulboundAccount,
+    #[msg("Subscription is still active")]
+    SubscriptionStillActive,
+    #[msg("Pending burn is below the flush threshold")]
+    BelowFlushThreshold,
+    #[msg("Slab is unknown, deactivated or its registry is missing")]
+    InvalidSlab,
+    #[msg("Subscription already has the current layout")]
+    SubscriptionAlreadyMigrated,
+    #[msg("Growing this subscription needs a payer for the extra rent")]
+    MigrationPayerRequired,
+    #[msg("Payment would prepay past the instance's cap")]
+    PrepaidLimitExceeded,
+    #[msg("Free trials are off for this instance")]
+    TrialsDisabled,
+    #[msg("Free trials are only for wallets that were never subscribed")]
+    TrialUnavailable,
+    #[msg("The insurance escrow must be swept before it is turned off")]
+    EscrowNotEmpty,
+    #[msg("Seat count must be nonzero and cover every current member")]
+    InvalidSeatCount,
+    #[msg("Every purchased seat is already taken")]
+    SeatsFull,
+    #[msg("Organization seats must use the organization's plan, priced in tokens")]
+    InvalidOrgPlan,
+    #[msg("Pay-as-you-go streams are off for this instance")]
+    StreamsDisabled,
+    #[msg("The stream can't cover another settle period")]
+    StreamBalanceTooLow,
+    #[msg(
+        "The calling program isn't allowed to pay through CPI, or didn't sign as its CPI authority"
+    )]
+    InvalidCpiCaller,
+    #[msg("Every CPI caller slot is taken")]
+    CpiCallersFull,
+    #[msg(
+        "Subscriptions can only move to another wallet, and only the named recipient can accept"
+    )]
+    InvalidTransfer,
+    #[msg("SOL payments are disabled")]
+    SolPaymentsDisabled,
+    #[msg("Buyback swap spent too much or returned too little")]
+    BuybackSlippage,
+    #[msg("A payment reference and its account must be passed together")]
+    MissingPaymentReference,
+    #[msg("This reference has already been paid")]
+    DuplicateReference,
+    #[msg("Payment is below the instance minimum")]
+    PaymentBelowMinimum,
+    #[msg("Too soon after this subscription's last payment")]
+    PaymentRateLimited,
+    #[msg("A sunset needs a successor program other than this one")]
+    InvalidSunset,
+    #[msg("This router has been sunset; pay through the successor program")]
+    ProgramSunset,
+    #[msg("Staking share must be between 1 and 10000 bps")]
+    InvalidBurnMode,
+    #[msg("The burn mode needs the staking vault")]
+    MissingStakingVault,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("A receipt account is numbered from the payer's stats, which light mode skips")]
+    ReceiptRequiresUserStats,
+    #[msg("This payment's insurance share isn't in the escrow to refund")]
+    RefundNotEscrowed,
+    #[msg("Refund exceeds the payment's unrefunded insurance share")]
+    RefundExceedsPayment,
+    #[msg("Config already has the reserved tail")]
+    ConfigAlreadyMigrated,
+    #[msg("Devnet and localnet need instance #0's mint and insurance vault")]
+    InstanceVaultRequired,
 }
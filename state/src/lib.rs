@@ -0,0 +1,1533 @@
+//! Dependency-free account layouts for the Sakura Fee Router.
+//!
+//! Decodes router accounts straight from their raw bytes so indexers, Geyser
+//! plugins and light clients can validate subscriptions without Anchor.
+//! Layouts mirror the `#[account]` structs in the program: an 8-byte Anchor
+//! discriminator followed by the Borsh-encoded fields.
+
+#![no_std]
+
+pub type Pubkey = [u8; 32];
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A subscription lives at the PDA of `[SUBSCRIPTION_SEED, config, user]`
+/// under [`PROGRAM_ID`]; programs with the router as a dependency can call
+/// `sakura_fee_router::subscription_address` instead
+pub const SUBSCRIPTION_SEED: &[u8] = b"subscription";
+
+/// Sakura Fee Router program id (FNoE2JUhn981hBDyBMvWJYkw9DThhtYwWoPbw6wgz1rg), as raw bytes
+pub const PROGRAM_ID: Pubkey = [
+    0xd5, 0x99, 0x14, 0x87, 0xbe, 0x9e, 0x02, 0x8b, 0x46, 0xdb, 0x38, 0xea, 0x1c, 0xa2, 0x28, 0xa4,
+    0xca, 0xf3, 0x5d, 0x56, 0x89, 0xf4, 0xee, 0xbb, 0x71, 0xee, 0xa1, 0xec, 0xd4, 0xd1, 0x7f, 0x99,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The account is shorter than the layout
+    TooShort,
+    /// The account holds a different account type
+    WrongDiscriminator,
+    /// An enum tag or bool byte is out of range
+    InvalidValue,
+}
+
+/// Little-endian cursor over an account's bytes
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], discriminator: [u8; 8]) -> Result<Self, DecodeError> {
+        let mut reader = Self { data };
+        if reader.take::<8>()? != discriminator {
+            return Err(DecodeError::WrongDiscriminator);
+        }
+        Ok(reader)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        if self.data.len() < N {
+            return Err(DecodeError::TooShort);
+        }
+        let (head, rest) = self.data.split_at(N);
+        self.data = rest;
+        let mut out = [0u8; N];
+        out.copy_from_slice(head);
+        Ok(out)
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey, DecodeError> {
+        self.take::<32>()
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, DecodeError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::InvalidValue),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn option_u64(&mut self) -> Result<Option<u64>, DecodeError> {
+        match self.bool()? {
+            true => Ok(Some(self.u64()?)),
+            false => Ok(None),
+        }
+    }
+
+    fn i64(&mut self) -> Result<i64, DecodeError> {
+        Ok(i64::from_le_bytes(self.take()?))
+    }
+
+    /// A Borsh string: u32 length prefix, then UTF-8 bytes borrowed from the account
+    fn str(&mut self) -> Result<&'a str, DecodeError> {
+        let len = self.u32()? as usize;
+        if self.data.len() < len {
+            return Err(DecodeError::TooShort);
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        core::str::from_utf8(head).map_err(|_| DecodeError::InvalidValue)
+    }
+}
+
+/// Packed subscription; the owner is implied by the PDA seeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subscription {
+    pub version: u8,
+    // Bits 0-19: expiry in whole days since the unix epoch, 20-21: status,
+    // 22-25: tier, 26-31: flags
+    pub packed: u32,
+    pub payment_count: u32,
+    pub last_payment_slot: u64,
+}
+
+impl Subscription {
+    pub const DISCRIMINATOR: [u8; 8] = [86, 65, 12, 166, 144, 147, 252, 224];
+
+    pub const STATUS_ACTIVE: u8 = 0;
+    pub const STATUS_REVOKED: u8 = 1;
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            version: r.u8()?,
+            packed: u32::from_le_bytes(r.take()?),
+            payment_count: r.u32()?,
+            last_payment_slot: r.u64()?,
+        })
+    }
+
+    pub fn expires_at(&self) -> i64 {
+        (self.packed & 0xF_FFFF) as i64 * SECONDS_PER_DAY
+    }
+
+    pub fn status(&self) -> u8 {
+        ((self.packed >> 20) & 0b11) as u8
+    }
+
+    pub fn tier(&self) -> u8 {
+        ((self.packed >> 22) & 0b1111) as u8
+    }
+
+    pub fn flags(&self) -> u8 {
+        ((self.packed >> 26) & 0b11_1111) as u8
+    }
+
+    /// Whether the subscription is not revoked and still paid up at unix time
+    /// `now`, allowing `grace_seconds` past its expiry
+    pub fn is_active(&self, now: i64, grace_seconds: i64) -> bool {
+        self.status() != Self::STATUS_REVOKED
+            && is_active(self.expires_at().saturating_add(grace_seconds.max(0)), now)
+    }
+
+    /// Where the subscription stands at unix time `now` given the instance's
+    /// `grace_period`, matching the program's `Subscription::state`
+    pub fn state(&self, now: i64, grace_period: i64) -> SubscriptionState {
+        if self.status() == Self::STATUS_REVOKED || !self.is_active(now, grace_period) {
+            SubscriptionState::Expired
+        } else if is_active(self.expires_at(), now) {
+            SubscriptionState::Active
+        } else {
+            SubscriptionState::Grace
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionState {
+    Active,
+    /// Past expiry but inside the instance's grace period
+    Grace,
+    Expired,
+}
+
+/// Subscription layout from before packing, until `migrate_subscription` runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegacySubscription {
+    pub user: Pubkey,
+    pub expires_at: i64,
+}
+
+impl LegacySubscription {
+    pub const DISCRIMINATOR: [u8; 8] = [64, 7, 26, 135, 102, 132, 98, 33];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            user: r.pubkey()?,
+            expires_at: r.i64()?,
+        })
+    }
+
+    pub fn is_active(&self, now: i64) -> bool {
+        is_active(self.expires_at, now)
+    }
+}
+
+/// The router's active-subscription rule: access lasts until, not through, `expires_at`
+pub fn is_active(expires_at: i64, now: i64) -> bool {
+    expires_at > now
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouterRegistry {
+    pub instance_count: u64,
+    pub bump: u8,
+}
+
+impl RouterRegistry {
+    pub const DISCRIMINATOR: [u8; 8] = [189, 40, 209, 121, 190, 170, 25, 122];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            instance_count: r.u64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComplianceMode {
+    Disabled,
+    Ed25519Attestation,
+    MemoAttestation,
+}
+
+impl ComplianceMode {
+    fn from_tag(tag: u8) -> Result<Self, DecodeError> {
+        match tag {
+            0 => Ok(Self::Disabled),
+            1 => Ok(Self::Ed25519Attestation),
+            2 => Ok(Self::MemoAttestation),
+            _ => Err(DecodeError::InvalidValue),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LoyaltyTier {
+    None,
+    Bronze,
+    Silver,
+    Gold,
+}
+
+impl LoyaltyTier {
+    fn from_tag(tag: u8) -> Result<Self, DecodeError> {
+        match tag {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Bronze),
+            2 => Ok(Self::Silver),
+            3 => Ok(Self::Gold),
+            _ => Err(DecodeError::InvalidValue),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BurnMode {
+    FullBurn,
+    SplitWithStaking { staking_bps: u16 },
+}
+
+impl BurnMode {
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        match r.u8()? {
+            0 => Ok(Self::FullBurn),
+            1 => Ok(Self::SplitWithStaking {
+                staking_bps: r.u16()?,
+            }),
+            _ => Err(DecodeError::InvalidValue),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProductKind {
+    Subscription,
+    OneTime,
+    AddOn,
+}
+
+impl ProductKind {
+    fn from_tag(tag: u8) -> Result<Self, DecodeError> {
+        match tag {
+            0 => Ok(Self::Subscription),
+            1 => Ok(Self::OneTime),
+            2 => Ok(Self::AddOn),
+            _ => Err(DecodeError::InvalidValue),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub id: u64,
+    pub admin: Pubkey,
+    pub operator: Pubkey,
+    pub mint: Pubkey,
+    pub insurance_vault: Pubkey,
+    pub vault_authority: Pubkey,
+    pub insurance_bps: u16,
+    pub burn_bps: u16,
+    pub subscription_duration: i64,
+    pub compliance_mode: ComplianceMode,
+    pub attestor: Pubkey,
+    pub lst_usd_price: u64,
+    pub price: u64,
+    pub promo_bps: u16,
+    pub promo_ends_at: i64,
+    pub require_receipts: bool,
+    pub loyalty_thresholds: [u64; 3],
+    pub split_promo_insurance_bps: u16,
+    pub split_promo_burn_bps: u16,
+    pub split_promo_starts_at: i64,
+    pub split_promo_ends_at: i64,
+    pub quote_authority: Pubkey,
+    pub require_jurisdiction_attestation: bool,
+    pub jurisdiction_attestor: Pubkey,
+    pub successor_mint: Pubkey,
+    pub successor_insurance_vault: Pubkey,
+    pub mint_cutover_at: i64,
+    pub curve_burn_step: u64,
+    pub curve_step_bps: u16,
+    pub curve_max_bps: u16,
+    pub curve_discount_bps: u16,
+    pub resale_royalty_bps: u16,
+    pub referral_bps: u16,
+    pub renewal_bounty_bps: u16,
+    pub paused: bool,
+    pub use_routing_table: bool,
+    pub pyth_feed_id: [u8; 32],
+    pub max_oracle_age: i64,
+    pub max_oracle_conf_bps: u16,
+    pub burn_flush_threshold: u64,
+    pub pending_burn_vault: Pubkey,
+    pub max_prepaid_seconds: i64,
+    pub clamp_prepaid: bool,
+    pub trial_duration: i64,
+    pub stream_rate: u64,
+    pub cpi_callers: [Pubkey; 4],
+    pub insurance_escrow: Pubkey,
+    pub pending_admin: Pubkey,
+    pub grace_period: i64,
+    pub sol_price_lamports: u64,
+    pub sol_insurance_vault: Pubkey,
+    pub min_payment_amount: u64,
+    pub min_payment_interval_slots: u64,
+    pub successor_program: Pubkey,
+    pub sunset_at: i64,
+    pub burn_mode: BurnMode,
+    pub staking_vault: Pubkey,
+    pub bump: u8,
+    pub sol_usd_feed_id: [u8; 32],
+    pub escrow_swept_at: i64,
+}
+
+impl Config {
+    pub const DISCRIMINATOR: [u8; 8] = [155, 12, 170, 224, 30, 250, 204, 130];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            id: r.u64()?,
+            admin: r.pubkey()?,
+            operator: r.pubkey()?,
+            mint: r.pubkey()?,
+            insurance_vault: r.pubkey()?,
+            vault_authority: r.pubkey()?,
+            insurance_bps: r.u16()?,
+            burn_bps: r.u16()?,
+            subscription_duration: r.i64()?,
+            compliance_mode: ComplianceMode::from_tag(r.u8()?)?,
+            attestor: r.pubkey()?,
+            lst_usd_price: r.u64()?,
+            price: r.u64()?,
+            promo_bps: r.u16()?,
+            promo_ends_at: r.i64()?,
+            require_receipts: r.bool()?,
+            loyalty_thresholds: [r.u64()?, r.u64()?, r.u64()?],
+            split_promo_insurance_bps: r.u16()?,
+            split_promo_burn_bps: r.u16()?,
+            split_promo_starts_at: r.i64()?,
+            split_promo_ends_at: r.i64()?,
+            quote_authority: r.pubkey()?,
+            require_jurisdiction_attestation: r.bool()?,
+            jurisdiction_attestor: r.pubkey()?,
+            successor_mint: r.pubkey()?,
+            successor_insurance_vault: r.pubkey()?,
+            mint_cutover_at: r.i64()?,
+            curve_burn_step: r.u64()?,
+            curve_step_bps: r.u16()?,
+            curve_max_bps: r.u16()?,
+            curve_discount_bps: r.u16()?,
+            resale_royalty_bps: r.u16()?,
+            referral_bps: r.u16()?,
+            renewal_bounty_bps: r.u16()?,
+            paused: r.bool()?,
+            use_routing_table: r.bool()?,
+            pyth_feed_id: r.take::<32>()?,
+            max_oracle_age: r.i64()?,
+            max_oracle_conf_bps: r.u16()?,
+            burn_flush_threshold: r.u64()?,
+            pending_burn_vault: r.pubkey()?,
+            max_prepaid_seconds: r.i64()?,
+            clamp_prepaid: r.bool()?,
+            trial_duration: r.i64()?,
+            stream_rate: r.u64()?,
+            cpi_callers: [r.pubkey()?, r.pubkey()?, r.pubkey()?, r.pubkey()?],
+            insurance_escrow: r.pubkey()?,
+            pending_admin: r.pubkey()?,
+            grace_period: r.i64()?,
+            sol_price_lamports: r.u64()?,
+            sol_insurance_vault: r.pubkey()?,
+            min_payment_amount: r.u64()?,
+            min_payment_interval_slots: r.u64()?,
+            successor_program: r.pubkey()?,
+            sunset_at: r.i64()?,
+            burn_mode: BurnMode::read(&mut r)?,
+            staking_vault: r.pubkey()?,
+            bump: r.u8()?,
+            // Past the original layout; zero until `migrate_config` grows the account
+            sol_usd_feed_id: r.take::<32>().unwrap_or_default(),
+            escrow_swept_at: r.i64().unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceFeed {
+    pub config: Pubkey,
+    pub price: u64,
+    pub effective_price: u64,
+    pub promo_bps: u16,
+    pub promo_ends_at: i64,
+    pub insurance_bps: u16,
+    pub burn_bps: u16,
+    pub subscription_duration: i64,
+    pub lst_usd_price: u64,
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+impl PriceFeed {
+    pub const DISCRIMINATOR: [u8; 8] = [189, 103, 252, 23, 152, 35, 243, 156];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            price: r.u64()?,
+            effective_price: r.u64()?,
+            promo_bps: r.u16()?,
+            promo_ends_at: r.i64()?,
+            insurance_bps: r.u16()?,
+            burn_bps: r.u16()?,
+            subscription_duration: r.i64()?,
+            lst_usd_price: r.u64()?,
+            updated_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamFunding {
+    pub user: Pubkey,
+    pub stream: Pubkey,
+    pub token_account: Pubkey,
+    pub amount_per_period: u64,
+    pub last_funded_at: i64,
+    pub bump: u8,
+}
+
+impl StreamFunding {
+    pub const DISCRIMINATOR: [u8; 8] = [144, 102, 68, 97, 147, 115, 39, 25];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            user: r.pubkey()?,
+            stream: r.pubkey()?,
+            token_account: r.pubkey()?,
+            amount_per_period: r.u64()?,
+            last_funded_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoRenew {
+    pub user: Pubkey,
+    pub token_account: Pubkey,
+    pub plan: Pubkey,
+    pub remaining_renewals: u32,
+    pub bump: u8,
+}
+
+impl AutoRenew {
+    pub const DISCRIMINATOR: [u8; 8] = [224, 142, 13, 60, 143, 58, 235, 123];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            user: r.pubkey()?,
+            token_account: r.pubkey()?,
+            plan: r.pubkey()?,
+            remaining_renewals: r.u32()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaymentStream {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub settled_at: i64,
+    pub bump: u8,
+}
+
+impl PaymentStream {
+    pub const DISCRIMINATOR: [u8; 8] = [124, 85, 193, 22, 93, 1, 143, 75];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            user: r.pubkey()?,
+            settled_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionTransfer {
+    pub config: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub initiated_at: i64,
+    pub bump: u8,
+}
+
+impl SubscriptionTransfer {
+    pub const DISCRIMINATOR: [u8; 8] = [57, 96, 160, 193, 114, 57, 229, 245];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            from: r.pubkey()?,
+            to: r.pubkey()?,
+            initiated_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trial {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub started_at: i64,
+    pub bump: u8,
+}
+
+impl Trial {
+    pub const DISCRIMINATOR: [u8; 8] = [168, 195, 240, 152, 137, 228, 54, 216];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            user: r.pubkey()?,
+            started_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SponsorPool {
+    pub config: Pubkey,
+    pub sponsor: Pubkey,
+    pub pool_id: u64,
+    pub amount_per_draw: u64,
+    pub draws_claimed: u64,
+    pub bump: u8,
+}
+
+impl SponsorPool {
+    pub const DISCRIMINATOR: [u8; 8] = [92, 119, 247, 66, 200, 25, 230, 54];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            sponsor: r.pubkey()?,
+            pool_id: r.u64()?,
+            amount_per_draw: r.u64()?,
+            draws_claimed: r.u64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SponsorAllowance {
+    pub pool: Pubkey,
+    pub wallet: Pubkey,
+    pub claimed: bool,
+    pub bump: u8,
+}
+
+impl SponsorAllowance {
+    pub const DISCRIMINATOR: [u8; 8] = [105, 139, 98, 247, 38, 82, 125, 24];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            pool: r.pubkey()?,
+            wallet: r.pubkey()?,
+            claimed: r.bool()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrgAccount {
+    pub config: Pubkey,
+    pub creator: Pubkey,
+    pub first_signer: Pubkey,
+    pub second_signer: Pubkey,
+    pub bump: u8,
+}
+
+impl OrgAccount {
+    pub const DISCRIMINATOR: [u8; 8] = [35, 98, 53, 119, 196, 72, 191, 3];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            creator: r.pubkey()?,
+            first_signer: r.pubkey()?,
+            second_signer: r.pubkey()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Organization {
+    pub config: Pubkey,
+    pub owner: Pubkey,
+    pub plan: Pubkey,
+    pub seats: u32,
+    pub members: u32,
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+impl Organization {
+    pub const DISCRIMINATOR: [u8; 8] = [145, 38, 152, 251, 91, 57, 118, 160];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            owner: r.pubkey()?,
+            plan: r.pubkey()?,
+            seats: r.u32()?,
+            members: r.u32()?,
+            expires_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+
+    pub fn is_active(&self, now: i64) -> bool {
+        is_active(self.expires_at, now)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Seat {
+    pub organization: Pubkey,
+    pub member: Pubkey,
+    pub added_at: i64,
+    pub bump: u8,
+}
+
+impl Seat {
+    pub const DISCRIMINATOR: [u8; 8] = [90, 228, 22, 90, 162, 86, 173, 26];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            organization: r.pubkey()?,
+            member: r.pubkey()?,
+            added_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Service {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub service_id: u64,
+    pub price: u64,
+    pub duration: i64,
+    pub bump: u8,
+}
+
+impl Service {
+    pub const DISCRIMINATOR: [u8; 8] = [144, 62, 76, 129, 167, 36, 151, 250];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            authority: r.pubkey()?,
+            service_id: r.u64()?,
+            price: r.u64()?,
+            duration: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GiftCard {
+    pub config: Pubkey,
+    pub mint: Pubkey,
+    pub price: u64,
+    pub duration: i64,
+    pub bump: u8,
+}
+
+impl GiftCard {
+    pub const DISCRIMINATOR: [u8; 8] = [54, 20, 8, 38, 75, 170, 131, 139];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            mint: r.pubkey()?,
+            price: r.u64()?,
+            duration: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassBatch {
+    pub config: Pubkey,
+    pub buyer: Pubkey,
+    pub batch_id: u64,
+    pub quantity: u32,
+    pub remaining: u32,
+    pub duration: i64,
+    pub bump: u8,
+}
+
+impl PassBatch {
+    pub const DISCRIMINATOR: [u8; 8] = [136, 110, 17, 194, 219, 30, 141, 196];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            buyer: r.pubkey()?,
+            batch_id: r.u64()?,
+            quantity: r.u32()?,
+            remaining: r.u32()?,
+            duration: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Incident {
+    pub config: Pubkey,
+    pub incident_id: u64,
+    pub stable_mint: Pubkey,
+    pub amount: u64,
+    pub extension: i64,
+    pub compensated: u64,
+    pub bump: u8,
+}
+
+impl Incident {
+    pub const DISCRIMINATOR: [u8; 8] = [144, 81, 144, 130, 200, 193, 26, 111];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            incident_id: r.u64()?,
+            stable_mint: r.pubkey()?,
+            amount: r.u64()?,
+            extension: r.i64()?,
+            compensated: r.u64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompensationClaim {
+    pub incident: Pubkey,
+    pub user: Pubkey,
+    pub claimed_at: i64,
+    pub bump: u8,
+}
+
+impl CompensationClaim {
+    pub const DISCRIMINATOR: [u8; 8] = [82, 187, 43, 169, 242, 113, 50, 169];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            incident: r.pubkey()?,
+            user: r.pubkey()?,
+            claimed_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+/// Entries held by a routing table
+pub const MAX_ROUTES: usize = 6;
+pub const MAX_SLABS: usize = 8;
+
+/// A routing table entry; the default key burns its share
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Route {
+    pub destination: Pubkey,
+    pub bps: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoutingTable {
+    pub config: Pubkey,
+    pub routes: [Route; MAX_ROUTES],
+    pub route_count: u8,
+    pub bump: u8,
+}
+
+impl RoutingTable {
+    pub const DISCRIMINATOR: [u8; 8] = [145, 127, 189, 194, 52, 215, 43, 79];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        let config = r.pubkey()?;
+        let mut routes = [Route::default(); MAX_ROUTES];
+        for route in routes.iter_mut() {
+            route.destination = r.pubkey()?;
+            route.bps = r.u16()?;
+        }
+        let table = Self {
+            config,
+            routes,
+            route_count: r.u8()?,
+            bump: r.u8()?,
+        };
+        if table.route_count as usize > MAX_ROUTES {
+            return Err(DecodeError::InvalidValue);
+        }
+        Ok(table)
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes[..self.route_count as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Slab {
+    pub slab_id: u64,
+    pub insurance_vault: Pubkey,
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlabRegistry {
+    pub config: Pubkey,
+    pub slabs: [Slab; MAX_SLABS],
+    pub slab_count: u8,
+    pub bump: u8,
+}
+
+impl SlabRegistry {
+    pub const DISCRIMINATOR: [u8; 8] = [115, 64, 68, 32, 29, 94, 129, 172];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        let config = r.pubkey()?;
+        let mut slabs = [Slab::default(); MAX_SLABS];
+        for slab in slabs.iter_mut() {
+            slab.slab_id = r.u64()?;
+            slab.insurance_vault = r.pubkey()?;
+            slab.active = r.bool()?;
+        }
+        let registry = Self {
+            config,
+            slabs,
+            slab_count: r.u8()?,
+            bump: r.u8()?,
+        };
+        if registry.slab_count as usize > MAX_SLABS {
+            return Err(DecodeError::InvalidValue);
+        }
+        Ok(registry)
+    }
+
+    pub fn slabs(&self) -> &[Slab] {
+        &self.slabs[..self.slab_count as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Promo {
+    pub config: Pubkey,
+    pub code_hash: [u8; 32],
+    pub discount_bps: u16,
+    pub remaining_uses: u32,
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+impl Promo {
+    pub const DISCRIMINATOR: [u8; 8] = [56, 91, 197, 41, 229, 168, 221, 54];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            code_hash: r.take::<32>()?,
+            discount_bps: r.u16()?,
+            remaining_uses: r.u32()?,
+            expires_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plan {
+    pub config: Pubkey,
+    pub plan_id: u64,
+    pub duration: i64,
+    pub price: u64,
+    pub usd_price: u64,
+    pub active: bool,
+    pub bump: u8,
+}
+
+impl Plan {
+    pub const DISCRIMINATOR: [u8; 8] = [161, 231, 251, 119, 2, 12, 162, 2];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            plan_id: r.u64()?,
+            duration: r.i64()?,
+            price: r.u64()?,
+            usd_price: r.u64()?,
+            active: r.bool()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Product {
+    pub config: Pubkey,
+    pub product_id: u64,
+    pub kind: ProductKind,
+    pub price: u64,
+    pub duration: i64,
+    pub insurance_bps: u16,
+    pub burn_bps: u16,
+    pub active: bool,
+    pub bump: u8,
+}
+
+impl Product {
+    pub const DISCRIMINATOR: [u8; 8] = [102, 76, 55, 251, 38, 73, 224, 229];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            product_id: r.u64()?,
+            kind: ProductKind::from_tag(r.u8()?)?,
+            price: r.u64()?,
+            duration: r.i64()?,
+            insurance_bps: r.u16()?,
+            burn_bps: r.u16()?,
+            active: r.bool()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Referrer {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub token_account: Pubkey,
+    pub referred_payments: u64,
+    pub total_earned: u64,
+    pub bump: u8,
+}
+
+impl Referrer {
+    pub const DISCRIMINATOR: [u8; 8] = [99, 150, 214, 66, 111, 120, 49, 126];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            authority: r.pubkey()?,
+            token_account: r.pubkey()?,
+            referred_payments: r.u64()?,
+            total_earned: r.u64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Listing {
+    pub config: Pubkey,
+    pub seller: Pubkey,
+    pub price: u64,
+    pub remaining: i64,
+    pub listed_at: i64,
+    pub bump: u8,
+}
+
+impl Listing {
+    pub const DISCRIMINATOR: [u8; 8] = [218, 32, 50, 73, 43, 134, 26, 58];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            seller: r.pubkey()?,
+            price: r.u64()?,
+            remaining: r.i64()?,
+            listed_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Invoice {
+    pub service: Pubkey,
+    pub payer: Pubkey,
+    pub invoice_id: u64,
+    pub amount: u64,
+    pub due_at: i64,
+    pub interval: i64,
+    pub payments: u32,
+    pub paid: bool,
+    pub bump: u8,
+}
+
+impl Invoice {
+    pub const DISCRIMINATOR: [u8; 8] = [51, 194, 250, 114, 6, 104, 18, 164];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            service: r.pubkey()?,
+            payer: r.pubkey()?,
+            invoice_id: r.u64()?,
+            amount: r.u64()?,
+            due_at: r.i64()?,
+            interval: r.i64()?,
+            payments: r.u32()?,
+            paid: r.bool()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub config: Pubkey,
+    pub payments: u64,
+    pub volume: u64,
+    pub insured: u64,
+    pub burned: u64,
+    pub unique_subscribers: u64,
+    pub updated_at: i64,
+    pub staked: u64,
+    pub bump: u8,
+}
+
+impl Stats {
+    pub const DISCRIMINATOR: [u8; 8] = [190, 125, 51, 63, 169, 197, 36, 238];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            payments: r.u64()?,
+            volume: r.u64()?,
+            insured: r.u64()?,
+            burned: r.u64()?,
+            unique_subscribers: r.u64()?,
+            updated_at: r.i64()?,
+            staked: r.u64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NftDiscount {
+    pub config: Pubkey,
+    pub collection: Pubkey,
+    pub discount_bps: u16,
+    pub bump: u8,
+}
+
+impl NftDiscount {
+    pub const DISCRIMINATOR: [u8; 8] = [117, 254, 180, 50, 181, 95, 117, 251];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            collection: r.pubkey()?,
+            discount_bps: r.u16()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JurisdictionAttestation {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub issuer: Pubkey,
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+impl JurisdictionAttestation {
+    pub const DISCRIMINATOR: [u8; 8] = [176, 101, 196, 54, 132, 135, 200, 30];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            user: r.pubkey()?,
+            issuer: r.pubkey()?,
+            expires_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+
+    pub fn is_valid(&self, attestor: &Pubkey, now: i64) -> bool {
+        self.issuer == *attestor && self.expires_at > now
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserStats {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub payments: u64,
+    pub lifetime_burned: u64,
+    pub tier: LoyaltyTier,
+    pub last_quote_nonce: u64,
+    pub bump: u8,
+}
+
+impl UserStats {
+    pub const DISCRIMINATOR: [u8; 8] = [176, 223, 136, 27, 122, 79, 32, 227];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            user: r.pubkey()?,
+            payments: r.u64()?,
+            lifetime_burned: r.u64()?,
+            tier: LoyaltyTier::from_tag(r.u8()?)?,
+            last_quote_nonce: r.u64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+/// Badge metadata is borrowed from the account data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Badge<'a> {
+    pub config: Pubkey,
+    pub badge_id: u8,
+    pub threshold: u64,
+    pub claimed: u64,
+    pub name: &'a str,
+    pub symbol: &'a str,
+    pub uri: &'a str,
+    pub bump: u8,
+}
+
+impl<'a> Badge<'a> {
+    pub const DISCRIMINATOR: [u8; 8] = [40, 127, 162, 181, 177, 154, 1, 48];
+
+    pub fn decode(data: &'a [u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            badge_id: r.u8()?,
+            threshold: r.u64()?,
+            claimed: r.u64()?,
+            name: r.str()?,
+            symbol: r.str()?,
+            uri: r.str()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiptTree<'a> {
+    pub config: Pubkey,
+    pub merkle_tree: Pubkey,
+    pub name: &'a str,
+    pub symbol: &'a str,
+    pub uri: &'a str,
+    pub minted: u64,
+    pub bump: u8,
+}
+
+impl<'a> ReceiptTree<'a> {
+    pub const DISCRIMINATOR: [u8; 8] = [229, 241, 21, 162, 193, 150, 129, 203];
+
+    pub fn decode(data: &'a [u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            merkle_tree: r.pubkey()?,
+            name: r.str()?,
+            symbol: r.str()?,
+            uri: r.str()?,
+            minted: r.u64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatsShard {
+    pub config: Pubkey,
+    pub index: u8,
+    pub payments: u64,
+    pub volume: u64,
+    pub insured: u64,
+    pub burned: u64,
+    pub subscribers: u64,
+    pub staked: u64,
+    pub bump: u8,
+}
+
+impl StatsShard {
+    pub const DISCRIMINATOR: [u8; 8] = [142, 95, 14, 158, 108, 216, 224, 249];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            index: r.u8()?,
+            payments: r.u64()?,
+            volume: r.u64()?,
+            insured: r.u64()?,
+            burned: r.u64()?,
+            subscribers: r.u64()?,
+            staked: r.u64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Receipt {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub subscriber: Pubkey,
+    pub payment_index: u64,
+    pub amount: u64,
+    pub insurance_amount: u64,
+    pub burn_amount: u64,
+    pub refunded: u64,
+    pub escrowed: bool,
+    pub plan_id: Option<u64>,
+    pub slab_id: Option<u64>,
+    pub reference: Option<[u8; 32]>,
+    pub duration: i64,
+    pub expires_at: i64,
+    pub paid_at: i64,
+    pub bump: u8,
+}
+
+impl Receipt {
+    pub const DISCRIMINATOR: [u8; 8] = [39, 154, 73, 106, 80, 102, 145, 153];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            user: r.pubkey()?,
+            subscriber: r.pubkey()?,
+            payment_index: r.u64()?,
+            amount: r.u64()?,
+            insurance_amount: r.u64()?,
+            burn_amount: r.u64()?,
+            refunded: r.u64()?,
+            escrowed: r.bool()?,
+            plan_id: r.option_u64()?,
+            slab_id: r.option_u64()?,
+            reference: match r.bool()? {
+                true => Some(r.take()?),
+                false => None,
+            },
+            duration: r.i64()?,
+            expires_at: r.i64()?,
+            paid_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+/// Depth of the receipt history Merkle tree
+pub const RECEIPT_HISTORY_DEPTH: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaymentReference {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub reference: [u8; 32],
+    pub amount: u64,
+    pub paid_at: i64,
+    pub bump: u8,
+}
+
+impl PaymentReference {
+    pub const DISCRIMINATOR: [u8; 8] = [54, 135, 178, 71, 171, 142, 69, 218];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        Ok(Self {
+            config: r.pubkey()?,
+            user: r.pubkey()?,
+            reference: r.take()?,
+            amount: r.u64()?,
+            paid_at: r.i64()?,
+            bump: r.u8()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiptHistory {
+    pub config: Pubkey,
+    pub count: u64,
+    pub root: [u8; 32],
+    pub frontier: [[u8; 32]; RECEIPT_HISTORY_DEPTH],
+    pub bump: u8,
+}
+
+impl ReceiptHistory {
+    pub const DISCRIMINATOR: [u8; 8] = [145, 44, 16, 55, 213, 175, 41, 109];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        let config = r.pubkey()?;
+        let count = r.u64()?;
+        let root = r.take()?;
+        let mut frontier = [[0u8; 32]; RECEIPT_HISTORY_DEPTH];
+        for node in frontier.iter_mut() {
+            *node = r.take()?;
+        }
+        Ok(Self {
+            config,
+            count,
+            root,
+            frontier,
+            bump: r.u8()?,
+        })
+    }
+}
+
+/// Entries per registry page
+pub const REGISTRY_PAGE_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RegistryEntry {
+    pub key: Pubkey,
+    pub value: u64,
+}
+
+/// Zero-copy in the program, so the layout is `repr(C)` rather than Borsh;
+/// every field is naturally aligned, so the two byte layouts coincide
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegistryPage {
+    pub config: Pubkey,
+    pub entries: [RegistryEntry; REGISTRY_PAGE_CAPACITY],
+    pub len: u16,
+    pub kind: u8,
+    pub page: u8,
+    pub bump: u8,
+}
+
+impl RegistryPage {
+    pub const DISCRIMINATOR: [u8; 8] = [190, 151, 207, 163, 226, 253, 16, 250];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        let config = r.pubkey()?;
+        let mut entries = [RegistryEntry::default(); REGISTRY_PAGE_CAPACITY];
+        for entry in entries.iter_mut() {
+            entry.key = r.pubkey()?;
+            entry.value = r.u64()?;
+        }
+        let page = Self {
+            config,
+            entries,
+            len: r.u16()?,
+            kind: r.u8()?,
+            page: r.u8()?,
+            bump: r.u8()?,
+        };
+        if page.len as usize > REGISTRY_PAGE_CAPACITY {
+            return Err(DecodeError::InvalidValue);
+        }
+        Ok(page)
+    }
+
+    pub fn entries(&self) -> &[RegistryEntry] {
+        &self.entries[..self.len as usize]
+    }
+}
+
+/// Entries held by the outbox ring
+pub const OUTBOX_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OutboxEntry {
+    pub seq: u64,
+    pub user: Pubkey,
+    pub value: u64,
+    pub queued_at: i64,
+    pub kind: u8,
+}
+
+impl OutboxEntry {
+    pub const EXPIRING_SOON: u8 = 0;
+    pub const PAYMENT_FAILED: u8 = 1;
+    pub const REFUND_ISSUED: u8 = 2;
+}
+
+/// Zero-copy in the program like [`RegistryPage`]; padding is explicit there
+/// and skipped here
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outbox {
+    pub config: Pubkey,
+    pub head: u64,
+    pub acked: u64,
+    pub dropped: u64,
+    pub entries: [OutboxEntry; OUTBOX_CAPACITY],
+    pub bump: u8,
+}
+
+impl Outbox {
+    pub const DISCRIMINATOR: [u8; 8] = [70, 215, 144, 9, 98, 225, 68, 204];
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data, Self::DISCRIMINATOR)?;
+        let config = r.pubkey()?;
+        let head = r.u64()?;
+        let acked = r.u64()?;
+        let dropped = r.u64()?;
+        let mut entries = [OutboxEntry::default(); OUTBOX_CAPACITY];
+        for entry in entries.iter_mut() {
+            entry.seq = r.u64()?;
+            entry.user = r.pubkey()?;
+            entry.value = r.u64()?;
+            entry.queued_at = r.i64()?;
+            entry.kind = r.u8()?;
+            r.take::<7>()?;
+        }
+        let outbox = Self {
+            config,
+            head,
+            acked,
+            dropped,
+            entries,
+            bump: r.u8()?,
+        };
+        if outbox.acked > outbox.head {
+            return Err(DecodeError::InvalidValue);
+        }
+        Ok(outbox)
+    }
+
+    /// Entries not yet acked, oldest first
+    pub fn pending(&self) -> impl Iterator<Item = &OutboxEntry> {
+        (self.acked..self.head).map(|seq| &self.entries[seq as usize % OUTBOX_CAPACITY])
+    }
+}
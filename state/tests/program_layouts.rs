@@ -0,0 +1,487 @@
+//! Decodes accounts serialized by the program itself, so a field added to an
+//! `#[account]` struct without its mirror here fails the build
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{AccountSerialize, Discriminator};
+use sakura_fee_router as program;
+use sakura_fee_router_state as state;
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+fn serialize<T: AccountSerialize>(account: &T) -> Vec<u8> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn program_id_matches() {
+    assert_eq!(state::PROGRAM_ID, program::ID.to_bytes());
+}
+
+#[test]
+fn discriminators_match() {
+    let pairs: &[(&[u8], &[u8])] = &[
+        (
+            &state::Subscription::DISCRIMINATOR,
+            program::Subscription::DISCRIMINATOR,
+        ),
+        (
+            &state::LegacySubscription::DISCRIMINATOR,
+            program::LegacySubscription::DISCRIMINATOR,
+        ),
+        (
+            &state::RouterRegistry::DISCRIMINATOR,
+            program::RouterRegistry::DISCRIMINATOR,
+        ),
+        (
+            &state::Config::DISCRIMINATOR,
+            program::Config::DISCRIMINATOR,
+        ),
+        (
+            &state::PriceFeed::DISCRIMINATOR,
+            program::PriceFeed::DISCRIMINATOR,
+        ),
+        (
+            &state::StreamFunding::DISCRIMINATOR,
+            program::StreamFunding::DISCRIMINATOR,
+        ),
+        (
+            &state::AutoRenew::DISCRIMINATOR,
+            program::AutoRenew::DISCRIMINATOR,
+        ),
+        (
+            &state::PaymentStream::DISCRIMINATOR,
+            program::PaymentStream::DISCRIMINATOR,
+        ),
+        (
+            &state::SubscriptionTransfer::DISCRIMINATOR,
+            program::SubscriptionTransfer::DISCRIMINATOR,
+        ),
+        (&state::Trial::DISCRIMINATOR, program::Trial::DISCRIMINATOR),
+        (
+            &state::SponsorPool::DISCRIMINATOR,
+            program::SponsorPool::DISCRIMINATOR,
+        ),
+        (
+            &state::SponsorAllowance::DISCRIMINATOR,
+            program::SponsorAllowance::DISCRIMINATOR,
+        ),
+        (
+            &state::OrgAccount::DISCRIMINATOR,
+            program::OrgAccount::DISCRIMINATOR,
+        ),
+        (
+            &state::Organization::DISCRIMINATOR,
+            program::Organization::DISCRIMINATOR,
+        ),
+        (&state::Seat::DISCRIMINATOR, program::Seat::DISCRIMINATOR),
+        (
+            &state::Service::DISCRIMINATOR,
+            program::Service::DISCRIMINATOR,
+        ),
+        (
+            &state::GiftCard::DISCRIMINATOR,
+            program::GiftCard::DISCRIMINATOR,
+        ),
+        (
+            &state::PassBatch::DISCRIMINATOR,
+            program::PassBatch::DISCRIMINATOR,
+        ),
+        (
+            &state::Incident::DISCRIMINATOR,
+            program::Incident::DISCRIMINATOR,
+        ),
+        (
+            &state::CompensationClaim::DISCRIMINATOR,
+            program::CompensationClaim::DISCRIMINATOR,
+        ),
+        (
+            &state::RoutingTable::DISCRIMINATOR,
+            program::RoutingTable::DISCRIMINATOR,
+        ),
+        (
+            &state::SlabRegistry::DISCRIMINATOR,
+            program::SlabRegistry::DISCRIMINATOR,
+        ),
+        (&state::Promo::DISCRIMINATOR, program::Promo::DISCRIMINATOR),
+        (&state::Plan::DISCRIMINATOR, program::Plan::DISCRIMINATOR),
+        (
+            &state::Product::DISCRIMINATOR,
+            program::Product::DISCRIMINATOR,
+        ),
+        (
+            &state::Referrer::DISCRIMINATOR,
+            program::Referrer::DISCRIMINATOR,
+        ),
+        (
+            &state::Listing::DISCRIMINATOR,
+            program::Listing::DISCRIMINATOR,
+        ),
+        (
+            &state::Invoice::DISCRIMINATOR,
+            program::Invoice::DISCRIMINATOR,
+        ),
+        (&state::Stats::DISCRIMINATOR, program::Stats::DISCRIMINATOR),
+        (
+            &state::NftDiscount::DISCRIMINATOR,
+            program::NftDiscount::DISCRIMINATOR,
+        ),
+        (
+            &state::JurisdictionAttestation::DISCRIMINATOR,
+            program::JurisdictionAttestation::DISCRIMINATOR,
+        ),
+        (
+            &state::UserStats::DISCRIMINATOR,
+            program::UserStats::DISCRIMINATOR,
+        ),
+        (&state::Badge::DISCRIMINATOR, program::Badge::DISCRIMINATOR),
+        (
+            &state::ReceiptTree::DISCRIMINATOR,
+            program::ReceiptTree::DISCRIMINATOR,
+        ),
+        (
+            &state::StatsShard::DISCRIMINATOR,
+            program::StatsShard::DISCRIMINATOR,
+        ),
+        (
+            &state::Receipt::DISCRIMINATOR,
+            program::Receipt::DISCRIMINATOR,
+        ),
+        (
+            &state::PaymentReference::DISCRIMINATOR,
+            program::PaymentReference::DISCRIMINATOR,
+        ),
+        (
+            &state::ReceiptHistory::DISCRIMINATOR,
+            program::ReceiptHistory::DISCRIMINATOR,
+        ),
+        (
+            &state::RegistryPage::DISCRIMINATOR,
+            <program::RegistryPage as Discriminator>::DISCRIMINATOR,
+        ),
+        (
+            &state::Outbox::DISCRIMINATOR,
+            <program::Outbox as Discriminator>::DISCRIMINATOR,
+        ),
+    ];
+    for (mirror, actual) in pairs {
+        assert_eq!(mirror, actual);
+    }
+}
+
+#[test]
+fn zero_copy_sizes_match() {
+    assert_eq!(
+        std::mem::size_of::<program::RegistryPage>(),
+        32 + 40 * state::REGISTRY_PAGE_CAPACITY + 8
+    );
+    assert_eq!(
+        std::mem::size_of::<program::Outbox>(),
+        32 + 24 + 64 * state::OUTBOX_CAPACITY + 8
+    );
+}
+
+#[test]
+fn subscription_round_trips() {
+    let mut subscription = program::Subscription {
+        version: program::Subscription::VERSION,
+        packed: 0,
+        payment_count: 7,
+        last_payment_slot: 11,
+    };
+    subscription.set_expires_at(1_700_000_001).unwrap();
+    subscription.set_tier(5);
+    subscription.set_status(2);
+    subscription.set_flags(63);
+
+    let decoded = state::Subscription::decode(&serialize(&subscription)).unwrap();
+    assert_eq!(decoded.expires_at(), subscription.expires_at());
+    assert_eq!(
+        (decoded.tier(), decoded.status(), decoded.flags()),
+        (5, 2, 63)
+    );
+    assert_eq!((decoded.payment_count, decoded.last_payment_slot), (7, 11));
+
+    let now = subscription.expires_at() + 10;
+    assert_eq!(decoded.state(now, 60), state::SubscriptionState::Grace);
+    assert_eq!(
+        subscription.state(now, 60),
+        program::SubscriptionState::Grace
+    );
+}
+
+#[test]
+fn config_round_trips() {
+    let config = program::Config {
+        id: 1,
+        admin: key(1),
+        operator: key(2),
+        mint: key(3),
+        insurance_vault: key(4),
+        vault_authority: key(5),
+        insurance_bps: 4000,
+        burn_bps: 6000,
+        subscription_duration: 5,
+        compliance_mode: program::ComplianceMode::MemoAttestation,
+        attestor: key(6),
+        lst_usd_price: 6,
+        price: 7,
+        promo_bps: 8,
+        promo_ends_at: 9,
+        require_receipts: true,
+        loyalty_thresholds: [1, 2, 3],
+        split_promo_insurance_bps: 10000,
+        split_promo_burn_bps: 0,
+        split_promo_starts_at: 11,
+        split_promo_ends_at: 12,
+        quote_authority: key(7),
+        require_jurisdiction_attestation: true,
+        jurisdiction_attestor: key(9),
+        successor_mint: key(5),
+        successor_insurance_vault: key(12),
+        mint_cutover_at: 77,
+        curve_burn_step: 1000,
+        curve_step_bps: 50,
+        curve_max_bps: 5000,
+        curve_discount_bps: 150,
+        resale_royalty_bps: 250,
+        referral_bps: 300,
+        renewal_bounty_bps: 100,
+        paused: true,
+        use_routing_table: true,
+        pyth_feed_id: [3; 32],
+        max_oracle_age: 60,
+        max_oracle_conf_bps: 200,
+        burn_flush_threshold: 1234,
+        pending_burn_vault: key(4),
+        max_prepaid_seconds: 999,
+        clamp_prepaid: true,
+        trial_duration: 604800,
+        stream_rate: 42,
+        cpi_callers: [key(1), Pubkey::default(), Pubkey::default(), key(2)],
+        insurance_escrow: key(6),
+        pending_admin: key(8),
+        grace_period: 3_600,
+        sol_price_lamports: 5_000,
+        sol_insurance_vault: key(9),
+        min_payment_amount: 1_000,
+        min_payment_interval_slots: 25,
+        successor_program: key(10),
+        sunset_at: 1_800_000_000,
+        burn_mode: program::BurnMode::SplitWithStaking { staking_bps: 2_500 },
+        staking_vault: key(11),
+        bump: 253,
+        sol_usd_feed_id: [13; 32],
+        escrow_swept_at: 88,
+    };
+
+    let data = serialize(&config);
+    let decoded = state::Config::decode(&data).unwrap();
+    assert_eq!(
+        (decoded.id, decoded.admin, decoded.mint),
+        (1, [1; 32], [3; 32])
+    );
+    assert_eq!(
+        decoded.compliance_mode,
+        state::ComplianceMode::MemoAttestation
+    );
+    assert_eq!((decoded.insurance_bps, decoded.burn_bps), (4000, 6000));
+    assert_eq!(decoded.loyalty_thresholds, [1, 2, 3]);
+    assert_eq!(
+        (decoded.quote_authority, decoded.jurisdiction_attestor),
+        ([7; 32], [9; 32])
+    );
+    assert_eq!(
+        (decoded.successor_insurance_vault, decoded.mint_cutover_at),
+        ([12; 32], 77)
+    );
+    assert_eq!(
+        (decoded.curve_burn_step, decoded.curve_discount_bps),
+        (1000, 150)
+    );
+    assert_eq!((decoded.paused, decoded.use_routing_table), (true, true));
+    assert_eq!(
+        (
+            decoded.pyth_feed_id,
+            decoded.max_oracle_age,
+            decoded.max_oracle_conf_bps
+        ),
+        ([3; 32], 60, 200)
+    );
+    assert_eq!(
+        (decoded.max_prepaid_seconds, decoded.clamp_prepaid),
+        (999, true)
+    );
+    assert_eq!(decoded.cpi_callers, [[1; 32], [0; 32], [0; 32], [2; 32]]);
+    assert_eq!(
+        (decoded.pending_admin, decoded.grace_period),
+        ([8; 32], 3_600)
+    );
+    assert_eq!(
+        (
+            decoded.min_payment_amount,
+            decoded.min_payment_interval_slots
+        ),
+        (1_000, 25)
+    );
+    assert_eq!(
+        (decoded.successor_program, decoded.sunset_at),
+        ([10; 32], 1_800_000_000)
+    );
+    assert_eq!(
+        decoded.burn_mode,
+        state::BurnMode::SplitWithStaking { staking_bps: 2_500 }
+    );
+    assert_eq!((decoded.staking_vault, decoded.bump), ([11; 32], 253));
+    assert_eq!(
+        (decoded.lst_usd_price, decoded.sol_usd_feed_id),
+        (6, [13; 32])
+    );
+    assert_eq!(decoded.escrow_swept_at, 88);
+
+    // A config from before the reserved tail ends at `bump`
+    let legacy = state::Config::decode(&data[..data.len() - 40]).unwrap();
+    assert_eq!((legacy.bump, legacy.sol_usd_feed_id), (253, [0; 32]));
+    assert_eq!(legacy.escrow_swept_at, 0);
+}
+
+#[test]
+fn default_config_decodes_with_reserved_tail() {
+    let config = program::Config::init_defaults(0, key(1), key(2), key(3), key(4), 255);
+    let mut data = serialize(&config);
+    assert!(data.len() < program::Config::SPACE);
+    data.resize(program::Config::SPACE, 0);
+
+    let decoded = state::Config::decode(&data).unwrap();
+    assert_eq!((decoded.admin, decoded.operator), ([1; 32], [1; 32]));
+    assert_eq!(
+        (decoded.insurance_bps, decoded.burn_bps),
+        (program::INSURANCE_BPS as u16, program::BURN_BPS as u16)
+    );
+    assert_eq!(decoded.burn_mode, state::BurnMode::FullBurn);
+    assert_eq!(decoded.bump, 255);
+}
+
+#[test]
+fn receipt_round_trips() {
+    let receipt = program::Receipt {
+        config: key(1),
+        user: key(2),
+        subscriber: key(8),
+        payment_index: 4,
+        amount: 1,
+        insurance_amount: 2,
+        burn_amount: 3,
+        refunded: 1,
+        escrowed: true,
+        plan_id: Some(9),
+        slab_id: None,
+        reference: Some([3; 32]),
+        duration: 86_400,
+        expires_at: 5,
+        paid_at: 6,
+        bump: 7,
+    };
+
+    let decoded = state::Receipt::decode(&serialize(&receipt)).unwrap();
+    assert_eq!(
+        (decoded.user, decoded.subscriber, decoded.payment_index),
+        ([2; 32], [8; 32], 4)
+    );
+    assert_eq!((decoded.refunded, decoded.duration), (1, 86_400));
+    assert!(decoded.escrowed);
+    assert_eq!(
+        (
+            decoded.amount,
+            decoded.insurance_amount,
+            decoded.burn_amount
+        ),
+        (1, 2, 3)
+    );
+    assert_eq!(
+        (decoded.plan_id, decoded.slab_id, decoded.reference),
+        (Some(9), None, Some([3; 32]))
+    );
+    assert_eq!(
+        (decoded.expires_at, decoded.paid_at, decoded.bump),
+        (5, 6, 7)
+    );
+}
+
+#[test]
+fn payer_stats_round_trip() {
+    let shard = program::StatsShard {
+        config: key(1),
+        index: 3,
+        payments: 4,
+        volume: 5,
+        insured: 6,
+        burned: 7,
+        subscribers: 8,
+        staked: 9,
+        bump: 10,
+    };
+    let decoded = state::StatsShard::decode(&serialize(&shard)).unwrap();
+    assert_eq!(
+        (
+            decoded.index,
+            decoded.payments,
+            decoded.volume,
+            decoded.insured
+        ),
+        (3, 4, 5, 6)
+    );
+    assert_eq!(
+        (
+            decoded.burned,
+            decoded.subscribers,
+            decoded.staked,
+            decoded.bump
+        ),
+        (7, 8, 9, 10)
+    );
+
+    let user_stats = program::UserStats {
+        config: key(1),
+        user: key(2),
+        payments: 3,
+        lifetime_burned: 4,
+        tier: program::LoyaltyTier::Silver,
+        last_quote_nonce: 5,
+        bump: 6,
+    };
+    let decoded = state::UserStats::decode(&serialize(&user_stats)).unwrap();
+    assert_eq!(
+        (decoded.payments, decoded.lifetime_burned, decoded.tier),
+        (3, 4, state::LoyaltyTier::Silver)
+    );
+    assert_eq!((decoded.last_quote_nonce, decoded.bump), (5, 6));
+}
+
+#[test]
+fn badge_round_trips() {
+    let badge = program::Badge {
+        config: key(1),
+        badge_id: 3,
+        threshold: 7,
+        claimed: 9,
+        name: "Burner".into(),
+        symbol: "SKB".into(),
+        uri: "https://example.com/badge.json".into(),
+        bump: 254,
+    };
+    let data = serialize(&badge);
+    let decoded = state::Badge::decode(&data).unwrap();
+    assert_eq!(
+        (decoded.badge_id, decoded.threshold, decoded.claimed),
+        (3, 7, 9)
+    );
+    assert_eq!(
+        (decoded.name, decoded.symbol, decoded.uri),
+        ("Burner", "SKB", "https://example.com/badge.json")
+    );
+    assert_eq!(decoded.bump, 254);
+}
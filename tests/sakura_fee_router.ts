@@ -7,9 +7,19 @@ import {
   createAccount,
   mintTo,
   getAccount,
+  getMint,
+  approve,
+  createAssociatedTokenAccount,
+  enableCpiGuard,
+  createWrappedNativeAccount,
+  getOrCreateAssociatedTokenAccount,
+  NATIVE_MINT,
+  reallocate,
+  ExtensionType,
   TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
 } from "@solana/spl-token";
-import { PublicKey, SystemProgram } from "@solana/web3.js";
+import { PublicKey, SystemProgram, SYSVAR_INSTRUCTIONS_PUBKEY } from "@solana/web3.js";
 
 describe("sakura_fee_router", () => {
   anchor.setProvider(anchor.AnchorProvider.env());
@@ -27,6 +37,241 @@ describe("sakura_fee_router", () => {
   const SAKURA_MINT = new PublicKey("EWiVNxCqNatzV2paBHyfKUwGLnk7WKs9uZTA5jkTpump");
   const PERCOLATOR_INSURANCE_VAULT = new PublicKey("63juJmvm1XHCHveWv9WdanxqJX6tD6DLFTZD7dvH12dc");
 
+  const instanceSeed = (id: number) => new anchor.BN(id).toArrayLike(Buffer, "le", 8);
+
+  // Mirrors stats_shard_index: payments write to the shard picked by the payer key
+  const STATS_SHARD_COUNT = 16;
+  const statsShardIndex = (payer: PublicKey) => payer.toBytes()[0] % STATS_SHARD_COUNT;
+  const statsShardPda = (config: PublicKey, payer: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("stats_shard"), config.toBuffer(), Buffer.from([statsShardIndex(payer)])],
+      program.programId
+    )[0];
+
+  const userStatsPda = (config: PublicKey, user: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("user_stats"), config.toBuffer(), user.toBuffer()],
+      program.programId
+    )[0];
+
+  const [registryPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("registry")],
+    program.programId
+  );
+  // Router instance #0 is the SAKURA deployment
+  const [configPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("config"), instanceSeed(0)],
+    program.programId
+  );
+
+  const subscriptionPda = (config: PublicKey, user: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("subscription"), config.toBuffer(), user.toBuffer()],
+      program.programId
+    )[0];
+
+  // Bits 0-19 of the packed word hold the expiry in whole days
+  const expiresAtOf = async (subscription: PublicKey) =>
+    ((await program.account.subscription.fetch(subscription)).packed & 0xfffff) * 24 * 60 * 60;
+
+  // The optional process_payment accounts a plain SPL payment leaves out
+  const plainPaymentAccounts = {
+    slabRegistry: null,
+    pendingBurnVault: null,
+    routingTable: null,
+    beneficiary: null,
+    attestation: null,
+    plan: null,
+    promo: null,
+    priceUpdate: null,
+    nftDiscount: null,
+    nftTokenAccount: null,
+    nftMetadata: null,
+    referrer: null,
+    referrerTokenAccount: null,
+    receipt: null,
+    paymentReference: null,
+    stakingVault: null,
+    receiptTree: null,
+    merkleTree: null,
+    treeConfig: null,
+    treeDelegate: null,
+    bubblegumProgram: null,
+    logWrapper: null,
+    compressionProgram: null,
+    soulboundMint: null,
+    soulboundAccount: null,
+    soulboundAuthority: null,
+    token2022Program: null,
+    associatedTokenProgram: null,
+    memoProgram: null,
+    instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+    tokenProgram: TOKEN_PROGRAM_ID,
+    systemProgram: SystemProgram.programId,
+  };
+
+  // A fresh router instance on its own mint with a funded payer, administered
+  // by the provider wallet
+  const createFundedInstance = async (insuranceBps: number, burnBps: number, duration: number) => {
+    const projectAuth = anchor.web3.Keypair.generate();
+    const payer = anchor.web3.Keypair.generate();
+    for (const wallet of [projectAuth, payer]) {
+      const sig = await provider.connection.requestAirdrop(wallet.publicKey, 1000000000);
+      await provider.connection.confirmTransaction(sig);
+    }
+
+    const mint = await createMint(provider.connection, projectAuth, projectAuth.publicKey, null, 6);
+    const vault = await createAccount(provider.connection, projectAuth, mint, projectAuth.publicKey);
+    const payerAccount = await createAccount(provider.connection, payer, mint, payer.publicKey);
+    await mintTo(provider.connection, projectAuth, mint, payerAccount, projectAuth, 10_000_000);
+
+    const registry = await program.account.routerRegistry.fetch(registryPda);
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), instanceSeed(registry.instanceCount.toNumber())],
+      program.programId
+    );
+    await program.methods
+      .createInstance(insuranceBps, burnBps, new anchor.BN(duration))
+      .accounts({
+        admin: user.publicKey,
+        registry: registryPda,
+        config: config,
+        mint: mint,
+        insuranceVault: vault,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    await program.methods
+      .initStatsShard(statsShardIndex(payer.publicKey))
+      .accounts({
+        payer: payer.publicKey,
+        config: config,
+        statsShard: statsShardPda(config, payer.publicKey),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer])
+      .rpc();
+
+    return { config, mint, vault, projectAuth, payer, payerAccount };
+  };
+
+  type FundedInstance = Awaited<ReturnType<typeof createFundedInstance>>;
+
+  // Moves the instance payer's remaining time to `to`, closing their subscription
+  const adminTransfer = (instance: FundedInstance, to: PublicKey) =>
+    program.methods
+      .adminTransferSubscription()
+      .accounts({
+        admin: user.publicKey,
+        config: instance.config,
+        from: instance.payer.publicKey,
+        to: to,
+        fromSubscription: subscriptionPda(instance.config, instance.payer.publicKey),
+        toSubscription: subscriptionPda(instance.config, to),
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+  // Receipts are numbered by the payer's lifetime payment count
+  const receiptPda = (config: PublicKey, user: PublicKey, paymentIndex: number) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("receipt"),
+        config.toBuffer(),
+        user.toBuffer(),
+        new anchor.BN(paymentIndex).toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+
+  // A plain process_payment from the instance's payer; `accounts` overrides
+  // any of the defaults
+  const pay = (
+    instance: FundedInstance,
+    amount: number,
+    accounts: Record<string, PublicKey | null> = {},
+    reference: number[] | null = null
+  ) =>
+    program.methods
+      .processPayment(new anchor.BN(amount), null, false, null, null, reference)
+      .accounts({
+        ...plainPaymentAccounts,
+        user: instance.payer.publicKey,
+        config: instance.config,
+        userTokenAccount: instance.payerAccount,
+        insuranceVault: instance.vault,
+        mint: instance.mint,
+        subscription: subscriptionPda(instance.config, instance.payer.publicKey),
+        statsShard: statsShardPda(instance.config, instance.payer.publicKey),
+        userStats: userStatsPda(instance.config, instance.payer.publicKey),
+        ...accounts,
+      })
+      .signers([instance.payer])
+      .rpc();
+
+  // The same payment pulled by the instance's pay delegate, approved for it
+  const payDelegated = async (instance: FundedInstance, amount: number) => {
+    const payer = instance.payer;
+    const [delegate] = PublicKey.findProgramAddressSync(
+      [Buffer.from("pay_delegate"), instance.config.toBuffer()],
+      program.programId
+    );
+    await approve(provider.connection, payer, instance.payerAccount, delegate, payer, amount);
+    return program.methods
+      .processDelegatedPayment(new anchor.BN(amount), false)
+      .accounts({
+        user: payer.publicKey,
+        config: instance.config,
+        userTokenAccount: instance.payerAccount,
+        insuranceVault: instance.vault,
+        mint: instance.mint,
+        delegate: delegate,
+        subscription: subscriptionPda(instance.config, payer.publicKey),
+        statsShard: statsShardPda(instance.config, payer.publicKey),
+        userStats: userStatsPda(instance.config, payer.publicKey),
+        attestation: null,
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer])
+      .rpc();
+  };
+
+  before(async () => {
+    // The deploying wallet is the upgrade authority, so it may claim the admin role
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+
+    await program.methods
+      .initializeConfig()
+      .accounts({
+        admin: user.publicKey,
+        registry: registryPda,
+        config: configPda,
+        program: program.programId,
+        programData: programData,
+        // Mainnet builds pin instance #0 to the SAKURA mint and vault
+        mint: null,
+        insuranceVault: null,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    await program.methods
+      .initStatsShard(statsShardIndex(user.publicKey))
+      .accounts({
+        payer: user.publicKey,
+        config: configPda,
+        statsShard: statsShardPda(configPda, user.publicKey),
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  });
+
   it("Is initialized!", async () => {
     // Tests environment setup
     assert.ok(program.programId);
@@ -65,21 +310,23 @@ describe("sakura_fee_router", () => {
         );
 
         const [subscriptionPda] = PublicKey.findProgramAddressSync(
-            [Buffer.from("subscription"), user.publicKey.toBuffer()],
+            [Buffer.from("subscription"), configPda.toBuffer(), user.publicKey.toBuffer()],
             program.programId
         );
 
         // This should fail because the mint is not SAKURA_MINT
         await program.methods
-            .processPayment(new anchor.BN(100_000))
+            .processPayment(new anchor.BN(100_000), null, false, null, null, null)
             .accounts({
+                ...plainPaymentAccounts,
                 user: user.publicKey,
+                config: configPda,
                 userTokenAccount: userTokenAccount,
                 insuranceVault: insuranceVault,
                 mint: fakeMint,
                 subscription: subscriptionPda,
-                tokenProgram: TOKEN_PROGRAM_ID,
-                systemProgram: SystemProgram.programId,
+                statsShard: statsShardPda(configPda, user.publicKey),
+                userStats: userStatsPda(configPda, user.publicKey),
             })
             .rpc();
         
@@ -101,20 +348,22 @@ describe("sakura_fee_router", () => {
       
       try {
           const [subscriptionPda] = PublicKey.findProgramAddressSync(
-              [Buffer.from("subscription"), user.publicKey.toBuffer()],
+              [Buffer.from("subscription"), configPda.toBuffer(), user.publicKey.toBuffer()],
               program.programId
           );
 
           await program.methods
-            .processPayment(new anchor.BN(100_000))
+            .processPayment(new anchor.BN(100_000), null, false, null, null, null)
             .accounts({
+                ...plainPaymentAccounts,
                 user: user.publicKey,
+                config: configPda,
                 userTokenAccount: fakeVault, // fake
                 insuranceVault: fakeVault,   // this should trigger InvalidVault
                 mint: SAKURA_MINT, 
                 subscription: subscriptionPda,
-                tokenProgram: TOKEN_PROGRAM_ID,
-                systemProgram: SystemProgram.programId,
+                statsShard: statsShardPda(configPda, user.publicKey),
+                userStats: userStatsPda(configPda, user.publicKey),
             })
             .rpc();
 
@@ -124,6 +373,272 @@ describe("sakura_fee_router", () => {
       }
   });
 
+  it("Rejects enabling compliance mode without an attestor", async () => {
+      try {
+          await program.methods
+              .setCompliance({ ed25519Attestation: {} }, PublicKey.default)
+              .accounts({
+                  admin: user.publicKey,
+                  config: configPda,
+              })
+              .rpc();
+
+          assert.fail("Should have failed due to missing attestor");
+      } catch (e: any) {
+          assert.include(e.message, "Compliance mode requires a non-default attestor key");
+      }
+  });
+
+  it("Publishes the promo-adjusted price in the price feed", async () => {
+      const [priceFeedPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("price_feed"), configPda.toBuffer()],
+          program.programId
+      );
+      const promoEndsAt = Math.floor(Date.now() / 1000) + 24 * 60 * 60;
+
+      await program.methods
+          .setPricing(new anchor.BN(100_000), 2500, new anchor.BN(promoEndsAt))
+          .accounts({
+              admin: user.publicKey,
+              config: configPda,
+              priceFeed: priceFeedPda,
+              systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+      const feed = await program.account.priceFeed.fetch(priceFeedPda);
+      assert.equal(feed.price.toNumber(), 100_000);
+      assert.equal(feed.effectivePrice.toNumber(), 75_000);
+      assert.equal(feed.promoBps, 2500);
+      assert.equal(feed.insuranceBps, 5000);
+  });
+
+  it("Creates a white-label router instance with its own mint and split", async () => {
+      const projectAuth = anchor.web3.Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(projectAuth.publicKey, 1000000000);
+      await provider.connection.confirmTransaction(sig);
+
+      const projectMint = await createMint(
+          provider.connection,
+          projectAuth,
+          projectAuth.publicKey,
+          null,
+          6
+      );
+      const projectVault = await createAccount(
+          provider.connection,
+          projectAuth,
+          projectMint,
+          projectAuth.publicKey
+      );
+
+      const registry = await program.account.routerRegistry.fetch(registryPda);
+      const [instancePda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("config"), instanceSeed(registry.instanceCount.toNumber())],
+          program.programId
+      );
+
+      await program.methods
+          .createInstance(7000, 3000, new anchor.BN(7 * 24 * 60 * 60))
+          .accounts({
+              admin: user.publicKey,
+              registry: registryPda,
+              config: instancePda,
+              mint: projectMint,
+              insuranceVault: projectVault,
+              systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+      const instance = await program.account.config.fetch(instancePda);
+      assert.ok(instance.mint.equals(projectMint));
+      assert.ok(instance.insuranceVault.equals(projectVault));
+      assert.ok(instance.vaultAuthority.equals(projectAuth.publicKey));
+      assert.equal(instance.insuranceBps, 7000);
+      assert.equal(instance.burnBps, 3000);
+  });
+
+  it("Routes CPI-guarded Token-2022 accounts through the delegate flow", async () => {
+      const projectAuth = anchor.web3.Keypair.generate();
+      const payer = anchor.web3.Keypair.generate();
+      for (const wallet of [projectAuth, payer]) {
+          const sig = await provider.connection.requestAirdrop(wallet.publicKey, 1000000000);
+          await provider.connection.confirmTransaction(sig);
+      }
+
+      const mint = await createMint(
+          provider.connection,
+          projectAuth,
+          projectAuth.publicKey,
+          null,
+          6,
+          undefined,
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+      );
+      const vault = await createAssociatedTokenAccount(
+          provider.connection,
+          projectAuth,
+          mint,
+          projectAuth.publicKey,
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+      );
+      const payerAccount = await createAssociatedTokenAccount(
+          provider.connection,
+          payer,
+          mint,
+          payer.publicKey,
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+      );
+      await mintTo(provider.connection, projectAuth, mint, payerAccount, projectAuth, 1_000_000, [], undefined, TOKEN_2022_PROGRAM_ID);
+      await reallocate(provider.connection, payer, payerAccount, payer, [ExtensionType.CpiGuard], [], undefined, TOKEN_2022_PROGRAM_ID);
+      await enableCpiGuard(provider.connection, payer, payerAccount, payer, [], undefined, TOKEN_2022_PROGRAM_ID);
+
+      const registry = await program.account.routerRegistry.fetch(registryPda);
+      const [instancePda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("config"), instanceSeed(registry.instanceCount.toNumber())],
+          program.programId
+      );
+      await program.methods
+          .createInstance(5000, 5000, new anchor.BN(30 * 24 * 60 * 60))
+          .accounts({
+              admin: user.publicKey,
+              registry: registryPda,
+              config: instancePda,
+              mint: mint,
+              insuranceVault: vault,
+              systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+      await program.methods
+          .initStatsShard(statsShardIndex(payer.publicKey))
+          .accounts({
+              payer: payer.publicKey,
+              config: instancePda,
+              statsShard: statsShardPda(instancePda, payer.publicKey),
+              systemProgram: SystemProgram.programId,
+          })
+          .signers([payer])
+          .rpc();
+
+      const [subscriptionPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("subscription"), instancePda.toBuffer(), payer.publicKey.toBuffer()],
+          program.programId
+      );
+      const [delegatePda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("pay_delegate"), instancePda.toBuffer()],
+          program.programId
+      );
+      const paymentAccounts = {
+          user: payer.publicKey,
+          config: instancePda,
+          userTokenAccount: payerAccount,
+          insuranceVault: vault,
+          mint: mint,
+          subscription: subscriptionPda,
+          statsShard: statsShardPda(instancePda, payer.publicKey),
+          userStats: userStatsPda(instancePda, payer.publicKey),
+          attestation: null,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+      };
+
+      // The pre-flight surfaces the same failure without the payer signing
+      try {
+          await program.methods
+              .validatePayment(new anchor.BN(100_000), false, null)
+              .accounts({
+                  user: payer.publicKey,
+                  config: instancePda,
+                  userTokenAccount: payerAccount,
+                  insuranceVault: vault,
+                  mint: mint,
+                  subscription: null,
+                  userStats: null,
+                  attestation: null,
+                  plan: null,
+                  promo: null,
+                  priceUpdate: null,
+                  nftDiscount: null,
+                  nftTokenAccount: null,
+                  nftMetadata: null,
+                  instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+                  tokenProgram: TOKEN_2022_PROGRAM_ID,
+              })
+              .rpc();
+
+          assert.fail("Pre-flight should have failed due to CPI guard");
+      } catch (e: any) {
+          assert.include(e.message, "CPI guard enabled");
+      }
+
+      try {
+          await program.methods
+              .processPayment(new anchor.BN(100_000), null, false, null, null, null)
+              .accounts({
+                  ...paymentAccounts,
+                  slabRegistry: null,
+                  pendingBurnVault: null,
+                  routingTable: null,
+                  beneficiary: null,
+                  plan: null,
+                  promo: null,
+                  priceUpdate: null,
+                  nftDiscount: null,
+                  nftTokenAccount: null,
+                  nftMetadata: null,
+                  referrer: null,
+                  referrerTokenAccount: null,
+                  receipt: null,
+                  receiptTree: null,
+                  merkleTree: null,
+                  treeConfig: null,
+                  treeDelegate: null,
+                  bubblegumProgram: null,
+                  logWrapper: null,
+                  compressionProgram: null,
+                  soulboundMint: null,
+                  soulboundAccount: null,
+                  soulboundAuthority: null,
+                  token2022Program: null,
+                  associatedTokenProgram: null,
+                  memoProgram: null,
+              })
+              .signers([payer])
+              .rpc();
+
+          assert.fail("Should have failed due to CPI guard");
+      } catch (e: any) {
+          assert.include(e.message, "CPI guard enabled");
+      }
+
+      await approve(provider.connection, payer, payerAccount, delegatePda, payer, 100_000, [], undefined, TOKEN_2022_PROGRAM_ID);
+      await program.methods
+          .processDelegatedPayment(new anchor.BN(100_000), false)
+          .accounts({ ...paymentAccounts, delegate: delegatePda })
+          .signers([payer])
+          .rpc();
+
+      const vaultAccount = await getAccount(provider.connection, vault, undefined, TOKEN_2022_PROGRAM_ID);
+      assert.equal(Number(vaultAccount.amount), 50_000);
+      const subscription = await program.account.subscription.fetch(subscriptionPda);
+      // Bits 0-19 of the packed word hold the expiry in whole days
+      const expiresAt = (subscription.packed & 0xfffff) * 24 * 60 * 60;
+      assert.ok(expiresAt > Date.now() / 1000);
+
+      const shard = await program.account.statsShard.fetch(statsShardPda(instancePda, payer.publicKey));
+      assert.equal(shard.payments.toNumber(), 1);
+      assert.equal(shard.burned.toNumber(), 50_000);
+      assert.equal(shard.subscribers.toNumber(), 1);
+
+      const userStats = await program.account.userStats.fetch(userStatsPda(instancePda, payer.publicKey));
+      assert.equal(userStats.lifetimeBurned.toNumber(), 50_000);
+  });
+
   it("Checks SPL split and burn logic mathematically", async () => {
       // In a real environment with a cloned SAKURA_MINT and the REAL insurance vault, 
       // we would verify that calling processPayment with 10_000 tokens results in:
@@ -136,4 +651,538 @@ describe("sakura_fee_router", () => {
       console.log("SPL split and burn logic validation outlined for mainnet-fork testing.");
   });
 
+  it("Keeps stream funding cranks off an auto-renew allowance", async () => {
+      const instance = await createFundedInstance(5000, 5000, 30 * 24 * 60 * 60);
+      const payer = instance.payer.publicKey;
+      const [priceFeedPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("price_feed"), instance.config.toBuffer()],
+          program.programId
+      );
+      await program.methods
+          .setPricing(new anchor.BN(100_000), 0, new anchor.BN(0))
+          .accounts({
+              admin: user.publicKey,
+              config: instance.config,
+              priceFeed: priceFeedPda,
+              systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+      const streamDelegate = PublicKey.findProgramAddressSync(
+          [Buffer.from("stream_delegate"), instance.config.toBuffer()],
+          program.programId
+      )[0];
+      const renewDelegate = PublicKey.findProgramAddressSync(
+          [Buffer.from("renew_delegate"), instance.config.toBuffer()],
+          program.programId
+      )[0];
+      const streamFundingPda = PublicKey.findProgramAddressSync(
+          [Buffer.from("stream_funding"), instance.config.toBuffer(), payer.toBuffer()],
+          program.programId
+      )[0];
+
+      await program.methods
+          .registerStreamFunding(new anchor.BN(100_000), new anchor.BN(100_000))
+          .accounts({
+              user: payer,
+              config: instance.config,
+              stream: anchor.web3.Keypair.generate().publicKey,
+              streamFunding: streamFundingPda,
+              subscription: subscriptionPda(instance.config, payer),
+              userTokenAccount: instance.payerAccount,
+              delegate: streamDelegate,
+              tokenProgram: TOKEN_PROGRAM_ID,
+              systemProgram: SystemProgram.programId,
+          })
+          .signers([instance.payer])
+          .rpc();
+
+      // The token account now approves the renewal delegate for three periods
+      await program.methods
+          .enableAutoRenew(3)
+          .accounts({
+              user: payer,
+              config: instance.config,
+              autoRenew: PublicKey.findProgramAddressSync(
+                  [Buffer.from("auto_renew"), instance.config.toBuffer(), payer.toBuffer()],
+                  program.programId
+              )[0],
+              subscription: subscriptionPda(instance.config, payer),
+              plan: null,
+              userTokenAccount: instance.payerAccount,
+              delegate: renewDelegate,
+              tokenProgram: TOKEN_PROGRAM_ID,
+              systemProgram: SystemProgram.programId,
+          })
+          .signers([instance.payer])
+          .rpc();
+
+      try {
+          await program.methods
+              .crankStreamFunding()
+              .accounts({
+                  cranker: user.publicKey,
+                  config: instance.config,
+                  streamFunding: streamFundingPda,
+                  subscription: subscriptionPda(instance.config, payer),
+                  userTokenAccount: instance.payerAccount,
+                  insuranceVault: instance.vault,
+                  mint: instance.mint,
+                  delegate: streamDelegate,
+                  streamProgram: null,
+                  tokenProgram: TOKEN_PROGRAM_ID,
+              })
+              .rpc();
+          assert.fail("Should have failed without a stream allowance");
+      } catch (e: any) {
+          // SPL Token's OwnerMismatch: the stream delegate isn't the approved one
+          assert.include(e.message, "0x4");
+      }
+
+      const payerAccount = await getAccount(provider.connection, instance.payerAccount);
+      assert.equal(Number(payerAccount.amount), 10_000_000);
+      assert.ok(payerAccount.delegate.equals(renewDelegate));
+      assert.equal(Number(payerAccount.delegatedAmount), 300_000);
+  });
+
+  it("Keeps a subscription open while its payment interval runs", async () => {
+      const instance = await createFundedInstance(5000, 5000, 30 * 24 * 60 * 60);
+      await program.methods
+          .setPaymentLimits(new anchor.BN(0), new anchor.BN(1_000_000))
+          .accounts({ admin: user.publicKey, config: instance.config })
+          .rpc();
+      await pay(instance, 100_000);
+
+      // Closing it now would hand the wallet a fresh subscription without the limit
+      try {
+          await adminTransfer(instance, anchor.web3.Keypair.generate().publicKey);
+          assert.fail("Should have failed while the payment interval runs");
+      } catch (e: any) {
+          assert.include(e.message, "Too soon after this subscription's last payment");
+      }
+  });
+
+  it("Keeps numbering receipts after the subscription is closed and reopened", async () => {
+      const instance = await createFundedInstance(5000, 5000, 30 * 24 * 60 * 60);
+      const payer = instance.payer.publicKey;
+      await program.methods
+          .setRequireReceipts(true)
+          .accounts({ admin: user.publicKey, config: instance.config })
+          .rpc();
+
+      await pay(instance, 100_000, { receipt: receiptPda(instance.config, payer, 0) });
+
+      // Moving the time away closes the payer's subscription
+      await adminTransfer(instance, anchor.web3.Keypair.generate().publicKey);
+      assert.isNull(await provider.connection.getAccountInfo(subscriptionPda(instance.config, payer)));
+
+      // The reopened subscription starts over, the receipt numbering doesn't
+      await pay(instance, 100_000, { receipt: receiptPda(instance.config, payer, 1) });
+
+      const first = await program.account.receipt.fetch(receiptPda(instance.config, payer, 0));
+      const second = await program.account.receipt.fetch(receiptPda(instance.config, payer, 1));
+      assert.equal(first.paymentIndex.toNumber(), 0);
+      assert.equal(second.paymentIndex.toNumber(), 1);
+  });
+
+  it("Counts wSOL payments in the payer's stats shard", async () => {
+      const instance = await createFundedInstance(5000, 5000, 30 * 24 * 60 * 60);
+      const payer = instance.payer;
+      const solVault = await createWrappedNativeAccount(
+          provider.connection,
+          instance.projectAuth,
+          instance.projectAuth.publicKey,
+          0
+      );
+      const userSolAccount = await createWrappedNativeAccount(
+          provider.connection,
+          payer,
+          payer.publicKey,
+          200_000_000
+      );
+      const [buybackAuthority] = PublicKey.findProgramAddressSync(
+          [Buffer.from("buyback"), instance.config.toBuffer()],
+          program.programId
+      );
+      const buybackVault = await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          payer,
+          NATIVE_MINT,
+          buybackAuthority,
+          true
+      );
+
+      await program.methods
+          .setSolPayments(new anchor.BN(100_000_000))
+          .accounts({ admin: user.publicKey, config: instance.config, solInsuranceVault: solVault })
+          .rpc();
+      await program.methods
+          .processPaymentSol(new anchor.BN(100_000_000), false)
+          .accounts({
+              user: payer.publicKey,
+              config: instance.config,
+              nativeMint: NATIVE_MINT,
+              userSolAccount: userSolAccount,
+              solInsuranceVault: solVault,
+              buybackAuthority: buybackAuthority,
+              buybackVault: buybackVault.address,
+              subscription: subscriptionPda(instance.config, payer.publicKey),
+              statsShard: statsShardPda(instance.config, payer.publicKey),
+              instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+              tokenProgram: TOKEN_PROGRAM_ID,
+              systemProgram: SystemProgram.programId,
+          })
+          .signers([payer])
+          .rpc();
+
+      // The burn share waits in the buyback escrow, so nothing is burned yet
+      const shard = await program.account.statsShard.fetch(statsShardPda(instance.config, payer.publicKey));
+      assert.equal(shard.payments.toNumber(), 1);
+      assert.equal(shard.subscribers.toNumber(), 1);
+      assert.equal(shard.burned.toNumber(), 0);
+      const escrow = await getAccount(provider.connection, buybackVault.address);
+      assert.equal(Number(escrow.amount), 50_000_000);
+  });
+
+  it("Rolls a refund back at the price the payment was made at", async () => {
+      const day = 24 * 60 * 60;
+      const instance = await createFundedInstance(5000, 5000, 30 * day);
+      const payer = instance.payer.publicKey;
+      const [escrowAuthority] = PublicKey.findProgramAddressSync(
+          [Buffer.from("insurance_escrow"), instance.config.toBuffer()],
+          program.programId
+      );
+      const escrow = await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          instance.projectAuth,
+          instance.mint,
+          escrowAuthority,
+          true
+      );
+      await program.methods
+          .setInsuranceEscrow(true)
+          .accounts({
+              admin: user.publicKey,
+              config: instance.config,
+              escrowAuthority: escrowAuthority,
+              insuranceEscrow: escrow.address,
+          })
+          .rpc();
+
+      const receipt = receiptPda(instance.config, payer, 0);
+      await pay(instance, 100_000, { insuranceVault: escrow.address, receipt: receipt });
+      const paidUntil = await expiresAtOf(subscriptionPda(instance.config, payer));
+
+      // A later price change doesn't change what the refunded payment bought
+      const [priceFeedPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("price_feed"), instance.config.toBuffer()],
+          program.programId
+      );
+      await program.methods
+          .setPricing(new anchor.BN(400_000), 0, new anchor.BN(0))
+          .accounts({
+              admin: user.publicKey,
+              config: instance.config,
+              priceFeed: priceFeedPda,
+              systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+      const refund = (amount: number) =>
+        program.methods
+          .refund(new anchor.BN(amount))
+          .accounts({
+              admin: user.publicKey,
+              config: instance.config,
+              escrowAuthority: escrowAuthority,
+              insuranceEscrow: escrow.address,
+              user: payer,
+              subscription: subscriptionPda(instance.config, payer),
+              receipt: receipt,
+              userTokenAccount: instance.payerAccount,
+              mint: instance.mint,
+              tokenProgram: TOKEN_PROGRAM_ID,
+              outbox: null,
+          })
+          .rpc();
+      await refund(25_000);
+
+      // A quarter of the 100_000 paid takes back 7.5 of its 30 days, kept to whole days
+      const expiresAt = await expiresAtOf(subscriptionPda(instance.config, payer));
+      assert.equal(paidUntil - expiresAt, 7 * day);
+      const refunded = await program.account.receipt.fetch(receipt);
+      assert.equal(refunded.refunded.toNumber(), 25_000);
+
+      // Once swept, the escrow holds other payers' money, not this payment's
+      await program.methods
+          .sweepInsuranceEscrow()
+          .accounts({
+              config: instance.config,
+              escrowAuthority: escrowAuthority,
+              insuranceEscrow: escrow.address,
+              insuranceVault: instance.vault,
+              mint: instance.mint,
+              tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+      try {
+          await refund(1_000);
+          assert.fail("Should have rejected a refund of swept escrow");
+      } catch (e: any) {
+          assert.include(e.message, "This payment's insurance share isn't in the escrow to refund");
+      }
+  });
+
+  it("Clamps prepaid time to the last day boundary under the cap", async () => {
+      const day = 24 * 60 * 60;
+      const instance = await createFundedInstance(5000, 5000, 30 * day);
+      await program.methods
+          .setPrepaidCap(new anchor.BN(45 * day), true)
+          .accounts({ admin: user.publicKey, config: instance.config })
+          .rpc();
+
+      await pay(instance, 100_000);
+      await pay(instance, 100_000);
+
+      // The second period is cut short instead of rounding past the cap
+      const expiresAt = await expiresAtOf(subscriptionPda(instance.config, instance.payer.publicKey));
+      assert.ok(expiresAt <= Date.now() / 1000 + 45 * day + 60);
+      assert.ok(expiresAt > Date.now() / 1000 + 44 * day - 60);
+  });
+
+  it("Holds delegated payments to the prepaid cap", async () => {
+      const day = 24 * 60 * 60;
+      const instance = await createFundedInstance(5000, 5000, 30 * day);
+      await program.methods
+          .setPrepaidCap(new anchor.BN(45 * day), false)
+          .accounts({ admin: user.publicKey, config: instance.config })
+          .rpc();
+      await pay(instance, 100_000);
+
+      try {
+          await payDelegated(instance, 100_000);
+          assert.fail("Should have rejected a second period past the cap");
+      } catch (e: any) {
+          assert.include(e.message, "Payment would prepay past the instance's cap");
+      }
+  });
+
+  it("Charges seats added mid-period for the rest of the organization's period", async () => {
+      const day = 24 * 60 * 60;
+      const instance = await createFundedInstance(5000, 5000, 30 * day);
+      const owner = instance.payer;
+      const [plan] = PublicKey.findProgramAddressSync(
+          [Buffer.from("plan"), instance.config.toBuffer(), instanceSeed(1)],
+          program.programId
+      );
+      const [organization] = PublicKey.findProgramAddressSync(
+          [Buffer.from("organization"), instance.config.toBuffer(), owner.publicKey.toBuffer()],
+          program.programId
+      );
+      await program.methods
+          .createPlan(new anchor.BN(1), new anchor.BN(30 * day), new anchor.BN(10_000), new anchor.BN(0))
+          .accounts({
+              admin: user.publicKey,
+              config: instance.config,
+              plan: plan,
+              systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      await program.methods
+          .createOrganization()
+          .accounts({
+              owner: owner.publicKey,
+              config: instance.config,
+              organization: organization,
+              plan: plan,
+              systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+
+      const buySeats = (seats: number) =>
+          program.methods
+              .processOrgPayment(seats)
+              .accounts({
+                  owner: owner.publicKey,
+                  config: instance.config,
+                  organization: organization,
+                  plan: plan,
+                  ownerTokenAccount: instance.payerAccount,
+                  insuranceVault: instance.vault,
+                  mint: instance.mint,
+                  statsShard: statsShardPda(instance.config, owner.publicKey),
+                  instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+                  tokenProgram: TOKEN_PROGRAM_ID,
+              })
+              .signers([owner])
+              .rpc();
+      const balance = async () => Number((await getAccount(provider.connection, instance.payerAccount)).amount);
+
+      const initial = await balance();
+      await buySeats(3);
+      assert.equal(initial - (await balance()), 30_000);
+
+      // Two more seats: a period for all five, plus the two new ones for
+      // the almost full period still running
+      const paidBefore = await balance();
+      await buySeats(5);
+      const charged = paidBefore - (await balance());
+      assert.ok(charged <= 70_000 && charged > 69_900);
+      const org = await program.account.organization.fetch(organization);
+      assert.equal(org.seats, 5);
+  });
+
+  it("Moves a subscription's remaining time to the wallet that accepts it", async () => {
+      const instance = await createFundedInstance(5000, 5000, 30 * 24 * 60 * 60);
+      const from = instance.payer.publicKey;
+      const next = anchor.web3.Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(next.publicKey, 1000000000);
+      await provider.connection.confirmTransaction(sig);
+
+      await pay(instance, 100_000);
+      const paidUntil = await expiresAtOf(subscriptionPda(instance.config, from));
+      const [transfer] = PublicKey.findProgramAddressSync(
+          [Buffer.from("subscription_transfer"), instance.config.toBuffer(), from.toBuffer()],
+          program.programId
+      );
+
+      await program.methods
+          .initiateTransfer(next.publicKey)
+          .accounts({
+              user: from,
+              config: instance.config,
+              subscription: subscriptionPda(instance.config, from),
+              subscriptionTransfer: transfer,
+              systemProgram: SystemProgram.programId,
+          })
+          .signers([instance.payer])
+          .rpc();
+      await program.methods
+          .acceptTransfer()
+          .accounts({
+              to: next.publicKey,
+              config: instance.config,
+              from: from,
+              subscriptionTransfer: transfer,
+              fromSubscription: subscriptionPda(instance.config, from),
+              toSubscription: subscriptionPda(instance.config, next.publicKey),
+              systemProgram: SystemProgram.programId,
+          })
+          .signers([next])
+          .rpc();
+
+      // The old wallet's subscription and the pending transfer are both closed
+      assert.isNull(await provider.connection.getAccountInfo(subscriptionPda(instance.config, from)));
+      assert.isNull(await provider.connection.getAccountInfo(transfer));
+      assert.equal(await expiresAtOf(subscriptionPda(instance.config, next.publicKey)), paidUntil);
+  });
+
+  it("Rejects a second payment with the same reference", async () => {
+      const instance = await createFundedInstance(5000, 5000, 30 * 24 * 60 * 60);
+      const reference = Array(32).fill(7);
+      const [paymentReference] = PublicKey.findProgramAddressSync(
+          [
+              Buffer.from("payment_reference"),
+              instance.config.toBuffer(),
+              instance.payer.publicKey.toBuffer(),
+              Buffer.from(reference),
+          ],
+          program.programId
+      );
+
+      await pay(instance, 100_000, { paymentReference: paymentReference }, reference);
+      // A backend retrying the same checkout must not charge twice
+      try {
+          await pay(instance, 100_000, { paymentReference: paymentReference }, reference);
+          assert.fail("Should have rejected the duplicate reference");
+      } catch (e: any) {
+          assert.include(e.message, "This reference has already been paid");
+      }
+      const paid = await program.account.paymentReference.fetch(paymentReference);
+      assert.equal(paid.amount.toNumber(), 100_000);
+  });
+
+  it("Rejects dust payments and payments inside the payment interval", async () => {
+      const instance = await createFundedInstance(5000, 5000, 30 * 24 * 60 * 60);
+      await program.methods
+          .setPaymentLimits(new anchor.BN(50_000), new anchor.BN(1_000_000))
+          .accounts({ admin: user.publicKey, config: instance.config })
+          .rpc();
+
+      try {
+          await pay(instance, 10_000);
+          assert.fail("Should have rejected a payment under the minimum");
+      } catch (e: any) {
+          assert.include(e.message, "Payment is below the instance minimum");
+      }
+
+      await pay(instance, 50_000);
+      try {
+          await pay(instance, 50_000);
+          assert.fail("Should have rejected a payment inside the interval");
+      } catch (e: any) {
+          assert.include(e.message, "Too soon after this subscription's last payment");
+      }
+
+      // A delegated payment is held to the same limits
+      try {
+          await payDelegated(instance, 10_000);
+          assert.fail("Should have rejected a delegated payment under the minimum");
+      } catch (e: any) {
+          assert.include(e.message, "Payment is below the instance minimum");
+      }
+      try {
+          await payDelegated(instance, 50_000);
+          assert.fail("Should have rejected a delegated payment inside the interval");
+      } catch (e: any) {
+          assert.include(e.message, "Too soon after this subscription's last payment");
+      }
+  });
+
+  it("Blocks payments once the router is sunset", async () => {
+      const instance = await createFundedInstance(5000, 5000, 30 * 24 * 60 * 60);
+      await pay(instance, 100_000);
+
+      const successor = anchor.web3.Keypair.generate().publicKey;
+      await program.methods
+          .setSunset(successor, new anchor.BN(Math.floor(Date.now() / 1000) - 60))
+          .accounts({ admin: user.publicKey, config: instance.config })
+          .rpc();
+
+      try {
+          await pay(instance, 100_000);
+          assert.fail("Should have failed after the sunset");
+      } catch (e: any) {
+          assert.include(e.message, "This router has been sunset");
+      }
+      const config = await program.account.config.fetch(instance.config);
+      assert.ok(config.successorProgram.equals(successor));
+  });
+
+  it("Pays the staking part of the burn share to the staking vault", async () => {
+      const instance = await createFundedInstance(5000, 5000, 30 * 24 * 60 * 60);
+      const stakingVault = await createAccount(
+          provider.connection,
+          instance.projectAuth,
+          instance.mint,
+          anchor.web3.Keypair.generate().publicKey
+      );
+      await program.methods
+          .setBurnMode({ splitWithStaking: { stakingBps: 2500 } })
+          .accounts({ admin: user.publicKey, config: instance.config, stakingVault: stakingVault })
+          .rpc();
+
+      const supplyBefore = (await getMint(provider.connection, instance.mint)).supply;
+      await pay(instance, 100_000, { stakingVault: stakingVault });
+
+      // 50_000 insured; of the 50_000 burn share a quarter is staked, the rest burned
+      const insured = await getAccount(provider.connection, instance.vault);
+      const staked = await getAccount(provider.connection, stakingVault);
+      const supplyAfter = (await getMint(provider.connection, instance.mint)).supply;
+      assert.equal(Number(insured.amount), 50_000);
+      assert.equal(Number(staked.amount), 12_500);
+      assert.equal(Number(supplyBefore - supplyAfter), 37_500);
+  });
+
 });